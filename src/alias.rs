@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::fs;
+
+use anyhow;
+
+use crate::models::{Metadata, MetadataBook};
+
+/// Aliases are followed up to this many times, longer chains are cycles
+const MAX_CHAIN: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Tag,
+    Artist,
+}
+
+/// # AliasTable
+/// Maps hitomi's tags and artists to Madome's before upload
+///
+/// ```text
+/// # kind name = name of Madome, nothing to drop it
+/// tag lolicon = loli
+/// tag loli = lolicon ♀
+/// tag banned tag =
+/// artist old name = new name
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct AliasTable {
+    /// `None` is banned
+    inner: HashMap<(Kind, String), Option<String>>,
+}
+
+impl AliasTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut table = Self::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = || anyhow::Error::msg(format!("Can't parse alias of line {}", n + 1));
+
+            let (kind, rest) = match line.find(' ') {
+                Some(i) => (&line[..i], &line[i + 1..]),
+                None => return Err(error()),
+            };
+            let kind = match kind {
+                "tag" => Kind::Tag,
+                "artist" => Kind::Artist,
+                _ => return Err(error()),
+            };
+            let (from, to) = match rest.find('=') {
+                Some(i) => (rest[..i].trim(), rest[i + 1..].trim()),
+                None => return Err(error()),
+            };
+
+            if from.is_empty() {
+                return Err(error());
+            }
+
+            let to = Some(to.to_string()).filter(|to| !to.is_empty());
+
+            table.insert(kind, from, to);
+        }
+
+        Ok(table)
+    }
+
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn insert(&mut self, kind: Kind, from: &str, to: Option<String>) {
+        self.inner.insert((kind, from.to_string()), to);
+    }
+
+    /// Follows chained aliases, `None` if banned
+    pub fn resolve(&self, kind: Kind, name: &str) -> anyhow::Result<Option<String>> {
+        let mut name = name.to_string();
+
+        for _ in 0..MAX_CHAIN {
+            match self.inner.get(&(kind, name.clone())) {
+                Some(Some(to)) if *to != name => name = to.clone(),
+                Some(None) => return Ok(None),
+                _ => return Ok(Some(name)),
+            }
+        }
+
+        Err(anyhow::Error::msg(format!(
+            "Alias of `{}` doesn't end, it may be a cycle",
+            name
+        )))
+    }
+
+    /// Merged names are kept once in the first position
+    pub fn apply(&self, kind: Kind, names: Vec<String>) -> anyhow::Result<Vec<String>> {
+        let mut r = Vec::with_capacity(names.len());
+
+        for name in names {
+            if let Some(name) = self.resolve(kind, &name)? {
+                if !r.contains(&name) {
+                    r.push(name);
+                }
+            }
+        }
+
+        Ok(r)
+    }
+
+    pub fn apply_book(&self, book: MetadataBook) -> anyhow::Result<MetadataBook> {
+        if self.inner.is_empty() {
+            return Ok(book);
+        }
+
+        let map = |kind, names: Option<Vec<String>>| -> anyhow::Result<Option<Vec<String>>> {
+            match names {
+                Some(names) => Ok(Some(self.apply(kind, names)?).filter(|x| !x.is_empty())),
+                None => Ok(None),
+            }
+        };
+
+        let tags = match book.tags {
+            Metadata::Tags(tags) => Metadata::Tags(map(Kind::Tag, tags)?),
+            x => x,
+        };
+        let artists = match book.artists {
+            Metadata::Artists(artists) => Metadata::Artists(map(Kind::Artist, artists)?),
+            x => x,
+        };
+
+        Ok(MetadataBook {
+            tags,
+            artists,
+            ..book
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AliasTable, Kind};
+
+    const TABLE: &'static str = "
+        # comment
+        tag lolicon = loli
+        tag loli = lolicon ♀
+        tag banned =
+        tag a = b
+        tag b = a
+        artist old name = new name
+    ";
+
+    #[test]
+    fn resolve_chained_aliases() -> anyhow::Result<()> {
+        let table = AliasTable::parse(TABLE)?;
+
+        assert_eq!(
+            Some("lolicon ♀".to_string()),
+            table.resolve(Kind::Tag, "lolicon")?
+        );
+        assert_eq!(
+            Some("lolicon ♀".to_string()),
+            table.resolve(Kind::Tag, "loli")?
+        );
+        assert_eq!(None, table.resolve(Kind::Tag, "banned")?);
+        assert_eq!(
+            Some("other".to_string()),
+            table.resolve(Kind::Tag, "other")?
+        );
+        assert_eq!(
+            Some("old name".to_string()),
+            table.resolve(Kind::Tag, "old name")?
+        );
+        assert!(table.resolve(Kind::Tag, "a").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn apply_merges_and_drops() -> anyhow::Result<()> {
+        let table = AliasTable::parse(TABLE)?;
+
+        let tags = ["lolicon", "banned", "loli", "other"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec!["lolicon ♀".to_string(), "other".to_string()],
+            table.apply(Kind::Tag, tags)?
+        );
+        assert_eq!(
+            vec!["new name".to_string()],
+            table.apply(Kind::Artist, vec!["old name".to_string()])?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_malformed_line() -> anyhow::Result<()> {
+        assert!(AliasTable::parse("tag lolicon loli").is_err());
+        assert!(AliasTable::parse("group a = b").is_err());
+        assert!(AliasTable::parse("tag = b").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+use anyhow;
+
+use crate::models::{Language, MetadataBook};
+pub use crate::parser::exists;
+use crate::parser::{File, GalleryInfo, Nozomi, Parser, ParserRegistry};
+
+/// Metadata of a gallery from the default sources
+pub fn fetch_metadata(id: u32) -> anyhow::Result<MetadataBook> {
+    ParserRegistry::with_defaults().fetch(id)
+}
+
+/// Gallery ids of `page` in the nozomi index of `language`, newest first
+pub fn fetch_ids(page: usize, per_page: usize, language: Language) -> anyhow::Result<Vec<u32>> {
+    Nozomi::new(page, per_page, language).request()?.parse()
+}
+
+/// Image files of a gallery
+pub fn fetch_files(id: u32) -> anyhow::Result<Vec<File>> {
+    Ok(GalleryInfo::new(id).request()?.parse()?.files)
+}
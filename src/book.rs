@@ -0,0 +1,89 @@
+#[cfg(feature = "net")]
+use anyhow;
+#[cfg(feature = "net")]
+use madome_client::book::Book;
+use serde::{Deserialize, Serialize};
+
+use crate::catalog::Work;
+#[cfg(feature = "net")]
+use crate::parser::ParserRegistry;
+use crate::parser::{GalleryInfoData, Provenance};
+#[cfg(feature = "net")]
+use crate::validate::{validate, Strictness};
+
+/// What `madome_client::book::MetadataBook` has no field for yet,
+/// kept next to the book until the Madome API accepts it
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct BookExtra {
+    /// Smallest gallery id among the translations of the same work
+    pub translation_group: Option<u32>,
+    /// Untranslated title, `title` of the book is translated one
+    #[serde(default)]
+    pub title_original: Option<String>,
+    /// First page of each chapter, from 1
+    #[serde(default)]
+    pub chapters: Option<Vec<usize>>,
+    /// Metadata source of each field, `tags: js`
+    #[serde(default)]
+    pub provenance: Provenance,
+    /// For catalog.json, the book has them already
+    #[serde(skip)]
+    pub work: Option<Work>,
+}
+
+impl BookExtra {
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = provenance;
+        self
+    }
+}
+
+impl From<&GalleryInfoData> for BookExtra {
+    fn from(gallery_info: &GalleryInfoData) -> Self {
+        Self {
+            translation_group: Some(gallery_info.translation_group()),
+            title_original: gallery_info.title().title_original,
+            chapters: gallery_info.chapters(),
+            provenance: Provenance::new(),
+            work: None,
+        }
+    }
+}
+
+/// Book of the gallery of `gallery_info_data` from the metadata sources of `registry`,
+/// `validate::Invalid` if the metadata fails `strictness`
+///
+/// `gallery_info_data` is the one the images were parsed from, it isn't requested again
+#[cfg(feature = "net")]
+pub fn parse_book(
+    gallery_info_data: &GalleryInfoData,
+    registry: &ParserRegistry,
+    strictness: Strictness,
+) -> anyhow::Result<(Book, BookExtra)> {
+    let id = gallery_info_data.id;
+
+    // the html source goes to the content url without the redirect
+    if let (None, Some(content_url)) = (
+        registry.content_urls().get(id),
+        gallery_info_data.content_url(),
+    ) {
+        registry.content_urls().insert(id, content_url);
+    }
+
+    let (metadata_book, provenance) = registry.fetch_with_provenance(id)?;
+    validate(id, &metadata_book, strictness)?;
+
+    let work = Work::of(&metadata_book);
+
+    let book = Book {
+        page_count: gallery_info_data.files.len(),
+        ..Book::from(metadata_book)
+    };
+
+    let extra = BookExtra {
+        work,
+        ..BookExtra::from(gallery_info_data).with_provenance(provenance)
+    };
+
+    Ok((book, extra))
+}
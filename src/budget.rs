@@ -0,0 +1,136 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow;
+
+/// `55m`, `90s`, `2h`, seconds without a unit
+pub fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let err = || anyhow::Error::msg(format!("Can't parse duration from `{}`, e.g. 55m", s));
+
+    let s = s.trim();
+    let (n, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => (&s[..i], &s[i..]),
+        None => (s, "s"),
+    };
+    let n = n.parse::<u64>().map_err(|_| err())?;
+
+    let secs = match unit {
+        "s" => n,
+        "m" => n * 60,
+        "h" => n * 60 * 60,
+        _ => return Err(err()),
+    };
+
+    Ok(Duration::from_secs(secs))
+}
+
+/// # RunBudget
+/// How long and how many galleries a run may synchronize
+///
+/// The engine stops taking galleries once it is exhausted,
+/// galleries in progress finish and the run checkpoints and exits
+#[derive(Debug)]
+pub struct RunBudget {
+    deadline: Option<Instant>,
+    max_galleries: Option<usize>,
+    taken: AtomicUsize,
+}
+
+impl RunBudget {
+    pub fn new(max_duration: Option<Duration>, max_galleries: Option<usize>) -> Self {
+        Self {
+            deadline: max_duration.map(|x| Instant::now() + x),
+            max_galleries,
+            taken: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.deadline.is_some() || self.max_galleries.is_some()
+    }
+
+    pub fn has_deadline(&self) -> bool {
+        self.deadline.is_some()
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.deadline.map_or(false, |x| Instant::now() >= x)
+            || self
+                .max_galleries
+                .map_or(false, |x| self.taken.load(Ordering::SeqCst) >= x)
+    }
+
+    /// Takes a gallery out of the budget, false if nothing is left
+    pub fn take(&self) -> bool {
+        if self.deadline.map_or(false, |x| Instant::now() >= x) {
+            return false;
+        }
+
+        match self.max_galleries {
+            Some(max) => self
+                .taken
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| {
+                    Some(x + 1).filter(|x| *x <= max)
+                })
+                .is_ok(),
+            None => {
+                self.taken.fetch_add(1, Ordering::SeqCst);
+                true
+            }
+        }
+    }
+
+    pub fn taken(&self) -> usize {
+        self.taken.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_duration, RunBudget};
+
+    #[test]
+    fn parse_durations() -> anyhow::Result<()> {
+        assert_eq!(Duration::from_secs(55 * 60), parse_duration("55m")?);
+        assert_eq!(Duration::from_secs(2 * 60 * 60), parse_duration("2h")?);
+        assert_eq!(Duration::from_secs(90), parse_duration("90s")?);
+        assert_eq!(Duration::from_secs(90), parse_duration("90")?);
+
+        assert!(parse_duration("55 minutes").is_err());
+        assert!(parse_duration("m").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn take_up_to_max_galleries() -> anyhow::Result<()> {
+        let budget = RunBudget::new(None, Some(2));
+
+        assert!(!budget.is_exhausted());
+        assert!(budget.take());
+        assert!(budget.take());
+        assert!(!budget.take());
+        assert!(budget.is_exhausted());
+        assert_eq!(2, budget.taken());
+
+        let unlimited = RunBudget::new(None, None);
+
+        assert!(!unlimited.is_limited());
+        assert!(unlimited.take());
+        assert!(!unlimited.is_exhausted());
+
+        Ok(())
+    }
+
+    #[test]
+    fn nothing_is_taken_after_deadline() -> anyhow::Result<()> {
+        let budget = RunBudget::new(Some(Duration::from_secs(0)), None);
+
+        assert!(budget.is_exhausted());
+        assert!(!budget.take());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use anyhow;
+use serde::{Deserialize, Serialize};
+use serde_json;
+use time::OffsetDateTime;
+
+use crate::compress::{self, Compression};
+#[cfg(feature = "sync")]
+use crate::idset::IdSet;
+use crate::models::{Metadata, MetadataBook};
+use crate::phash::CoverHash;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    /// Uploaded to Madome
+    Synced,
+    /// In local storage by `import`, not uploaded yet
+    Imported,
+    /// Uploaded only a subset of pages, `PAGES`
+    Partial,
+}
+
+/// What is sent to the Madome API when a gallery is removed from hitomi
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TombstoneAction {
+    Delete,
+    Hide,
+    /// Keeps the book visible, marked as removed upstream
+    Flag,
+}
+
+impl TombstoneAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Delete => "delete",
+            Self::Hide => "hide",
+            Self::Flag => "flag",
+        }
+    }
+}
+
+impl Display for TombstoneAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// `delete`, `hide` or `flag`
+impl FromStr for TombstoneAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let r = match s.trim() {
+            "delete" => Self::Delete,
+            "hide" => Self::Hide,
+            "flag" => Self::Flag,
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "Unknown tombstone action `{}`",
+                    s
+                )))
+            }
+        };
+
+        Ok(r)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Tombstone {
+    pub action: TombstoneAction,
+    /// Unix timestamp
+    pub at: i64,
+}
+
+/// Title and artists, what tells re-uploads of the same work apart
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Work {
+    pub title: String,
+    #[serde(default)]
+    pub artists: Vec<String>,
+}
+
+impl Work {
+    /// `None` without a title
+    pub fn of(book: &MetadataBook) -> Option<Self> {
+        let title = match &book.title {
+            Metadata::Title(Some(title)) => title.clone(),
+            _ => return None,
+        };
+        let artists = match &book.artists {
+            Metadata::Artists(Some(artists)) => artists.clone(),
+            _ => vec![],
+        };
+
+        Some(Self { title, artists })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub status: Status,
+    /// Unix timestamp
+    pub updated_at: i64,
+    /// Unix timestamp of `resync` by field
+    #[serde(default)]
+    pub refreshed_at: BTreeMap<String, i64>,
+    /// Language of the index it was synchronized from, `korean`
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Removed from hitomi and sent to the Madome API
+    #[serde(default)]
+    pub tombstone: Option<Tombstone>,
+    /// What `galleries/{id}.html` redirects to
+    #[serde(default)]
+    pub content_url: Option<String>,
+    /// Recorded when the book is synchronized or `resync`ed
+    #[serde(default)]
+    pub work: Option<Work>,
+    /// Of the cover downloaded with the images, for `find-similar`
+    #[serde(default)]
+    pub cover_hash: Option<CoverHash>,
+}
+
+/// # Catalog
+/// What this synchronizer knows about each gallery, kept in `catalog.json`
+pub struct Catalog {
+    inner: BTreeMap<u32, Entry>,
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Self {
+            inner: BTreeMap::new(),
+        }
+    }
+
+    pub fn iter(&self) -> std::collections::btree_map::Iter<'_, u32, Entry> {
+        self.inner.iter()
+    }
+
+    pub fn get(&self, id: &u32) -> Option<&Entry> {
+        self.inner.get(id)
+    }
+
+    #[cfg(feature = "sync")]
+    pub fn ids(&self) -> IdSet {
+        self.inner.keys().copied().collect()
+    }
+
+    pub fn has(&self, id: &u32) -> bool {
+        self.inner.contains_key(id)
+    }
+
+    pub fn status(&self, id: &u32) -> Option<Status> {
+        self.get(id).map(|entry| entry.status)
+    }
+
+    pub fn set_status(&mut self, id: u32, status: Status) {
+        let updated_at = OffsetDateTime::now_utc().unix_timestamp();
+
+        let entry = self.inner.entry(id).or_insert_with(|| Entry {
+            status,
+            updated_at,
+            refreshed_at: BTreeMap::new(),
+            language: None,
+            tombstone: None,
+            content_url: None,
+            work: None,
+            cover_hash: None,
+        });
+
+        entry.status = status;
+        entry.updated_at = updated_at;
+    }
+
+    /// Records `resync` of a field, only for galleries in catalog
+    pub fn set_refreshed(&mut self, id: u32, field: &str) {
+        if let Some(entry) = self.inner.get_mut(&id) {
+            entry.refreshed_at.insert(
+                field.to_string(),
+                OffsetDateTime::now_utc().unix_timestamp(),
+            );
+        }
+    }
+
+    pub fn set_language(&mut self, id: u32, language: &str) {
+        if let Some(entry) = self.inner.get_mut(&id) {
+            entry.language = Some(language.to_lowercase());
+        }
+    }
+
+    /// Records what was sent for a gallery removed upstream
+    pub fn set_tombstone(&mut self, id: u32, action: TombstoneAction) {
+        if let Some(entry) = self.inner.get_mut(&id) {
+            let at = OffsetDateTime::now_utc().unix_timestamp();
+
+            entry.tombstone = Some(Tombstone { action, at });
+            entry.updated_at = at;
+        }
+    }
+
+    pub fn set_content_url(&mut self, id: u32, content_url: String) {
+        if let Some(entry) = self.inner.get_mut(&id) {
+            entry.content_url = Some(content_url);
+        }
+    }
+
+    pub fn set_work(&mut self, id: u32, work: Work) {
+        if let Some(entry) = self.inner.get_mut(&id) {
+            entry.work = Some(work);
+        }
+    }
+
+    pub fn set_cover_hash(&mut self, id: u32, cover_hash: CoverHash) {
+        if let Some(entry) = self.inner.get_mut(&id) {
+            entry.cover_hash = Some(cover_hash);
+        }
+    }
+
+    pub fn is_tombstoned(&self, id: &u32) -> bool {
+        self.get(id)
+            .map_or(false, |entry| entry.tombstone.is_some())
+    }
+
+    /// Keeps the entry updated later
+    pub fn merge(&mut self, id: u32, entry: Entry) {
+        match self.inner.get(&id) {
+            Some(current) if current.updated_at > entry.updated_at => {}
+            _ => {
+                self.inner.insert(id, entry);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: &u32) -> Option<Entry> {
+        self.inner.remove(id)
+    }
+
+    /// Pretty json, compact if it is compressed
+    pub fn synchronize(&self, path: &str, compression: Compression) -> anyhow::Result<()> {
+        let json = if compression.is_none() {
+            serde_json::to_vec_pretty(&self.inner)?
+        } else {
+            serde_json::to_vec(&self.inner)?
+        };
+
+        compress::write(path, json, compression)
+    }
+
+    /// Empty catalog if the file doesn't exist yet, compressed or not
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        let text = match compress::read(path) {
+            Ok(buf) => String::from_utf8(buf)?,
+            Err(err) if compress::is_not_found(&err) => return Ok(Self::new()),
+            Err(err) => return Err(err),
+        };
+
+        if text.trim().is_empty() {
+            return Ok(Self::new());
+        }
+
+        let inner = serde_json::from_str(&text)?;
+
+        Ok(Self { inner })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::{Catalog, Status, TombstoneAction};
+    use crate::compress::Compression;
+
+    #[test]
+    fn catalog_synchronize() -> anyhow::Result<()> {
+        let path = env::temp_dir().join("madome_synchronizer_catalog.json");
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+
+        assert!(!Catalog::from_file(path)?.has(&1));
+
+        let mut catalog = Catalog::new();
+        catalog.set_status(1, Status::Imported);
+        catalog.set_status(2, Status::Synced);
+        catalog.synchronize(path, Compression::None)?;
+
+        let catalog = Catalog::from_file(path)?;
+
+        assert_eq!(Some(Status::Imported), catalog.status(&1));
+        assert_eq!(Some(Status::Synced), catalog.status(&2));
+        assert_eq!(None, catalog.status(&3));
+
+        let mut catalog = catalog;
+        catalog.set_refreshed(1, "tags");
+        catalog.set_status(1, Status::Synced);
+
+        assert!(catalog.get(&1).unwrap().refreshed_at.contains_key("tags"));
+        assert_eq!(Some(Status::Synced), catalog.status(&1));
+
+        catalog.set_tombstone(2, "hide".parse()?);
+
+        // read over the uncompressed one of the first synchronize
+        #[cfg(feature = "zstd")]
+        let compression = Compression::Zstd(3);
+        #[cfg(not(feature = "zstd"))]
+        let compression = Compression::None;
+
+        catalog.synchronize(path, compression)?;
+
+        let catalog = Catalog::from_file(path)?;
+
+        assert!(catalog.is_tombstoned(&2));
+        assert!(!catalog.is_tombstoned(&1));
+        assert_eq!(
+            Some(TombstoneAction::Hide),
+            catalog
+                .get(&2)
+                .unwrap()
+                .tombstone
+                .as_ref()
+                .map(|x| x.action)
+        );
+        assert!("remove".parse::<TombstoneAction>().is_err());
+
+        fs::remove_file(path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use anyhow;
+
+/// Served instead of the body, what a parser sees on an unexpected page
+const MALFORMED_BODY: &'static str = "<html><body><h1>Something went wrong</h1></body>";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    /// The request fails
+    Failure,
+    /// Only the first half of the body arrives
+    Truncated,
+    /// The response comes after `ChaosConfig::delay`, the body is intact
+    Slow,
+    Malformed,
+}
+
+/// Rate of each fault between 0 and 1
+#[derive(Debug, Clone)]
+pub struct ChaosConfig {
+    pub failure: f64,
+    pub truncated: f64,
+    pub slow: f64,
+    pub malformed: f64,
+    pub delay: Duration,
+    /// Same seed, same faults
+    pub seed: u64,
+}
+
+impl ChaosConfig {
+    /// `rate` of responses broken, split evenly among failures, truncated and malformed bodies,
+    /// and as many slow ones
+    pub fn uniform(rate: f64, seed: u64) -> Self {
+        Self {
+            failure: rate / 3.0,
+            truncated: rate / 3.0,
+            slow: rate,
+            malformed: rate / 3.0,
+            delay: Duration::from_millis(1),
+            seed,
+        }
+    }
+}
+
+/// # Chaos
+/// Wraps fetching of a body and injects faults, only for resilience tests
+pub struct Chaos {
+    config: ChaosConfig,
+    state: Mutex<u64>,
+    injected: AtomicUsize,
+    delayed: AtomicUsize,
+}
+
+impl Chaos {
+    pub fn new(config: ChaosConfig) -> Self {
+        Self {
+            // xorshift gets stuck at 0
+            state: Mutex::new(config.seed.max(1)),
+            config,
+            injected: AtomicUsize::new(0),
+            delayed: AtomicUsize::new(0),
+        }
+    }
+
+    /// xorshift64*, between 0 and 1
+    fn next(&self) -> f64 {
+        let mut state = self.state.lock().unwrap();
+
+        *state ^= *state >> 12;
+        *state ^= *state << 25;
+        *state ^= *state >> 27;
+
+        (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn pick(&self) -> Option<Fault> {
+        let ChaosConfig {
+            failure,
+            truncated,
+            slow,
+            malformed,
+            ..
+        } = self.config;
+
+        let x = self.next();
+
+        let fault = if x < failure {
+            Fault::Failure
+        } else if x < failure + truncated {
+            Fault::Truncated
+        } else if x < failure + truncated + malformed {
+            Fault::Malformed
+        } else if self.next() < slow {
+            Fault::Slow
+        } else {
+            return None;
+        };
+
+        Some(fault)
+    }
+
+    /// Faults that broke a response, slow ones aren't counted
+    pub fn injected(&self) -> usize {
+        self.injected.load(Ordering::SeqCst)
+    }
+
+    pub fn delayed(&self) -> usize {
+        self.delayed.load(Ordering::SeqCst)
+    }
+
+    pub fn fetch<F>(&self, fetch: F) -> anyhow::Result<String>
+    where
+        F: FnOnce() -> anyhow::Result<String>,
+    {
+        let fault = match self.pick() {
+            Some(fault) => fault,
+            None => return fetch(),
+        };
+
+        if fault == Fault::Slow {
+            self.delayed.fetch_add(1, Ordering::SeqCst);
+            thread::sleep(self.config.delay);
+            return fetch();
+        }
+
+        self.injected.fetch_add(1, Ordering::SeqCst);
+
+        match fault {
+            Fault::Failure => Err(anyhow::Error::msg("Injected failure")),
+            Fault::Truncated => {
+                let body = fetch()?;
+                let mut end = body.len() / 2;
+
+                while !body.is_char_boundary(end) {
+                    end -= 1;
+                }
+
+                Ok(body[..end].to_string())
+            }
+            _ => Ok(MALFORMED_BODY.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chaos, ChaosConfig};
+
+    #[test]
+    fn same_seed_same_faults() -> anyhow::Result<()> {
+        let a = Chaos::new(ChaosConfig::uniform(0.5, 7));
+        let b = Chaos::new(ChaosConfig::uniform(0.5, 7));
+
+        let faults_a = (0..100).map(|_| a.pick()).collect::<Vec<_>>();
+        let faults_b = (0..100).map(|_| b.pick()).collect::<Vec<_>>();
+
+        assert_eq!(faults_a, faults_b);
+        assert!(faults_a.iter().any(|x| x.is_some()));
+        assert!(faults_a.iter().any(|x| x.is_none()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn no_fault_at_zero_rate() -> anyhow::Result<()> {
+        let chaos = Chaos::new(ChaosConfig::uniform(0.0, 7));
+
+        for _ in 0..100 {
+            assert_eq!("{}", chaos.fetch(|| Ok("{}".to_string()))?);
+        }
+
+        assert_eq!(0, chaos.injected());
+
+        Ok(())
+    }
+}
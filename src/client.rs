@@ -0,0 +1,217 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow;
+use once_cell::sync::{Lazy, OnceCell};
+use reqwest;
+
+use crate::downtime::check_page;
+use crate::rate_limit::{parse_retry_after, RateLimiter, RetryAfter};
+
+static CLIENT: OnceCell<reqwest::blocking::Client> = OnceCell::new();
+
+static METADATA_LIMIT: Lazy<RwLock<Arc<RateLimiter>>> =
+    Lazy::new(|| RwLock::new(Arc::new(RateLimiter::unlimited())));
+
+/// Options of the client shared by every request
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// Hosts pinned to addresses instead of DNS
+    pub resolve: Vec<(String, SocketAddr)>,
+    /// Idle connections kept for each host, unlimited if None
+    pub pool_max_idle_per_host: Option<usize>,
+    /// HTTP/2 when the server accepts it, otherwise HTTP/1.1 only
+    pub http2: bool,
+    pub tcp_keepalive: Option<Duration>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            resolve: vec![],
+            pool_max_idle_per_host: None,
+            http2: true,
+            tcp_keepalive: None,
+        }
+    }
+}
+
+impl ClientConfig {
+    pub fn build(&self) -> anyhow::Result<reqwest::blocking::Client> {
+        let mut builder = reqwest::blocking::Client::builder().tcp_keepalive(self.tcp_keepalive);
+
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+
+        if !self.http2 {
+            builder = builder.http1_only();
+        }
+
+        for (host, addr) in &self.resolve {
+            builder = builder.resolve(host, *addr);
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+/// `ltn.hitomi.la=1.2.3.4,aa.hitomi.la=1.2.3.5:443`, port is 443 if omitted
+pub fn parse_resolve(s: &str) -> anyhow::Result<Vec<(String, SocketAddr)>> {
+    s.split(',')
+        .map(|pair| pair.trim())
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut pair = pair.splitn(2, '=');
+
+            let host = pair.next().unwrap_or("").trim();
+            let addr = pair
+                .next()
+                .ok_or_else(|| anyhow::Error::msg(format!("Address of `{}` is missing", host)))?
+                .trim();
+
+            let addr = match addr.parse::<SocketAddr>() {
+                Ok(addr) => addr,
+                Err(_) => SocketAddr::new(addr.parse::<IpAddr>()?, 443),
+            };
+
+            Ok((host.to_string(), addr))
+        })
+        .collect()
+}
+
+/// Builds the shared client with `config`, only before any request
+pub fn init(config: &ClientConfig) -> anyhow::Result<()> {
+    CLIENT
+        .set(config.build()?)
+        .map_err(|_| anyhow::Error::msg("Client is already initialized"))
+}
+
+/// Body of a response checked by `check_rate_limit`, `UnderMaintenance` if it is the maintenance page
+///
+/// hitomi.la may serve the maintenance page with 200
+pub fn text(response: reqwest::blocking::Response) -> anyhow::Result<String> {
+    let url = response.url().to_string();
+
+    check_page(&url, response.text()?)
+}
+
+/// The shared client, connections are kept alive across requests
+pub fn shared() -> anyhow::Result<&'static reqwest::blocking::Client> {
+    CLIENT.get_or_try_init(|| ClientConfig::default().build())
+}
+
+/// Limits every metadata request made after it, unlimited until then
+pub fn limit_metadata(limit: Arc<RateLimiter>) {
+    *METADATA_LIMIT.write().unwrap() = limit;
+}
+
+/// The shared client once the metadata limit allows a request, called for each of them
+///
+/// Nozomi, gallery blocks, galleries and the HEADs of `exists`, but not images
+pub fn metadata() -> anyhow::Result<&'static reqwest::blocking::Client> {
+    let limit = Arc::clone(&METADATA_LIMIT.read().unwrap());
+
+    limited(&limit)
+}
+
+/// The shared client once `limit` allows a request
+pub fn limited(limit: &RateLimiter) -> anyhow::Result<&'static reqwest::blocking::Client> {
+    limit.acquire();
+
+    shared()
+}
+
+/// `RetryAfter` on 429, `UnderMaintenance` on 503 of the maintenance page,
+/// other responses as they are
+pub fn check_rate_limit(
+    response: reqwest::blocking::Response,
+) -> anyhow::Result<reqwest::blocking::Response> {
+    if response.status() == reqwest::StatusCode::SERVICE_UNAVAILABLE {
+        let status = response.status();
+        let url = response.url().to_string();
+
+        check_page(&url, response.text()?)?;
+
+        return Err(anyhow::Error::msg(format!("{} of {}", status, url)));
+    }
+
+    if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Ok(response);
+    }
+
+    let wait = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|x| x.to_str().ok())
+        .and_then(parse_retry_after);
+    let host = response.url().host_str().map(|x| x.to_string());
+
+    Err(RetryAfter { wait, host }.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::time::{Duration, Instant};
+
+    use super::{limited, parse_resolve, ClientConfig};
+    use crate::rate_limit::RateLimiter;
+
+    #[test]
+    fn build_tuned_client() -> anyhow::Result<()> {
+        let config = ClientConfig {
+            resolve: parse_resolve("ltn.hitomi.la=127.0.0.1")?,
+            pool_max_idle_per_host: Some(4),
+            http2: false,
+            tcp_keepalive: Some(Duration::from_secs(60)),
+        };
+
+        config.build()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_resolve_overrides() -> anyhow::Result<()> {
+        let resolve = parse_resolve("ltn.hitomi.la=1.2.3.4, aa.hitomi.la=[::1]:8443")?;
+
+        assert_eq!(
+            vec![
+                (
+                    "ltn.hitomi.la".to_string(),
+                    "1.2.3.4:443".parse::<SocketAddr>()?
+                ),
+                (
+                    "aa.hitomi.la".to_string(),
+                    "[::1]:8443".parse::<SocketAddr>()?
+                ),
+            ],
+            resolve
+        );
+
+        assert!(parse_resolve("ltn.hitomi.la").is_err());
+        assert!(parse_resolve("ltn.hitomi.la=hitomi").is_err());
+        assert!(parse_resolve("")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn limit_each_metadata_request() -> anyhow::Result<()> {
+        // its own limiter, `METADATA_LIMIT` is shared by the tests running along
+        let limit = RateLimiter::new(10.0);
+
+        let started = Instant::now();
+
+        // a turn for each request, not one for the gallery
+        for _ in 0..3 {
+            limited(&limit)?;
+        }
+
+        assert!(started.elapsed() >= Duration::from_millis(200));
+
+        Ok(())
+    }
+}
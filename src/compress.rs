@@ -0,0 +1,178 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow;
+
+/// First bytes of a zstd frame
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Level of `zstd` without one
+pub const DEFAULT_LEVEL: i32 = 3;
+
+pub fn is_zstd(buf: &[u8]) -> bool {
+    buf.starts_with(&ZSTD_MAGIC)
+}
+
+/// # Compression
+/// How catalog.json, snapshots and the event log are written, COMPRESSION
+///
+/// They are read either way, uncompressed files of older versions by their first bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    /// Level of zstd, 1 to 21
+    Zstd(i32),
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl Compression {
+    pub fn is_none(&self) -> bool {
+        *self == Self::None
+    }
+
+    pub fn encode(&self, buf: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::None => Ok(buf),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(level) => Ok(zstd::encode_all(buf.as_slice(), *level)?),
+            #[cfg(not(feature = "zstd"))]
+            Self::Zstd(_) => Err(anyhow::Error::msg(
+                "COMPRESSION=zstd needs cargo build --features zstd",
+            )),
+        }
+    }
+}
+
+/// `none`, `zstd` or `zstd:19`
+impl FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        let r = match (s, s.strip_prefix("zstd:")) {
+            ("none", _) => Self::None,
+            ("zstd", _) => Self::Zstd(DEFAULT_LEVEL),
+            (_, Some(level)) => match level.parse::<i32>() {
+                Ok(level) if (1..=21).contains(&level) => Self::Zstd(level),
+                _ => {
+                    return Err(anyhow::Error::msg(format!(
+                        "Level of zstd is 1 to 21, not `{}`",
+                        level
+                    )))
+                }
+            },
+            _ => return Err(anyhow::Error::msg(format!("Unknown compression `{}`", s))),
+        };
+
+        Ok(r)
+    }
+}
+
+/// Decompressed if `buf` is zstd, otherwise `buf` as it is
+pub fn decode(buf: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+    if !is_zstd(&buf) {
+        return Ok(buf);
+    }
+
+    #[cfg(feature = "zstd")]
+    {
+        // every frame, the event log is a frame per batch of lines
+        Ok(zstd::decode_all(buf.as_slice())?)
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    {
+        Err(anyhow::Error::msg(
+            "Can't read a zstd file without cargo build --features zstd",
+        ))
+    }
+}
+
+/// `fs::read` decompressing zstd, `NotFound` is kept for callers treating it as empty
+pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Vec<u8>> {
+    let buf = fs::read(path)?;
+
+    decode(buf)
+}
+
+/// Written to `{path}.part` and renamed, a crash leaves the last complete file
+pub fn write(path: impl AsRef<Path>, buf: Vec<u8>, compression: Compression) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let part = part_path(path);
+
+    if let Err(err) = fs::write(&part, compression.encode(buf)?) {
+        let _ = fs::remove_file(&part);
+        return Err(err.into());
+    }
+
+    fs::rename(&part, path)?;
+
+    Ok(())
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+
+    PathBuf::from(part)
+}
+
+pub fn is_not_found(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<io::Error>()
+        .map_or(false, |err| err.kind() == io::ErrorKind::NotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compression;
+
+    #[test]
+    fn parse_compression() -> anyhow::Result<()> {
+        assert_eq!(Compression::None, "none".parse()?);
+        assert_eq!(Compression::Zstd(3), "zstd".parse()?);
+        assert_eq!(Compression::Zstd(19), "zstd:19".parse()?);
+        assert!("zstd:30".parse::<Compression>().is_err());
+        assert!("gzip".parse::<Compression>().is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn read_either_way() -> anyhow::Result<()> {
+        use super::{is_not_found, is_zstd, part_path, read, write};
+        use std::{env, fs};
+
+        let path = env::temp_dir().join("madome_synchronizer_compress.json");
+        let _ = fs::remove_file(&path);
+        let json = br#"{"1724122":{"status":"Synced"}}"#.repeat(100);
+
+        write(&path, json.clone(), Compression::Zstd(3))?;
+
+        let buf = fs::read(&path)?;
+        assert!(is_zstd(&buf));
+        assert!(buf.len() < json.len());
+        assert_eq!(json, read(&path)?);
+
+        // written by an older version
+        write(&path, json.clone(), Compression::None)?;
+
+        assert!(!is_zstd(&fs::read(&path)?));
+        assert_eq!(json, read(&path)?);
+        assert!(!part_path(&path).exists());
+
+        fs::remove_file(&path)?;
+
+        assert!(is_not_found(&read(&path).unwrap_err()));
+
+        Ok(())
+    }
+}
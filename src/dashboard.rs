@@ -0,0 +1,243 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::stage::Stage;
+
+/// Errors kept for `--tui`
+const RECENT_ERRORS: usize = 20;
+const STAGES: usize = 7;
+
+struct Download {
+    id: u32,
+    filename: String,
+    bytes: u64,
+    started_at: Instant,
+}
+
+struct Inner {
+    /// Calls in progress of each stage by `Stage::as_u8`
+    stages: [usize; STAGES],
+    /// Ids discovered and not taken by a worker yet
+    queued: usize,
+    downloads: BTreeMap<u64, Download>,
+    next_download: u64,
+    /// (id, error), newest last
+    errors: VecDeque<(u32, String)>,
+}
+
+/// # Dashboard
+/// What the workers are doing right now, drawn by `--tui`
+pub struct Dashboard {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadSnapshot {
+    pub id: u32,
+    pub filename: String,
+    pub bytes: u64,
+    pub bytes_per_second: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DashboardSnapshot {
+    /// (stage, calls in progress), pages for `Add Images`
+    pub stages: Vec<(String, usize)>,
+    pub queued: usize,
+    pub downloads: Vec<DownloadSnapshot>,
+    /// Newest first
+    pub errors: Vec<(u32, String)>,
+}
+
+impl Dashboard {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                stages: [0; STAGES],
+                queued: 0,
+                downloads: BTreeMap::new(),
+                next_download: 0,
+                errors: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn enqueue(&self) {
+        self.inner.lock().unwrap().queued += 1;
+    }
+
+    pub fn dequeue(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.queued = inner.queued.saturating_sub(1);
+    }
+
+    /// Counts a call of `Stage::as_u8` while `f` runs
+    pub fn track<T>(&self, stage: u8, f: impl FnOnce() -> T) -> T {
+        let i = stage as usize;
+
+        self.inner.lock().unwrap().stages[i] += 1;
+        let r = f();
+        self.inner.lock().unwrap().stages[i] -= 1;
+
+        r
+    }
+
+    /// Listed until the guard is dropped
+    pub fn download(&self, id: u32, filename: &str) -> DownloadGuard<'_> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let key = inner.next_download;
+        inner.next_download += 1;
+        inner.downloads.insert(
+            key,
+            Download {
+                id,
+                filename: filename.to_string(),
+                bytes: 0,
+                started_at: Instant::now(),
+            },
+        );
+
+        DownloadGuard {
+            dashboard: self,
+            key,
+        }
+    }
+
+    pub fn error(&self, id: u32, error: impl ToString) {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.errors.push_back((id, error.to_string()));
+
+        while inner.errors.len() > RECENT_ERRORS {
+            inner.errors.pop_front();
+        }
+    }
+
+    pub fn snapshot(&self) -> DashboardSnapshot {
+        let inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+
+        DashboardSnapshot {
+            stages: (0..STAGES)
+                .map(|i| (Stage::from(i as u8).to_string(), inner.stages[i]))
+                .collect(),
+            queued: inner.queued,
+            downloads: inner
+                .downloads
+                .values()
+                .map(|download| {
+                    let secs = now.duration_since(download.started_at).as_secs_f64();
+
+                    DownloadSnapshot {
+                        id: download.id,
+                        filename: download.filename.clone(),
+                        bytes: download.bytes,
+                        bytes_per_second: if secs > 0.0 {
+                            download.bytes as f64 / secs
+                        } else {
+                            0.0
+                        },
+                    }
+                })
+                .collect(),
+            errors: inner.errors.iter().rev().cloned().collect(),
+        }
+    }
+}
+
+pub struct DownloadGuard<'a> {
+    dashboard: &'a Dashboard,
+    key: u64,
+}
+
+impl<'a> DownloadGuard<'a> {
+    pub fn add_bytes(&self, bytes: usize) {
+        let mut inner = self.dashboard.inner.lock().unwrap();
+
+        if let Some(download) = inner.downloads.get_mut(&self.key) {
+            download.bytes += bytes as u64;
+        }
+    }
+
+    /// Counts what is written through it as downloaded
+    pub fn counted<W: Write>(&self, inner: W) -> Counted<'_, 'a, W> {
+        Counted { inner, guard: self }
+    }
+}
+
+impl<'a> Drop for DownloadGuard<'a> {
+    fn drop(&mut self) {
+        self.dashboard
+            .inner
+            .lock()
+            .unwrap()
+            .downloads
+            .remove(&self.key);
+    }
+}
+
+pub struct Counted<'b, 'a, W> {
+    inner: W,
+    guard: &'b DownloadGuard<'a>,
+}
+
+impl<'b, 'a, W: Write> Write for Counted<'b, 'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.guard.add_bytes(n);
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::Dashboard;
+    use crate::stage::Stage;
+
+    #[test]
+    fn track_stages_and_downloads() -> anyhow::Result<()> {
+        let dashboard = Dashboard::new();
+
+        dashboard.enqueue();
+        dashboard.enqueue();
+        dashboard.dequeue();
+
+        let in_stage = dashboard.track(Stage::AddImages.as_u8(), || {
+            let download = dashboard.download(1724122, "1.webp");
+            download.counted(Vec::new()).write_all(&[0; 100])?;
+
+            let snapshot = dashboard.snapshot();
+
+            assert_eq!(1, snapshot.downloads.len());
+            assert_eq!(100, snapshot.downloads[0].bytes);
+
+            Ok::<_, anyhow::Error>(snapshot.stages[Stage::AddImages.as_u8() as usize].1)
+        })?;
+
+        assert_eq!(1, in_stage);
+
+        for i in 0..25 {
+            dashboard.error(i, "404 Not Found");
+        }
+
+        let snapshot = dashboard.snapshot();
+
+        assert_eq!(1, snapshot.queued);
+        assert!(snapshot.downloads.is_empty());
+        assert!(snapshot.stages.iter().all(|(_, n)| *n == 0));
+        assert_eq!(20, snapshot.errors.len());
+        assert_eq!(24, snapshot.errors[0].0);
+
+        Ok(())
+    }
+}
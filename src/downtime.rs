@@ -0,0 +1,206 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow;
+use log::info;
+
+/// Phrases of the maintenance page of hitomi.la, lowercased
+const SIGNATURES: [&'static str; 4] = [
+    "<title>maintenance",
+    "under maintenance",
+    "down for maintenance",
+    "maintenance in progress",
+];
+
+/// The maintenance page came instead of what `url` is
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnderMaintenance {
+    pub url: String,
+}
+
+impl Display for UnderMaintenance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Under maintenance instead of {}", self.url)
+    }
+}
+
+impl Error for UnderMaintenance {}
+
+pub fn is_under_maintenance(err: &anyhow::Error) -> bool {
+    err.chain()
+        .any(|cause| cause.downcast_ref::<UnderMaintenance>().is_some())
+}
+
+/// Html of the maintenance page, whatever the status is
+pub fn is_maintenance_page(html: &str) -> bool {
+    let html = html.to_lowercase();
+
+    html.contains("<html") && SIGNATURES.iter().any(|x| html.contains(x))
+}
+
+/// `UnderMaintenance` if `html` is the maintenance page, otherwise `html`
+pub fn check_page(url: &str, html: String) -> anyhow::Result<String> {
+    if is_maintenance_page(&html) {
+        return Err(UnderMaintenance {
+            url: url.to_string(),
+        }
+        .into());
+    }
+
+    Ok(html)
+}
+
+/// # Downtime
+/// Pause of every worker while hitomi.la is under maintenance
+///
+/// The first worker to see the maintenance page re-checks the site every `interval`,
+/// the others wait until it is back
+#[derive(Debug)]
+pub struct Downtime {
+    interval: Duration,
+    /// Since when the site is down
+    since: Mutex<Option<Instant>>,
+    resumed: Condvar,
+}
+
+impl Downtime {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            since: Mutex::new(None),
+            resumed: Condvar::new(),
+        }
+    }
+
+    pub fn is_down(&self) -> bool {
+        self.since.lock().unwrap().is_some()
+    }
+
+    /// Blocks while the site is down
+    pub fn wait(&self) {
+        let mut since = self.since.lock().unwrap();
+
+        while since.is_some() {
+            since = self.resumed.wait(since).unwrap();
+        }
+    }
+
+    /// Pauses until `check` passes, returns how long the site was down
+    ///
+    /// If another worker is checking already, it only waits for it
+    pub fn pause_until(&self, check: impl Fn() -> anyhow::Result<()>) -> Duration {
+        {
+            let mut since = self.since.lock().unwrap();
+
+            if since.is_some() {
+                drop(since);
+                self.wait();
+                return Duration::from_secs(0);
+            }
+
+            *since = Some(Instant::now());
+        }
+
+        info!(
+            "hitomi.la is under maintenance, check again every {} secs",
+            self.interval.as_secs()
+        );
+
+        loop {
+            thread::sleep(self.interval);
+
+            match check() {
+                Ok(_) => break,
+                Err(err) => info!("Still under maintenance: {}", err),
+            }
+        }
+
+        let down = self
+            .since
+            .lock()
+            .unwrap()
+            .take()
+            .map_or(Duration::from_secs(0), |since| since.elapsed());
+        self.resumed.notify_all();
+
+        info!("hitomi.la is back after {} secs", down.as_secs());
+
+        down
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{check_page, is_maintenance_page, is_under_maintenance, Downtime};
+
+    #[test]
+    fn detect_maintenance_page() -> anyhow::Result<()> {
+        let page = "<!DOCTYPE html><html><head><title>Maintenance</title></head>\
+                    <body><h1>Hitomi.la is down for maintenance</h1></body></html>";
+        let gallery = "<html><body><a href=\"/galleries/1724122.html\">1724122</a></body></html>";
+
+        assert!(is_maintenance_page(page));
+        assert!(!is_maintenance_page(gallery));
+        assert!(!is_maintenance_page(
+            "var galleryinfo = {\"title\": \"maintenance\"}"
+        ));
+
+        let err = check_page(
+            "https://ltn.hitomi.la/galleryblock/1724122.html",
+            page.into(),
+        )
+        .unwrap_err()
+        .context("Can't parse gallery");
+
+        assert!(is_under_maintenance(&err));
+        assert_eq!(gallery, check_page("", gallery.into())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn pause_until_back() -> anyhow::Result<()> {
+        let downtime = Arc::new(Downtime::new(Duration::from_millis(10)));
+        let checks = Arc::new(AtomicUsize::new(0));
+
+        let checking = {
+            let downtime = Arc::clone(&downtime);
+            let checks = Arc::clone(&checks);
+
+            thread::spawn(move || {
+                downtime.pause_until(|| match checks.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => Err(anyhow::Error::msg("503 Service Unavailable")),
+                    _ => Ok(()),
+                })
+            })
+        };
+
+        while !downtime.is_down() {
+            thread::yield_now();
+        }
+
+        // the second worker doesn't check, it waits
+        assert_eq!(
+            Duration::from_secs(0),
+            downtime.pause_until(|| panic!("checked twice"))
+        );
+        assert!(!downtime.is_down());
+
+        let down = checking.join().unwrap();
+
+        assert_eq!(3, checks.load(Ordering::SeqCst));
+        assert!(down >= Duration::from_millis(30));
+
+        downtime.wait();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+use serde::Serialize;
+
+use crate::catalog::{Catalog, Entry, Status};
+
+/// # DuplicateGroup
+/// Galleries of the same title and artists, re-uploads in most cases
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DuplicateGroup {
+    pub title: String,
+    pub artists: Vec<String>,
+    /// Suggested one to keep, the others point to it
+    pub canonical: u32,
+    /// Every id of the group including `canonical`, ascending
+    pub ids: Vec<u32>,
+}
+
+/// # SimilarPair
+/// Galleries whose covers are at most a few bits of `CoverHash` apart,
+/// re-uploads under another title or decensored variants
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SimilarPair {
+    /// The lower id
+    pub a: u32,
+    pub b: u32,
+    pub distance: u32,
+}
+
+/// `Loli  Sister` and `loli sister` are the same title
+fn normalize(s: &str) -> String {
+    s.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Galleries still on hitomi and fully synchronized come first,
+/// then the oldest id, links to the work point to it already
+fn rank(id: u32, entry: &Entry) -> (bool, bool, u32) {
+    (
+        entry.tombstone.is_some(),
+        entry.status != Status::Synced,
+        id,
+    )
+}
+
+/// Groups of more than one gallery, sorted by canonical id
+///
+/// Entries synchronized before works were recorded have none, `resync` records them
+pub fn find_duplicates(catalog: &Catalog) -> Vec<DuplicateGroup> {
+    let mut works = BTreeMap::<(String, Vec<String>), Vec<(u32, &Entry)>>::new();
+
+    for (id, entry) in catalog.iter() {
+        let work = match &entry.work {
+            Some(work) => work,
+            None => continue,
+        };
+
+        let mut artists = work
+            .artists
+            .iter()
+            .map(|x| normalize(x))
+            .collect::<Vec<_>>();
+        artists.sort();
+        artists.dedup();
+
+        works
+            .entry((normalize(&work.title), artists))
+            .or_default()
+            .push((*id, entry));
+    }
+
+    let mut groups = works
+        .into_iter()
+        .filter(|(_, entries)| entries.len() > 1)
+        .map(|(_, entries)| {
+            let (canonical, canonical_entry) = *entries
+                .iter()
+                .min_by_key(|(id, entry)| rank(*id, entry))
+                .unwrap();
+            let work = canonical_entry.work.as_ref().unwrap();
+
+            DuplicateGroup {
+                title: work.title.clone(),
+                artists: work.artists.clone(),
+                canonical,
+                ids: entries.iter().map(|(id, _)| *id).collect(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    groups.sort_by_key(|group| group.canonical);
+
+    groups
+}
+
+/// Pairs of covers at most `max_distance` apart, the closest first, then by id
+///
+/// Only galleries whose cover was hashed when it was downloaded are compared
+pub fn find_similar(catalog: &Catalog, max_distance: u32) -> Vec<SimilarPair> {
+    let hashes = catalog
+        .iter()
+        .filter_map(|(id, entry)| entry.cover_hash.map(|hash| (*id, hash)))
+        .collect::<Vec<_>>();
+
+    let mut pairs = vec![];
+
+    for (i, (a, a_hash)) in hashes.iter().enumerate() {
+        for (b, b_hash) in &hashes[i + 1..] {
+            let distance = a_hash.distance(b_hash);
+
+            if distance <= max_distance {
+                pairs.push(SimilarPair {
+                    a: *a,
+                    b: *b,
+                    distance,
+                });
+            }
+        }
+    }
+
+    pairs.sort_by_key(|pair| (pair.distance, pair.a, pair.b));
+
+    pairs
+}
+
+/// `1724122 ~ 1744332, distance 3`
+impl Display for SimilarPair {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ~ {}, distance {}", self.a, self.b, self.distance)
+    }
+}
+
+impl Display for DuplicateGroup {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let others = self
+            .ids
+            .iter()
+            .filter(|id| **id != self.canonical)
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>();
+
+        write!(
+            f,
+            "{} by {}: keep {}, duplicates {}",
+            self.title,
+            if self.artists.is_empty() {
+                "N/A".to_string()
+            } else {
+                self.artists.join(", ")
+            },
+            self.canonical,
+            others.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_duplicates, find_similar, SimilarPair};
+    use crate::catalog::{Catalog, Status, TombstoneAction, Work};
+    use crate::phash::CoverHash;
+
+    fn work(title: &str, artists: &[&str]) -> Work {
+        Work {
+            title: title.to_string(),
+            artists: artists.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn group_same_title_and_artists() -> anyhow::Result<()> {
+        let mut catalog = Catalog::new();
+
+        for id in 1..=6 {
+            catalog.set_status(id, Status::Synced);
+        }
+
+        catalog.set_work(1, work("Sister", &["a", "b"]));
+        catalog.set_work(2, work("sister ", &["B", "a"]));
+        catalog.set_work(3, work("Sister", &["c"]));
+        catalog.set_work(4, work("Other", &[]));
+        catalog.set_work(5, work("other", &[]));
+        catalog.set_work(6, work("Sister", &["a", "b"]));
+
+        // the oldest is removed from hitomi, the next one is kept
+        catalog.set_tombstone(1, TombstoneAction::Flag);
+        catalog.set_status(5, Status::Partial);
+
+        let groups = find_duplicates(&catalog);
+
+        assert_eq!(2, groups.len());
+
+        assert_eq!(2, groups[0].canonical);
+        assert_eq!(vec![1, 2, 6], groups[0].ids);
+        assert_eq!("sister ", groups[0].title);
+
+        assert_eq!(4, groups[1].canonical);
+        assert_eq!(vec![4, 5], groups[1].ids);
+        assert_eq!("Other by N/A: keep 4, duplicates 5", groups[1].to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn pair_similar_covers() -> anyhow::Result<()> {
+        let mut catalog = Catalog::new();
+
+        for id in 1..=5 {
+            catalog.set_status(id, Status::Synced);
+        }
+
+        catalog.set_cover_hash(1, CoverHash(0xf0f0_f0f0_f0f0_f0f0));
+        // decensored, a few cells differ
+        catalog.set_cover_hash(2, CoverHash(0xf0f0_f0f0_f0f0_f0f3));
+        catalog.set_cover_hash(3, CoverHash(0x0f0f_0f0f_0f0f_0f0f));
+        // re-encoded, the same hash
+        catalog.set_cover_hash(4, CoverHash(0xf0f0_f0f0_f0f0_f0f0));
+
+        let pairs = find_similar(&catalog, 6);
+
+        assert_eq!(
+            vec![
+                SimilarPair {
+                    a: 1,
+                    b: 4,
+                    distance: 0
+                },
+                SimilarPair {
+                    a: 1,
+                    b: 2,
+                    distance: 2
+                },
+                SimilarPair {
+                    a: 2,
+                    b: 4,
+                    distance: 2
+                },
+            ],
+            pairs
+        );
+        assert_eq!("1 ~ 4, distance 0", pairs[0].to_string());
+        assert_eq!(1, find_similar(&catalog, 0).len());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow;
+use log::{error, info};
+use serde::Serialize;
+use serde_json;
+use time::OffsetDateTime;
+
+use crate::catalog::TombstoneAction;
+use crate::compress::{self, Compression};
+
+/// Lines of a zstd frame of a compressed `EventLog`, a line alone hardly compresses
+const FRAME_LINES: usize = 256;
+
+/// What happened to a gallery, a line of `EventLog`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Images and manifest, `pages` is a `PageSubset`, or the pages done of a timed out gallery, `1-12,14`
+    Synced {
+        id: u32,
+        pages: String,
+    },
+    /// Only the book, without images
+    BookSynced {
+        id: u32,
+    },
+    /// `class` of `ERROR_POLICY` and what it did with the gallery
+    Failed {
+        id: u32,
+        class: String,
+        policy: String,
+        error: String,
+    },
+    Skipped {
+        id: u32,
+        reason: String,
+    },
+    /// Found by `reconcile`, `action` is `None` without TOMBSTONE
+    RemovedUpstream {
+        id: u32,
+        action: Option<TombstoneAction>,
+    },
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    /// Unix timestamp
+    at: i64,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// # EventLog
+/// NDJSON of `Event`s appended as they happen, for systems tailing it
+///
+/// Apart from the logs, its lines are never rewritten or reordered
+///
+/// Compressed, lines are appended as a zstd frame every `FRAME_LINES` and on `flush`,
+/// so tails see them late
+pub struct EventLog {
+    file: Mutex<File>,
+    compression: Compression,
+    /// Lines of the next frame and how many
+    pending: Mutex<(Vec<u8>, usize)>,
+}
+
+impl EventLog {
+    /// A log of lines is appended to as it is, plain or compressed, whatever `compression` is
+    pub fn open(path: impl AsRef<Path>, compression: Compression) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+
+        let mut magic = [0; 4];
+        let len = file.read(&mut magic)?;
+
+        let compression = match (len, compress::is_zstd(&magic[..len]), compression) {
+            (0, _, compression) => compression,
+            (_, true, Compression::None) => Compression::Zstd(compress::DEFAULT_LEVEL),
+            (_, false, Compression::Zstd(_)) => {
+                info!(
+                    "{} is uncompressed, events are appended uncompressed",
+                    path.display()
+                );
+                Compression::None
+            }
+            (_, _, compression) => compression,
+        };
+
+        Ok(Self {
+            file: Mutex::new(file),
+            compression,
+            pending: Mutex::new((vec![], 0)),
+        })
+    }
+
+    /// Lines of a log written compressed or not
+    pub fn lines(path: impl AsRef<Path>) -> anyhow::Result<Vec<String>> {
+        let log = String::from_utf8(compress::read(path)?)?;
+
+        Ok(log.lines().map(|line| line.to_string()).collect())
+    }
+
+    /// Appends the pending lines of a compressed log
+    pub fn flush(&self) -> anyhow::Result<()> {
+        let mut pending = self.pending.lock().unwrap();
+
+        if pending.1 == 0 {
+            return Ok(());
+        }
+
+        let frame = self.compression.encode(std::mem::take(&mut pending.0))?;
+        pending.1 = 0;
+
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&frame)?;
+        file.flush()?;
+
+        Ok(())
+    }
+
+    pub fn append(&self, event: &Event) -> anyhow::Result<()> {
+        let line = Line {
+            at: OffsetDateTime::now_utc().unix_timestamp(),
+            event,
+        };
+        let mut buf = serde_json::to_vec(&line)?;
+        buf.push(b'\n');
+
+        if !self.compression.is_none() {
+            let lines = {
+                let mut pending = self.pending.lock().unwrap();
+                pending.0.extend_from_slice(&buf);
+                pending.1 += 1;
+                pending.1
+            };
+
+            if lines >= FRAME_LINES {
+                self.flush()?;
+            }
+
+            return Ok(());
+        }
+
+        // a whole line in a write, tails never see half of it
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&buf)?;
+        file.flush()?;
+
+        Ok(())
+    }
+}
+
+impl Drop for EventLog {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            error!("Can't append events: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::{Event, EventLog};
+    use crate::catalog::TombstoneAction;
+    use crate::compress::Compression;
+
+    #[test]
+    fn append_lines_of_json() -> anyhow::Result<()> {
+        let path = env::temp_dir().join("madome_synchronizer_events.ndjson");
+        let _ = fs::remove_file(&path);
+
+        let events = EventLog::open(&path, Compression::None)?;
+        events.append(&Event::Synced {
+            id: 1724122,
+            pages: "all".to_string(),
+        })?;
+        drop(events);
+
+        // stays plain, a tail of it would break otherwise
+        let events = EventLog::open(&path, Compression::Zstd(3))?;
+        events.append(&Event::RemovedUpstream {
+            id: 1721169,
+            action: Some(TombstoneAction::Hide),
+        })?;
+        drop(events);
+
+        let log = fs::read_to_string(&path)?;
+        let lines = log
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(2, lines.len());
+        assert_eq!("synced", lines[0]["event"]);
+        assert_eq!(1724122, lines[0]["id"]);
+        assert_eq!("all", lines[0]["pages"]);
+        assert!(lines[0]["at"].as_i64().unwrap() > 0);
+        assert_eq!("removed_upstream", lines[1]["event"]);
+
+        fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn append_compressed_frames() -> anyhow::Result<()> {
+        let path = env::temp_dir().join("madome_synchronizer_events.ndjson.zst");
+        let _ = fs::remove_file(&path);
+
+        let skipped = |id| Event::Skipped {
+            id,
+            reason: "seen".to_string(),
+        };
+
+        let events = EventLog::open(&path, Compression::Zstd(3))?;
+        for id in 0..300 {
+            events.append(&skipped(id))?;
+        }
+        drop(events);
+
+        // appended as frames, whatever it is opened with
+        let events = EventLog::open(&path, Compression::None)?;
+        events.append(&skipped(300))?;
+        events.flush()?;
+
+        let lines = EventLog::lines(&path)?;
+
+        assert!(crate::compress::is_zstd(&fs::read(&path)?));
+        assert_eq!(301, lines.len());
+        assert_eq!(
+            300,
+            serde_json::from_str::<serde_json::Value>(&lines[300])?["id"]
+        );
+
+        drop(events);
+        fs::remove_file(&path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use anyhow;
+use serde::Serialize;
+use serde_json;
+
+use crate::secret;
+
+/// Exit codes of the binary, for scripts driving it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// Synchronized something without a failure
+    Success = 0,
+    /// Config error or anything that stopped the run
+    Fatal = 1,
+    /// Some galleries failed
+    PartialFailure = 2,
+    /// Nothing failed, but nothing new either
+    NothingNew = 3,
+}
+
+impl ExitCode {
+    pub fn of(failures: &[Failure], done: u64) -> Self {
+        if !failures.is_empty() {
+            Self::PartialFailure
+        } else if done == 0 {
+            Self::NothingNew
+        } else {
+            Self::Success
+        }
+    }
+
+    pub fn code(&self) -> i32 {
+        *self as i32
+    }
+}
+
+/// A gallery that failed in a run
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Failure {
+    pub id: u32,
+    pub error: String,
+}
+
+impl Failure {
+    pub fn new(id: u32, error: impl Display) -> Self {
+        Self {
+            id,
+            error: secret::redact(&error.to_string()),
+        }
+    }
+}
+
+/// `--error-format text|json`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    Text,
+    Json,
+}
+
+impl Default for ErrorFormat {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+impl FromStr for ErrorFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow::Error::msg(format!("Unknown error format `{}`", s))),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    exit_code: i32,
+    /// Error of `Fatal`
+    error: Option<String>,
+    failures: Vec<&'a Failure>,
+}
+
+impl ErrorFormat {
+    pub fn render(
+        &self,
+        exit_code: ExitCode,
+        error: Option<&anyhow::Error>,
+        failures: &[Failure],
+    ) -> String {
+        // by id, workers fail in any order
+        let mut failures = failures.iter().collect::<Vec<_>>();
+        failures.sort_by_key(|failure| failure.id);
+
+        match self {
+            Self::Text => {
+                let mut lines = failures
+                    .iter()
+                    .map(|failure| format!("{}: {}", failure.id, failure.error))
+                    .collect::<Vec<_>>();
+
+                if let Some(error) = error {
+                    lines.push(format!("Error: {:?}", error));
+                }
+
+                lines.join("\n")
+            }
+            Self::Json => serde_json::to_string(&Report {
+                exit_code: exit_code.code(),
+                error: error.map(|error| format!("{:#}", error)),
+                failures,
+            })
+            .unwrap(),
+        }
+    }
+
+    /// Writes failures to stderr
+    pub fn report(&self, exit_code: ExitCode, error: Option<&anyhow::Error>, failures: &[Failure]) {
+        let rendered = self.render(exit_code, error, failures);
+
+        if !rendered.is_empty() {
+            let _ = writeln!(io::stderr(), "{}", rendered);
+        }
+    }
+}
+
+impl Display for ExitCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} ({})", self, self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorFormat, ExitCode, Failure};
+
+    #[test]
+    fn exit_code_of_run() -> anyhow::Result<()> {
+        let failures = vec![Failure::new(1724122, "404 Not Found")];
+
+        assert_eq!(ExitCode::PartialFailure, ExitCode::of(&failures, 10));
+        assert_eq!(ExitCode::NothingNew, ExitCode::of(&[], 0));
+        assert_eq!(ExitCode::Success, ExitCode::of(&[], 10));
+        assert_eq!(2, ExitCode::PartialFailure.code());
+
+        Ok(())
+    }
+
+    #[test]
+    fn render_failures_as_json() -> anyhow::Result<()> {
+        let failures = vec![Failure::new(1724122, "404 Not Found")];
+
+        assert_eq!(
+            r#"{"exit_code":2,"error":null,"failures":[{"id":1724122,"error":"404 Not Found"}]}"#,
+            ErrorFormat::Json.render(ExitCode::PartialFailure, None, &failures)
+        );
+        assert_eq!(
+            "1724122: 404 Not Found",
+            "text"
+                .parse::<ErrorFormat>()?
+                .render(ExitCode::PartialFailure, None, &failures)
+        );
+
+        // sorted by id, in whatever order they failed
+        let failures = vec![
+            Failure::new(1724122, "404 Not Found"),
+            Failure::new(1721169, "timed out"),
+        ];
+
+        assert_eq!(
+            "1721169: timed out\n1724122: 404 Not Found",
+            ErrorFormat::Text.render(ExitCode::PartialFailure, None, &failures)
+        );
+
+        Ok(())
+    }
+}
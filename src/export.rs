@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow;
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json;
+use zstd;
+
+use crate::catalog::{Catalog, Entry};
+use crate::compress;
+use crate::manifest::Manifest;
+use crate::storage::Storage;
+
+/// # CatalogExport
+/// What a synchronizer knows, moved to another machine by `catalog export` and `catalog import`
+///
+/// Written as json, compressed with zstd when the path ends with `.zst`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CatalogExport {
+    pub catalog: BTreeMap<u32, Entry>,
+    /// Of galleries in storage
+    #[serde(default)]
+    pub manifests: Vec<Manifest>,
+    /// `checkpoint.txt` and `checkpoint.{index}-{count}.txt` by filename
+    #[serde(default)]
+    pub checkpoints: BTreeMap<String, String>,
+}
+
+fn is_checkpoint(filename: &str) -> bool {
+    filename.starts_with("checkpoint") && filename.ends_with(".txt")
+}
+
+impl CatalogExport {
+    pub fn new(
+        catalog: &Catalog,
+        storage: Option<&Storage>,
+        checkpoint_dir: impl AsRef<Path>,
+    ) -> anyhow::Result<Self> {
+        let manifests = match storage {
+            Some(storage) => storage
+                .ids()?
+                .into_iter()
+                .filter_map(|id| storage.manifest(id).ok())
+                .collect(),
+            None => vec![],
+        };
+
+        let mut checkpoints = BTreeMap::new();
+
+        for entry in fs::read_dir(checkpoint_dir)? {
+            let entry = entry?;
+            let filename = entry.file_name().to_string_lossy().to_string();
+
+            if is_checkpoint(&filename) {
+                checkpoints.insert(filename, fs::read_to_string(entry.path())?);
+            }
+        }
+
+        Ok(Self {
+            catalog: catalog
+                .iter()
+                .map(|(id, entry)| (*id, entry.clone()))
+                .collect(),
+            manifests,
+            checkpoints,
+        })
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let json = serde_json::to_vec(self)?;
+
+        if is_zst(path) {
+            fs::write(path, zstd::encode_all(json.as_slice(), 0)?)?;
+        } else {
+            fs::write(path, json)?;
+        }
+
+        Ok(())
+    }
+
+    /// Compressed or not, whatever the path ends with
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let json = compress::read(path)?;
+
+        Ok(serde_json::from_slice(&json)?)
+    }
+
+    /// Merges into `catalog` keeping the newer entry,
+    /// writes manifests missing in `storage` and every checkpoint into `checkpoint_dir`
+    pub fn restore(
+        self,
+        catalog: &mut Catalog,
+        storage: Option<&Storage>,
+        checkpoint_dir: impl AsRef<Path>,
+    ) -> anyhow::Result<()> {
+        for (id, entry) in self.catalog {
+            catalog.merge(id, entry);
+        }
+
+        if let Some(storage) = storage {
+            for manifest in &self.manifests {
+                if storage.manifest(manifest.id).is_err() {
+                    storage.write_manifest(manifest)?;
+                }
+            }
+        }
+
+        for (filename, page) in &self.checkpoints {
+            if !is_checkpoint(filename) || filename.contains('/') || filename.contains('\\') {
+                info!("Skipped checkpoint {}", filename);
+                continue;
+            }
+
+            fs::write(checkpoint_dir.as_ref().join(filename), page)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn is_zst(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext == "zst")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::CatalogExport;
+    use crate::catalog::{Catalog, Status};
+
+    #[test]
+    fn export_and_import_catalog() -> anyhow::Result<()> {
+        let dir = env::temp_dir().join("madome_synchronizer_export");
+        let _ = fs::remove_dir_all(&dir);
+        let from = dir.join("from");
+        let to = dir.join("to");
+
+        fs::create_dir_all(&from)?;
+        fs::create_dir_all(&to)?;
+        fs::write(from.join("checkpoint.2-8.txt"), "42")?;
+        fs::write(from.join("fail_store.txt"), "1")?;
+
+        let mut catalog = Catalog::new();
+        catalog.set_status(1724122, Status::Synced);
+
+        let path = dir.join("catalog.json.zst");
+
+        CatalogExport::new(&catalog, None, &from)?.write(&path)?;
+
+        let mut restored = Catalog::new();
+        restored.set_status(1, Status::Imported);
+
+        CatalogExport::read(&path)?.restore(&mut restored, None, &to)?;
+
+        assert_eq!(Some(Status::Synced), restored.status(&1724122));
+        assert_eq!(Some(Status::Imported), restored.status(&1));
+        assert_eq!("42", fs::read_to_string(to.join("checkpoint.2-8.txt"))?);
+        assert!(!to.join("fail_store.txt").exists());
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+
+use anyhow;
+use serde_json;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Lowercased names
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(|x| x.as_str())
+    }
+
+    pub fn json(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+/// Head and body of an HTTP/1.1 request, the body by its Content-Length
+///
+/// A body larger than `max_body_len` is refused, `None` is unlimited
+pub fn read_request(r: impl Read, max_body_len: Option<usize>) -> anyhow::Result<Request> {
+    let mut r = BufReader::new(r);
+
+    let mut line = String::new();
+    r.read_line(&mut line)?;
+
+    let mut request_line = line.split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+
+    if method.is_empty() || path.is_empty() {
+        return Err(anyhow::Error::msg(format!(
+            "Can't parse request line `{}`",
+            line.trim()
+        )));
+    }
+
+    let mut headers = HashMap::new();
+
+    loop {
+        line.clear();
+
+        if r.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+
+        if let Some(i) = line.find(':') {
+            headers.insert(
+                line[..i].trim().to_lowercase(),
+                line[i + 1..].trim().to_string(),
+            );
+        }
+    }
+
+    let len = headers
+        .get("content-length")
+        .map(|x| x.parse::<usize>())
+        .transpose()?
+        .unwrap_or(0);
+
+    if max_body_len.map_or(false, |max| len > max) {
+        return Err(anyhow::Error::msg(format!(
+            "Body of {} bytes is too large",
+            len
+        )));
+    }
+
+    let mut body = vec![0; len];
+    r.read_exact(&mut body)?;
+
+    Ok(Request {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_request;
+
+    #[test]
+    fn read_head_and_body() -> anyhow::Result<()> {
+        let raw = "POST /sync HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 9\r\n\r\n{\"id\": 1}";
+
+        let request = read_request(raw.as_bytes(), None)?;
+
+        assert_eq!("POST", request.method);
+        assert_eq!("/sync", request.path);
+        assert_eq!(Some("application/json"), request.header("content-type"));
+        assert_eq!(serde_json::json!({ "id": 1 }), request.json()?);
+
+        assert!(read_request(raw.as_bytes(), Some(8))
+            .unwrap_err()
+            .to_string()
+            .contains("too large"));
+        assert!(read_request("\r\n".as_bytes(), None).is_err());
+
+        Ok(())
+    }
+}
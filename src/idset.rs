@@ -0,0 +1,190 @@
+use std::io;
+use std::iter::FromIterator;
+use std::path::Path;
+
+use anyhow;
+use roaring::RoaringBitmap;
+use serde::Serialize;
+
+use crate::compress::{self, Compression};
+use crate::shard::Shard;
+
+/// # IdSet
+/// Gallery ids as a roaring bitmap
+///
+/// Ids of hitomi are dense, a whole index takes a few hundred KB
+/// and set operations run on containers instead of each id
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IdSet {
+    inner: RoaringBitmap,
+}
+
+/// What changed from an older set to a newer one
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct IdDiff {
+    pub added: Vec<u32>,
+    pub removed: Vec<u32>,
+}
+
+impl IdSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// True if it wasn't in the set
+    pub fn insert(&mut self, id: u32) -> bool {
+        self.inner.insert(id)
+    }
+
+    pub fn remove(&mut self, id: u32) -> bool {
+        self.inner.remove(id)
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.inner.contains(id)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Ascending
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.inner.iter()
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            inner: &self.inner | &other.inner,
+        }
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self {
+            inner: &self.inner & &other.inner,
+        }
+    }
+
+    /// In `self`, not in `other`
+    pub fn difference(&self, other: &Self) -> Self {
+        Self {
+            inner: &self.inner - &other.inner,
+        }
+    }
+
+    pub fn diff(&self, newer: &Self) -> IdDiff {
+        IdDiff {
+            added: newer.difference(self).iter().collect(),
+            removed: self.difference(newer).iter().collect(),
+        }
+    }
+
+    /// Ids `shard` synchronizes
+    pub fn partition(&self, shard: Shard) -> Self {
+        self.iter().filter(|id| shard.contains(*id)).collect()
+    }
+
+    /// Portable format of roaring, readable by other implementations
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(self.inner.serialized_size());
+        self.inner.serialize_into(&mut buf)?;
+
+        Ok(buf)
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> io::Result<Self> {
+        let inner = RoaringBitmap::deserialize_from(buf)?;
+
+        Ok(Self { inner })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, compression: Compression) -> anyhow::Result<()> {
+        compress::write(path, self.to_bytes()?, compression)
+    }
+
+    /// Empty set if the file doesn't exist yet, compressed or not
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        match compress::read(path) {
+            Ok(buf) => Ok(Self::from_bytes(&buf)?),
+            Err(err) if compress::is_not_found(&err) => Ok(Self::new()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl FromIterator<u32> for IdSet {
+    fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> Self {
+        Self {
+            inner: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<u32> for IdSet {
+    fn extend<I: IntoIterator<Item = u32>>(&mut self, iter: I) {
+        self.inner.extend(iter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::{IdDiff, IdSet};
+    use crate::compress::Compression;
+    use crate::shard::Shard;
+
+    #[test]
+    fn set_operations() -> anyhow::Result<()> {
+        let a = [1, 2, 3, 1724122].iter().copied().collect::<IdSet>();
+        let b = [3, 4, 1724122].iter().copied().collect::<IdSet>();
+
+        assert_eq!(
+            vec![1, 2, 3, 4, 1724122],
+            a.union(&b).iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![3, 1724122],
+            a.intersection(&b).iter().collect::<Vec<_>>()
+        );
+        assert_eq!(vec![1, 2], a.difference(&b).iter().collect::<Vec<_>>());
+        assert_eq!(
+            IdDiff {
+                added: vec![4],
+                removed: vec![1, 2],
+            },
+            a.diff(&b)
+        );
+
+        let shard = "2/2".parse::<Shard>()?;
+
+        assert_eq!(vec![1, 3], a.partition(shard).iter().collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn save_and_load() -> anyhow::Result<()> {
+        let path = env::temp_dir().join("madome_synchronizer_idset.roaring");
+        let _ = std::fs::remove_file(&path);
+
+        assert!(IdSet::load(&path)?.is_empty());
+
+        let ids = (1_700_000..1_750_000).step_by(3).collect::<IdSet>();
+        ids.save(&path, Compression::None)?;
+
+        let loaded = IdSet::load(&path)?;
+
+        assert_eq!(ids, loaded);
+        assert!(loaded.contains(1_700_003));
+        assert!(!loaded.contains(1_700_004));
+
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow;
+use log::info;
+
+use crate::book::parse_book;
+use crate::catalog::{Catalog, Status};
+use crate::manifest::{Manifest, ManifestFile};
+use crate::parser::{GalleryInfo, Parser, ParserRegistry};
+use crate::storage::Storage;
+use crate::utils::get_ext;
+use crate::validate::Strictness;
+
+const IMAGE_EXTS: [&'static str; 6] = ["jpg", "jpeg", "png", "gif", "webp", "avif"];
+
+/// Last number in the name of directory, `[airandou] Tsundere Imouto (1724122)` => 1724122
+pub fn gallery_id_from_name(name: &str) -> Option<u32> {
+    name.split(|c: char| !c.is_ascii_digit())
+        .filter(|x| !x.is_empty())
+        .last()
+        .and_then(|x| x.parse().ok())
+}
+
+/// Images in the directory in order of page, `2.jpg` comes before `10.jpg`
+pub fn image_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .map_or(false, |ext| {
+                    IMAGE_EXTS.contains(&ext.to_lowercase().as_str())
+                })
+        })
+        .collect::<Vec<_>>();
+
+    files.sort_by_key(|path| {
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("")
+            .to_string();
+
+        (gallery_id_from_name(&name), name)
+    });
+
+    Ok(files)
+}
+
+/// Copies images of a directory into storage as if they were downloaded,
+/// only the thumbnail is downloaded
+pub fn import_gallery(
+    id: u32,
+    dir: &Path,
+    storage: &Storage,
+    registry: &ParserRegistry,
+) -> anyhow::Result<Manifest> {
+    let gallery_info = GalleryInfo::new(id).request()?.parse()?;
+    let images = &gallery_info.files;
+    let local_files = image_files(dir)?;
+
+    if images.len() != local_files.len() {
+        return Err(anyhow::Error::msg(format!(
+            "{} has {} images, but gallery {} has {} pages",
+            dir.display(),
+            local_files.len(),
+            id,
+            images.len()
+        )));
+    }
+
+    let (book, extra) = parse_book(&gallery_info, registry, Strictness::default())?;
+
+    let thumbnail = {
+        let image = images
+            .first()
+            .ok_or_else(|| anyhow::Error::msg(format!("gallery {} has no page", id)))?;
+        let (origin_url, buf) = image.download(id, true)?;
+        let filename = format!("thumbnail.{}", get_ext(&origin_url).unwrap_or("jpg"));
+
+        storage.write(id, &filename, &buf)?;
+
+        ManifestFile::new(
+            filename.clone(),
+            format!("image/library/{}/{}", id, filename),
+            origin_url,
+            &buf,
+        )
+    };
+
+    let files = images
+        .iter()
+        .zip(local_files.iter())
+        .enumerate()
+        .map(|(i, (image, local_file))| -> anyhow::Result<ManifestFile> {
+            let (origin_url, _) = image.url(id)?;
+            let ext = local_file
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or("jpg");
+            let filename = format!("{}.{}", i + 1, ext);
+            let buf = fs::read(local_file)?;
+
+            storage.write(id, &filename, &buf)?;
+
+            Ok(ManifestFile::new(
+                filename.clone(),
+                format!("image/library/{}/{}", id, filename),
+                origin_url,
+                &buf,
+            ))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let manifest = Manifest::new(id, book, extra, thumbnail, files);
+
+    storage.write_manifest(&manifest)?;
+
+    Ok(manifest)
+}
+
+/// `import <dir>`, imports every sub directory whose name has a gallery id
+///
+/// Returns ids of imported galleries
+pub fn import_dir(
+    dir: &Path,
+    storage: &Storage,
+    registry: &ParserRegistry,
+    catalog: &mut Catalog,
+) -> anyhow::Result<Vec<u32>> {
+    let mut imported = vec![];
+
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.sort();
+
+    for path in paths {
+        if !path.is_dir() {
+            continue;
+        }
+
+        let id = match path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(gallery_id_from_name)
+        {
+            Some(id) => id,
+            None => {
+                info!("Can't find gallery id in {}", path.display());
+                continue;
+            }
+        };
+
+        if catalog.has(&id) {
+            info!("{}: Already in catalog", id);
+            continue;
+        }
+
+        match import_gallery(id, &path, storage, registry) {
+            Ok(_) => {
+                info!("{}: Imported {}", id, path.display());
+                catalog.set_status(id, Status::Imported);
+                imported.push(id);
+            }
+            Err(err) => info!("{}: Can't import {}: {}", id, path.display(), err),
+        }
+    }
+
+    imported.sort();
+
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::{gallery_id_from_name, image_files};
+
+    #[test]
+    fn gallery_id_from_directory_name() -> anyhow::Result<()> {
+        assert_eq!(Some(1724122), gallery_id_from_name("1724122"));
+        assert_eq!(
+            Some(1724122),
+            gallery_id_from_name("[airandou] Tsundere Imouto (1724122)")
+        );
+        assert_eq!(None, gallery_id_from_name("Tsundere Imouto"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn image_files_in_order_of_page() -> anyhow::Result<()> {
+        let dir = env::temp_dir().join("madome_synchronizer_import");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+
+        for name in &["10.jpg", "2.png", "1.jpg", "info.txt"] {
+            fs::write(dir.join(name), b"")?;
+        }
+
+        let names = image_files(&dir)?
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec!["1.jpg", "2.png", "10.jpg"], names);
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
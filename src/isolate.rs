@@ -0,0 +1,74 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::panic::{self, AssertUnwindSafe};
+
+use anyhow;
+
+/// A panic caught by `catch_panic`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Panicked {
+    pub message: String,
+}
+
+impl Display for Panicked {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Panicked: {}", self.message)
+    }
+}
+
+impl Error for Panicked {}
+
+/// Runs `f`, a panic of it comes back as `Panicked` instead of unwinding further
+///
+/// Unexpected markup can still panic deep in the parsers,
+/// that should fail only the gallery, not the whole synchronize.
+pub fn catch_panic<T, F>(f: F) -> anyhow::Result<T>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(r) => r,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|x| x.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+
+            Err(Panicked { message }.into())
+        }
+    }
+}
+
+pub fn is_panicked(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<Panicked>().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{catch_panic, is_panicked};
+
+    #[test]
+    fn catch_panic_as_error() -> anyhow::Result<()> {
+        let err = catch_panic::<(), _>(|| {
+            let xs: Vec<u32> = vec![];
+            xs.first().unwrap();
+            Ok(())
+        })
+        .unwrap_err();
+
+        assert!(is_panicked(&err));
+
+        let id = 1724122;
+        let err = catch_panic::<(), _>(|| panic!("{} has no gallery-info", id)).unwrap_err();
+
+        assert_eq!("Panicked: 1724122 has no gallery-info", err.to_string());
+
+        let err = catch_panic::<(), _>(|| Err(anyhow::Error::msg("404 Not Found"))).unwrap_err();
+
+        assert!(!is_panicked(&err));
+        assert_eq!(3, catch_panic(|| Ok(3))?);
+
+        Ok(())
+    }
+}
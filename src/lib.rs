@@ -1,5 +1,143 @@
+/// Models the parsers build, the ones of madome_client with `net`
+///
+/// madome_client brings reqwest 0.10 with `blocking`, which doesn't compile to wasm32-unknown-unknown,
+/// so `parser` alone has copies of them
+pub mod models;
+
 pub mod parser;
 
+pub mod urls;
+
 pub mod utils;
 
 pub mod stage;
+
+#[cfg(feature = "net")]
+pub mod client;
+
+#[cfg(feature = "net")]
+pub mod token;
+
+pub mod secret;
+
+pub mod alias;
+
+pub mod book;
+
+pub mod skip;
+
+pub mod validate;
+
+#[cfg(feature = "download")]
+pub mod manifest;
+
+#[cfg(feature = "download")]
+pub mod storage;
+
+#[cfg(feature = "download")]
+pub mod postprocess;
+
+#[cfg(feature = "download")]
+pub mod verify;
+
+pub mod catalog;
+
+#[cfg(feature = "sync")]
+pub mod shard;
+
+#[cfg(feature = "sync")]
+pub mod idset;
+
+#[cfg(feature = "metrics")]
+pub mod progress;
+
+#[cfg(feature = "metrics")]
+pub mod dashboard;
+
+/// `--tui`
+#[cfg(feature = "tui")]
+pub mod tui;
+
+#[cfg(feature = "metrics")]
+pub mod stats;
+
+#[cfg(feature = "sync")]
+pub mod duplicates;
+
+pub mod phash;
+
+#[cfg(feature = "sync")]
+pub mod resync;
+
+#[cfg(feature = "sync")]
+pub mod policy;
+
+pub mod rate_limit;
+
+pub mod memory;
+
+pub mod downtime;
+
+#[cfg(feature = "sync")]
+pub mod subdomain;
+
+pub mod subset;
+
+#[cfg(feature = "sync")]
+pub mod isolate;
+
+#[cfg(feature = "sync")]
+pub mod pipeline;
+
+#[cfg(feature = "sync")]
+pub mod budget;
+
+#[cfg(feature = "download")]
+pub mod maintenance;
+
+pub mod exit;
+
+#[cfg(feature = "sync")]
+pub mod events;
+
+/// Fault injection for resilience tests
+#[cfg(any(test, feature = "chaos"))]
+pub mod chaos;
+
+/// HTTP/1.1 requests read off a socket, by the webhook and the mock server
+#[cfg(any(feature = "webhook", all(test, feature = "net")))]
+pub mod http;
+
+/// Local HTTP server of tests, for the requests to Madome
+#[cfg(all(test, feature = "net"))]
+pub mod mock;
+
+/// zstd of catalog.json, snapshots and event logs, detected when they are read
+pub mod compress;
+
+/// `catalog export`, `catalog import`
+#[cfg(all(feature = "sync", feature = "zstd"))]
+pub mod export;
+
+/// Disk-backed set of ids seen by discovery
+#[cfg(all(feature = "sync", feature = "sled"))]
+pub mod seen;
+
+#[cfg(feature = "sync")]
+pub mod sink;
+
+#[cfg(feature = "sync")]
+pub mod import;
+
+/// `selftest`
+#[cfg(feature = "sync")]
+pub mod selftest;
+
+/// `POST /sync` of WEBHOOK_ADDR
+#[cfg(feature = "webhook")]
+pub mod webhook;
+
+/// Every request is blocking already,
+/// this only adds simple entry points for embedders
+#[cfg(feature = "blocking")]
+pub mod blocking;
@@ -1,141 +1,764 @@
 extern crate madome_synchronizer;
 
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
-// use std::sync::Arc;
+use std::io::{self, IsTerminal, Write};
+use std::iter;
+use std::path::Path;
+use std::sync::Arc;
 use std::sync::Mutex;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use anyhow;
+use anyhow::{self, Context as _};
 use env_logger;
-use log::{info, trace};
+use log::{debug, error, info, trace, warn};
 use madome_client::auth::Token;
 use madome_client::book::{Book, Language};
 use madome_client::{AuthClient, BookClient, FileClient};
 use rayon::prelude::*;
 
-use fp_core::lens::Lens;
-
+use crate::madome_synchronizer::alias::AliasTable;
+use crate::madome_synchronizer::book;
+use crate::madome_synchronizer::budget::{parse_duration, RunBudget};
+use crate::madome_synchronizer::catalog::{Catalog, Status, TombstoneAction, Work};
+use crate::madome_synchronizer::client::{self, parse_resolve, ClientConfig};
+use crate::madome_synchronizer::compress::Compression;
+use crate::madome_synchronizer::dashboard::Dashboard;
+use crate::madome_synchronizer::downtime::{is_under_maintenance, Downtime};
+use crate::madome_synchronizer::duplicates::{find_duplicates, find_similar};
+use crate::madome_synchronizer::events::{Event, EventLog};
+use crate::madome_synchronizer::exit::{ErrorFormat, ExitCode, Failure};
+use crate::madome_synchronizer::export::CatalogExport;
+use crate::madome_synchronizer::idset::IdSet;
+use crate::madome_synchronizer::import::import_dir;
+use crate::madome_synchronizer::isolate::{catch_panic, is_panicked};
+use crate::madome_synchronizer::maintenance::Maintenance;
+use crate::madome_synchronizer::manifest::{Manifest, ManifestFile};
+use crate::madome_synchronizer::memory::{MemoryProfile, Semaphore};
 use crate::madome_synchronizer::parser;
-use crate::madome_synchronizer::parser::Parser;
-
+use crate::madome_synchronizer::parser::{
+    download_image_to, CreatedAtPolicy, FutureDates, LanguageFallback, NozomiIndex, NozomiTarget,
+    Parser, ParserRegistry,
+};
+use crate::madome_synchronizer::phash::{cover_hash, CoverHash};
+use crate::madome_synchronizer::pipeline::{self, PriorityLane};
+use crate::madome_synchronizer::policy::{classify, ErrorPolicy, Policy};
+use crate::madome_synchronizer::postprocess::{ImageHooks, ImageInfo};
+use crate::madome_synchronizer::progress::{self, Progress};
+use crate::madome_synchronizer::rate_limit::{rate_limited_host, retry_after, RateLimiter};
+use crate::madome_synchronizer::resync::{book_patch, parse_fields};
+use crate::madome_synchronizer::shard::{Checkpoint, Shard};
+use crate::madome_synchronizer::sink::Sink;
+use crate::madome_synchronizer::skip::{is_deferred, is_skipped, SizeLimit, SkipReason};
+use crate::madome_synchronizer::stats::LanguageStats;
+use crate::madome_synchronizer::subdomain::{self, SubdomainLimits};
+#[cfg(feature = "tui")]
+use crate::madome_synchronizer::tui::{Sources, Tui};
+use crate::madome_synchronizer::urls;
+use crate::madome_synchronizer::validate::Strictness;
+#[cfg(feature = "webhook")]
+use crate::madome_synchronizer::webhook::Webhook;
+
+use crate::madome_synchronizer::secret;
+use crate::madome_synchronizer::seen::SeenSet;
+use crate::madome_synchronizer::selftest::SelfTest;
 use crate::madome_synchronizer::stage::{self, Stage, StageR, StageUpdater, State};
+use crate::madome_synchronizer::storage::Storage;
+use crate::madome_synchronizer::subset::{self, PageSubset};
+use crate::madome_synchronizer::token::{TokenManager, TokenSource, TokenStore};
 use crate::madome_synchronizer::utils::{get_ext, IntoResultVec, TextStore};
+use crate::madome_synchronizer::verify::{
+    repair_gallery, verify_acknowledgement, verify_gallery, BadFile, UnconfirmedUpload,
+};
 
 const MADOME_URL: &'static str = "https://api.madome.app";
 const FILE_REPOSITORY_URL: &'static str = "https://file.madome.app";
+const BACKOFF_SECS: u64 = 60;
+/// Pages of a run between cross-checks of the nozomi, from the first one
+const CROSS_CHECK_PAGES: usize = 10;
+/// Language of the index `parse_ids` synchronizes, recorded in catalog.json
+const SYNC_LANGUAGE: &'static str = "korean";
 
-fn init_logger() {
-    env_logger::init()
-}
-
-pub struct TokenLens;
+/// Bits two cover hashes differ in at most for `find-similar`
+const SIMILAR_DISTANCE: u32 = 6;
 
-impl Lens<Token, String> for TokenLens {
-    fn get(s: &Token) -> Option<&String> {
-        Some(&s.token)
+/// Tokens never reach the log, even in the errors of madome_client
+fn init_logger() {
+    // logs would draw over the dashboard, errors are listed in it instead
+    if env::args().any(|arg| arg == "--tui") {
+        return env_logger::Builder::new()
+            .filter_level(log::LevelFilter::Off)
+            .init();
     }
 
-    fn set(a: String, _: &Token) -> Token {
-        Token { token: a }
-    }
-}
-
-pub struct TokenManager;
-
-impl TokenManager {
-    pub fn refresh(auth_client: &AuthClient, token: Token) -> anyhow::Result<Token> {
-        let old_token = TokenLens::get(&token).unwrap();
-        let new_token = auth_client.refresh_token(old_token)?;
-
-        fs::write("./.token", &new_token)?;
-
-        let new_token = TokenLens::set(new_token, &token);
+    // over the progress line, which is drawn again after the next page
+    let clear_line = if io::stderr().is_terminal() {
+        progress::CLEAR_LINE
+    } else {
+        ""
+    };
 
-        Ok(new_token)
-    }
+    env_logger::Builder::from_default_env()
+        .format(move |buf, record| {
+            writeln!(
+                buf,
+                "{}[{} {} {}] {}",
+                clear_line,
+                buf.timestamp(),
+                record.level(),
+                record.target(),
+                secret::redact(&record.args().to_string())
+            )
+        })
+        .init()
 }
 
 #[derive(Debug)]
 struct Config {
     infinity_synchronize: bool,
     retry_fail: bool,
+    /// A gallery is synced only when the file repository confirms the checksums of its files
+    confirm_uploads: bool,
     page: usize,
     per_page: usize,
     latency: u64,
 
     specified_id: Option<u32>,
+
+    size_limit: SizeLimit,
+
+    metadata_sources: String,
+    /// Alias table of tags and artists, `AliasTable`
+    aliases: Option<String>,
+    /// Language of books without one, `index` of SYNC_LANGUAGE and `script` of the title
+    language_fallback: LanguageFallback,
+    /// `created_at` in UTC, and dates ahead of the clock by FUTURE_DATES and DATE_SKEW
+    created_at: CreatedAtPolicy,
+
+    storage_dir: Option<String>,
+    /// Run on images in storage_dir, `ImageHooks::from_names`
+    image_hooks: String,
+
+    /// TOKEN_FILE or TOKEN_KEYRING
+    token_source: TokenSource,
+
+    shard: Option<Shard>,
+
+    error_policy: ErrorPolicy,
+
+    nozomi_index: NozomiIndex,
+    /// `--artist` or `--series`, overrides `nozomi_index`
+    target: Option<NozomiTarget>,
+
+    /// requests per second, 0 is unlimited
+    metadata_rate: f64,
+    image_rate: f64,
+    /// Of the Madome API and the file repository
+    madome_rate: f64,
+    /// Downloads in flight by image CDN subdomain, `4,aa=2`
+    subdomain_concurrency: String,
+
+    pages: PageSubset,
+
+    /// Workers, buffers and parses held at once, `MemoryProfile`
+    memory_profile: MemoryProfile,
+    /// Ids waiting for a free worker, discovery pauses when it is full
+    queue_size: usize,
+    /// Pages downloaded by then are committed and the gallery is `Partial`
+    gallery_timeout: Option<Duration>,
+    /// Directory of `SeenSet`, discovery skips ids handled by earlier pages and runs
+    seen_db: Option<String>,
+    /// NDJSON of synced, failed, skipped and removed galleries
+    event_log: Option<String>,
+    /// Of catalog.json, snapshots and EVENT_LOG
+    compression: Compression,
+    /// Metadata checks before the book is uploaded
+    validation: Strictness,
+
+    maintenance_interval: Option<u64>,
+    maintenance_hook: Option<String>,
+    /// Secs between checks of hitomi.la while it is under maintenance
+    downtime_recheck: u64,
+
+    /// Listens for `POST /sync {"id": 1744332}`, `127.0.0.1:8787`
+    webhook_addr: Option<String>,
+    /// Bearer token of webhook requests
+    webhook_token: Option<String>,
+
+    /// Sent by `reconcile` for galleries removed from hitomi, only logged if none
+    tombstone: Option<TombstoneAction>,
+
+    /// The run checkpoints and exits after it, `--max-duration 55m`
+    max_duration: Option<Duration>,
+    /// Galleries synchronized at most, `--max-galleries N`
+    max_galleries: Option<usize>,
 }
 
 impl Config {
-    pub fn new() -> Self {
+    pub fn new() -> anyhow::Result<Self> {
         let infinity_synchronize = env::var("INFINITY").is_ok();
         let retry_fail = env::var("RETRY_FAIL").is_ok();
+        let confirm_uploads = env::var("CONFIRM_UPLOADS").is_ok();
         let page = env::var("PAGE").unwrap_or("1".to_string());
         let per_page = env::var("PER_PAGE").unwrap_or("25".to_string());
         let latency = env::var("LATENCY").unwrap_or("3600".to_string());
-        let specified_id = env::var("ID").ok().and_then(|x| x.parse::<u32>().ok());
+        let specified_id = env::var("ID")
+            .ok()
+            .map(|x| {
+                x.parse::<u32>()
+                    .context("Can't parse ID from environment variables")
+            })
+            .transpose()?;
+        let metadata_sources = env::var("METADATA_SOURCES").unwrap_or("block,html".to_string());
+        let aliases = env::var("ALIASES").ok();
+        let language_fallback = env::var("LANGUAGE_FALLBACK").unwrap_or("index".to_string());
+        let language_fallback = LanguageFallback::parse(&language_fallback, SYNC_LANGUAGE)
+            .context("Can't parse LANGUAGE_FALLBACK, index, script or index,script")?;
+        let future_dates = env::var("FUTURE_DATES")
+            .ok()
+            .map(|x| {
+                x.parse::<FutureDates>()
+                    .context("Can't parse FUTURE_DATES, clamp, reject or flag")
+            })
+            .transpose()?
+            .unwrap_or(FutureDates::Clamp);
+        let date_skew = env::var("DATE_SKEW")
+            .ok()
+            .map(|x| parse_duration(&x).context("Can't parse DATE_SKEW, e.g. 1h"))
+            .transpose()?
+            .unwrap_or(Duration::from_secs(3600));
+        let created_at = CreatedAtPolicy::new()
+            .with_future(future_dates)
+            .with_skew(date_skew);
+        let image_hooks = env::var("IMAGE_HOOKS").unwrap_or_default();
+        let storage_dir = env::var("STORAGE_DIR").ok();
+        // hooks run on the stored file, images would be uploaded untouched
+        if !image_hooks.is_empty() && storage_dir.is_none() {
+            return Err(anyhow::Error::msg(format!(
+                "IMAGE_HOOKS={} needs STORAGE_DIR",
+                image_hooks
+            )));
+        }
+        let token_source = TokenSource::from_env().context("Can't read TOKEN_KEYRING")?;
+        let shard = arg_value("--shard")
+            .or_else(|| env::var("SHARD").ok())
+            .map(|x| x.parse::<Shard>().context("Can't parse SHARD, e.g. 2/8"))
+            .transpose()?;
+        let error_policy = env::var("ERROR_POLICY")
+            .ok()
+            .map(|x| {
+                x.parse::<ErrorPolicy>()
+                    .context("Can't parse ERROR_POLICY, e.g. parse=retry,not_found=skip")
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let nozomi_index = env::var("NOZOMI_INDEX")
+            .ok()
+            .map(|x| {
+                x.parse::<NozomiIndex>()
+                    .context("Can't parse NOZOMI_INDEX, e.g. popular/today")
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let target = arg_value("--artist")
+            .map(NozomiTarget::Artist)
+            .or_else(|| arg_value("--series").map(NozomiTarget::Series));
+        let metadata_rate = env::var("METADATA_RATE").unwrap_or("0".to_string());
+        let image_rate = env::var("IMAGE_RATE").unwrap_or("0".to_string());
+        let madome_rate = env::var("MADOME_RATE").unwrap_or("0".to_string());
+        let subdomain_concurrency = env::var("SUBDOMAIN_CONCURRENCY").unwrap_or_default();
+        let pages = env::var("PAGES")
+            .ok()
+            .map(|x| {
+                x.parse::<PageSubset>()
+                    .context("Can't parse PAGES, e.g. cover, first:5, every:10")
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let memory_profile = memory_profile()?;
+        let queue_size = env::var("QUEUE_SIZE")
+            .ok()
+            .map(|x| {
+                x.parse::<usize>()
+                    .context("Can't parse QUEUE_SIZE from environment variables")
+            })
+            .transpose()?
+            .unwrap_or_else(|| memory_profile.queue_size());
+        let seen_db = env::var("SEEN_DB").ok();
+        let event_log = env::var("EVENT_LOG").ok();
+        let compression = env::var("COMPRESSION")
+            .ok()
+            .map(|x| {
+                x.parse::<Compression>()
+                    .context("Can't parse COMPRESSION, none, zstd or zstd:19")
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let validation = env::var("VALIDATION")
+            .ok()
+            .map(|x| {
+                x.parse::<Strictness>()
+                    .context("Can't parse VALIDATION, off, lenient or strict")
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let gallery_timeout = env::var("GALLERY_TIMEOUT")
+            .ok()
+            .map(|x| parse_duration(&x).context("Can't parse GALLERY_TIMEOUT, e.g. 10m"))
+            .transpose()?;
+        let maintenance_interval = env::var("MAINTENANCE_INTERVAL")
+            .ok()
+            .map(|x| {
+                x.parse::<u64>()
+                    .context("Can't parse MAINTENANCE_INTERVAL from environment variables")
+            })
+            .transpose()?;
+        let maintenance_hook = env::var("MAINTENANCE_HOOK").ok();
+        let downtime_recheck = env::var("DOWNTIME_RECHECK").unwrap_or("300".to_string());
+        let webhook_addr = env::var("WEBHOOK_ADDR").ok();
+        let webhook_token = env::var("WEBHOOK_TOKEN").ok();
+        // `Config` is logged at every start
+        if let Some(webhook_token) = &webhook_token {
+            secret::register(webhook_token);
+        }
+        let tombstone = env::var("TOMBSTONE")
+            .ok()
+            .map(|x| {
+                x.parse::<TombstoneAction>()
+                    .context("Can't parse TOMBSTONE, delete, hide or flag")
+            })
+            .transpose()?;
+        let max_duration = arg_value("--max-duration")
+            .or_else(|| env::var("MAX_DURATION").ok())
+            .map(|x| parse_duration(&x).context("Can't parse MAX_DURATION, e.g. 55m"))
+            .transpose()?;
+        let max_galleries = arg_value("--max-galleries")
+            .or_else(|| env::var("MAX_GALLERIES").ok())
+            .map(|x| {
+                x.parse::<usize>()
+                    .context("Can't parse MAX_GALLERIES from environment variables")
+            })
+            .transpose()?;
+        let max_pages = env::var("MAX_PAGES")
+            .ok()
+            .map(|x| {
+                x.parse::<usize>()
+                    .context("Can't parse MAX_PAGES from environment variables")
+            })
+            .transpose()?;
+        let max_bytes = env::var("MAX_BYTES")
+            .ok()
+            .map(|x| {
+                x.parse::<u64>()
+                    .context("Can't parse MAX_BYTES from environment variables")
+            })
+            .transpose()?;
 
         let page: usize = page
             .parse()
-            .expect("Can't parse PAGE from environment variables");
+            .context("Can't parse PAGE from environment variables")?;
         let per_page: usize = per_page
             .parse()
-            .expect("Can't parse PER_PAGE from environment variables");
+            .context("Can't parse PER_PAGE from environment variables")?;
         let latency: u64 = latency
             .parse()
-            .expect("Can't parse LATENCY from environment variables");
+            .context("Can't parse LATENCY from environment variables")?;
+        let downtime_recheck: u64 = downtime_recheck
+            .parse()
+            .context("Can't parse DOWNTIME_RECHECK from environment variables")?;
+        let metadata_rate: f64 = metadata_rate
+            .parse()
+            .context("Can't parse METADATA_RATE from environment variables")?;
+        let image_rate: f64 = image_rate
+            .parse()
+            .context("Can't parse IMAGE_RATE from environment variables")?;
+        let madome_rate: f64 = madome_rate
+            .parse()
+            .context("Can't parse MADOME_RATE from environment variables")?;
+        // checked here, `SubdomainLimits` is built again for each context
+        subdomain_concurrency
+            .parse::<SubdomainLimits>()
+            .context("Can't parse SUBDOMAIN_CONCURRENCY, e.g. 4,aa=2")?;
 
-        Self {
+        Ok(Self {
             infinity_synchronize,
             retry_fail,
+            confirm_uploads,
             page,
             per_page,
             latency,
 
             specified_id,
-        }
+
+            size_limit: SizeLimit {
+                max_pages,
+                max_bytes,
+            },
+
+            metadata_sources,
+            aliases,
+            language_fallback,
+            created_at,
+
+            storage_dir,
+            image_hooks,
+
+            token_source,
+
+            shard,
+
+            error_policy,
+
+            nozomi_index,
+            target,
+
+            metadata_rate,
+            image_rate,
+            madome_rate,
+            subdomain_concurrency,
+
+            pages,
+
+            memory_profile,
+            queue_size,
+            gallery_timeout,
+            seen_db,
+            event_log,
+            compression,
+            validation,
+
+            maintenance_interval,
+            maintenance_hook,
+            downtime_recheck,
+
+            webhook_addr,
+            webhook_token,
+
+            tombstone,
+
+            max_duration,
+            max_galleries,
+        })
     }
 }
 
-fn parse_ids(page: usize, per_page: usize, language: Language) -> anyhow::Result<Vec<u32>> {
-    trace!("parse_ids({}, {}, {:#?})", page, per_page, language);
-    parser::Nozomi::new(page, per_page, language)
-        .request()?
-        .parse()
+/// MEMORY_PROFILE, also read before `Config` for the thread pool
+fn memory_profile() -> anyhow::Result<MemoryProfile> {
+    let memory_profile = env::var("MEMORY_PROFILE")
+        .ok()
+        .map(|x| {
+            x.parse::<MemoryProfile>()
+                .context("Can't parse MEMORY_PROFILE, default or low")
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    Ok(memory_profile)
+}
+
+/// Metadata sources with the alias table of ALIASES
+fn registry(
+    metadata_sources: &str,
+    aliases: &Option<String>,
+    language_fallback: &LanguageFallback,
+    created_at: &CreatedAtPolicy,
+) -> anyhow::Result<ParserRegistry> {
+    let aliases = match aliases {
+        Some(path) => AliasTable::from_file(path)?,
+        None => AliasTable::new(),
+    };
+
+    Ok(ParserRegistry::from_names(metadata_sources)?
+        .with_aliases(aliases)
+        .with_language_fallback(language_fallback.clone())
+        .with_created_at(created_at.clone()))
+}
+
+/// `--flag value`
+fn arg_value(flag: &str) -> Option<String> {
+    let mut args = env::args().skip_while(|arg| arg != flag);
+
+    args.next().and_then(|_| args.next())
 }
 
-fn parse_images(id: u32) -> anyhow::Result<Vec<parser::File>> {
+fn parse_ids(
+    page: usize,
+    per_page: usize,
+    language: Language,
+    index: NozomiIndex,
+    target: Option<NozomiTarget>,
+    cross_check: bool,
+    progress: &Progress,
+) -> anyhow::Result<Vec<u32>> {
+    trace!(
+        "parse_ids({}, {}, {:#?}, {})",
+        page,
+        per_page,
+        language,
+        index
+    );
+    let nozomi = parser::Nozomi::new(page, per_page, language)
+        .with_index(index)
+        .with_target(target)
+        .request()?;
+
+    if let Some(total_ids) = nozomi.total_ids() {
+        progress.set_total_ids(total_ids);
+    }
+
+    let ids = nozomi.parse()?;
+
+    if cross_check {
+        parser::cross_check(&ids)?;
+    }
+
+    Ok(ids)
+}
+
+/// Gallery info of the images, `parse_book` takes it too
+fn parse_images(id: u32, context: &Context) -> anyhow::Result<parser::GalleryInfoData> {
     trace!("parse_image({})", id);
-    parser::Image::new(id).request()?.parse()
+    let gallery_info = parser::GalleryInfo::new(id).request()?.parse()?;
+
+    if gallery_info.is_video() {
+        return Err(SkipReason::Video {
+            url: gallery_info.video_url(),
+        }
+        .into());
+    }
+
+    context.size_limit.check(&gallery_info.files)?;
+
+    Ok(gallery_info)
 }
 
-fn add_image(id: u32, page: usize, image: &parser::File, token: &Token) -> anyhow::Result<String> {
-    let file_client = FileClient::new(FILE_REPOSITORY_URL);
+/// Shared by every gallery in a synchronize cycle
+struct Context {
+    token: TokenStore,
+    fail_store: Mutex<TextStore<u32>>,
+    defer_store: Mutex<TextStore<u32>>,
+    quarantine_store: Mutex<TextStore<u32>>,
+    error_policy: ErrorPolicy,
+    size_limit: SizeLimit,
+    registry: ParserRegistry,
+    storage: Option<Storage>,
+    /// Images on their way to the file repository without STORAGE_DIR, removed once uploaded
+    spool: Storage,
+    hooks: ImageHooks,
+    /// `Sink` of FILE_REPOSITORY_URL with CONFIRM_UPLOADS
+    uploads: Option<Sink>,
+    catalog: Mutex<Catalog>,
+    progress: Arc<Progress>,
+    /// ltn.hitomi.la and hitomi.la
+    metadata_limit: Arc<RateLimiter>,
+    /// image CDNs
+    image_limit: Arc<RateLimiter>,
+    /// Madome API and file repository, the one of `token`
+    madome_limit: Arc<RateLimiter>,
+    subdomain_limits: SubdomainLimits,
+    /// Drawn by `--tui`
+    dashboard: Arc<Dashboard>,
+    pages: PageSubset,
+    /// Only with all pages, a subset of a subset can't be told apart
+    gallery_timeout: Option<Duration>,
+    validation: Strictness,
+    /// Galleries failed in this run, for the exit code
+    failures: Mutex<Vec<Failure>>,
+    maintenance: Option<Maintenance>,
+    /// Workers pause on the maintenance page of hitomi.la
+    downtime: Downtime,
+    /// Ids requested by webhook, synchronized before discovered ones
+    requested: Arc<PriorityLane<u32>>,
+    /// Images between download and upload, `MemoryProfile::image_buffers`
+    image_buffers: Semaphore,
+    /// Hashed as the cover is downloaded, recorded in catalog once the gallery is synchronized
+    cover_hashes: Mutex<BTreeMap<u32, CoverHash>>,
+    seen: Option<SeenSet>,
+    events: Option<EventLog>,
+}
+
+/// Appends to EVENT_LOG if it is set, a synchronize goes on without it
+fn record(event: Event, events: &Option<EventLog>) {
+    if let Some(events) = events {
+        if let Err(err) = events.append(&event) {
+            error!("Can't append {:?} to EVENT_LOG: {}", event, err);
+        }
+    }
+}
 
-    image.download(id, false).and_then(|(origin_url, buf)| {
-        let ext = get_ext(&origin_url).unwrap_or("jpg");
-        let filename = format!("{}.{}", page, ext);
-        let url_path = format!("image/library/{}/{}", id, filename);
+/// Seen by an earlier page or run of SEEN_DB
+fn is_seen(id: u32, context: &Context) -> bool {
+    match &context.seen {
+        Some(seen) => seen.contains(id).unwrap_or_else(|err| {
+            error!("{}: Can't read seen ids: {}", id, err);
+            false
+        }),
+        None => false,
+    }
+}
 
-        file_client.upload(TokenLens::get(token).unwrap(), &url_path, buf)?;
+/// Reports failures of the run and returns its exit code
+fn finish(context: &Context, error_format: ErrorFormat) -> ExitCode {
+    let failures = context.failures.lock().unwrap();
+    let exit_code = ExitCode::of(&failures, context.progress.galleries());
 
-        Ok(url_path)
-    })
+    error_format.report(exit_code, None, &failures);
+
+    exit_code
 }
 
-fn add_thumbnail(id: u32, image: &parser::File, token: &Token) -> anyhow::Result<()> {
-    let file_client = FileClient::new(FILE_REPOSITORY_URL);
+/// Reports failures of a command and returns its exit code
+fn finish_command(failures: &[Failure], done: u64, error_format: ErrorFormat) -> ExitCode {
+    let exit_code = ExitCode::of(failures, done);
 
-    image.download(id, true).and_then(|(origin_url, buf)| {
-        let ext = get_ext(&origin_url).unwrap_or("jpg");
-        let url_path = format!("image/library/{}/thumbnail.{}", id, ext);
-        file_client.upload(TokenLens::get(&token).unwrap(), url_path, buf)
-    })
+    error_format.report(exit_code, None, failures);
+
+    exit_code
+}
+
+/// Subdomain of the image CDN `image` is downloaded from, `aa`
+fn image_subdomain(id: u32, image: &parser::File, is_thumbnail: bool) -> String {
+    image
+        .url(id)
+        .ok()
+        .and_then(|(image_url, thumbnail_url)| {
+            let url = if is_thumbnail {
+                thumbnail_url
+            } else {
+                image_url
+            };
+
+            urls::subdomain(&url).map(|x| x.to_string())
+        })
+        .unwrap_or_default()
+}
+
+/// Downloads an image as `{name}.{ext}` and uploads it
+fn add_image_file(
+    id: u32,
+    name: &str,
+    image: &parser::File,
+    is_thumbnail: bool,
+    context: &Context,
+) -> anyhow::Result<ManifestFile> {
+    // held until it is uploaded
+    let _buffer = context.image_buffers.acquire();
+    // held while downloading, not while uploading
+    let permit = context
+        .subdomain_limits
+        .acquire(&image_subdomain(id, image, is_thumbnail));
+    context.image_limit.acquire();
+
+    let (image_url, thumbnail_url) = image.url(id)?;
+    let origin_url = if is_thumbnail {
+        thumbnail_url
+    } else {
+        image_url
+    };
+    let ext = get_ext(&origin_url).unwrap_or("jpg");
+    let filename = format!("{}.{}", name, ext);
+
+    // streamed to disk as it is downloaded and uploaded from there,
+    // never whole in memory
+    let storage = context.storage.as_ref().unwrap_or(&context.spool);
+
+    let download = context.dashboard.download(id, &filename);
+    let digest = storage.write_with(id, &filename, |writer| {
+        download_image_to(id, &origin_url, &mut download.counted(writer))?;
+        Ok(())
+    })?;
+    drop(download);
+    drop(permit);
+
+    let r = upload_image_file(
+        id,
+        &filename,
+        &origin_url,
+        is_thumbnail,
+        digest,
+        storage,
+        context,
+    );
+
+    if context.storage.is_none() {
+        let _ = fs::remove_file(storage.path(id, &filename));
+    }
+
+    r
+}
+
+/// Uploads an image written by `add_image_file`, after the hooks
+fn upload_image_file(
+    id: u32,
+    filename: &str,
+    origin_url: &str,
+    is_thumbnail: bool,
+    mut digest: (u64, String),
+    storage: &Storage,
+    context: &Context,
+) -> anyhow::Result<ManifestFile> {
+    if !context.hooks.is_empty() {
+        let image = ImageInfo {
+            id,
+            filename,
+            source_url: origin_url,
+            is_thumbnail,
+        };
+
+        context.hooks.run(&storage.path(id, filename), &image)?;
+
+        // hooks may have rewritten it
+        digest = storage.digest(id, filename)?;
+    }
+
+    if is_thumbnail {
+        hash_cover(id, &storage.read(id, filename)?, context);
+    }
+
+    let url_path = format!("image/library/{}/{}", id, filename);
+
+    Sink::new(FILE_REPOSITORY_URL)?.upload_file(
+        &context.token,
+        &url_path,
+        storage.path(id, filename),
+    )?;
+
+    context.progress.add_bytes(digest.0 as usize);
+
+    Ok(ManifestFile::from_digest(
+        filename.to_string(),
+        url_path,
+        origin_url.to_string(),
+        digest,
+    ))
+}
+
+fn add_image(
+    id: u32,
+    page: usize,
+    image: &parser::File,
+    context: &Context,
+) -> anyhow::Result<ManifestFile> {
+    add_image_file(id, &page.to_string(), image, false, context)
 }
 
-fn add_image_list_txt(id: u32, image_list: &Vec<String>, token: &Token) -> anyhow::Result<()> {
+/// A cover that can't be decoded, e.g. AVIF, is only left out of `find-similar`
+fn hash_cover(id: u32, buf: &[u8], context: &Context) {
+    match cover_hash(buf) {
+        Ok(hash) => {
+            context.cover_hashes.lock().unwrap().insert(id, hash);
+        }
+        Err(err) => debug!("{}: Can't hash cover: {}", id, err),
+    }
+}
+
+fn add_thumbnail(id: u32, image: &parser::File, context: &Context) -> anyhow::Result<ManifestFile> {
+    add_image_file(id, "thumbnail", image, true, context)
+}
+
+fn add_image_list_txt(id: u32, image_list: &Vec<String>, context: &Context) -> anyhow::Result<()> {
     let file_client = FileClient::new(FILE_REPOSITORY_URL);
 
     let image_list_txt = image_list
@@ -146,126 +769,468 @@ fn add_image_list_txt(id: u32, image_list: &Vec<String>, token: &Token) -> anyho
             acc
         });
 
-    file_client.upload(
-        TokenLens::get(token).unwrap(),
-        &format!("image/library/{}/image_list.txt", id),
-        image_list_txt.trim(),
-    )
+    context.token.with_token(|token| {
+        file_client.upload(
+            token,
+            &format!("image/library/{}/image_list.txt", id),
+            image_list_txt.trim(),
+        )
+    })
 }
 
-fn parse_book(id: u32, page: usize) -> anyhow::Result<Book> {
-    let gallery_data = parser::Gallery::new(id).request()?.parse()?;
-    let mut gallery_block_data = parser::GalleryBlock::new(id).request()?.parse()?;
+fn add_manifest(manifest: &Manifest, context: &Context) -> anyhow::Result<()> {
+    let file_client = FileClient::new(FILE_REPOSITORY_URL);
 
-    gallery_block_data.groups = gallery_data.groups;
-    gallery_block_data.characters = gallery_data.characters;
+    let manifest_json = manifest.to_json()?;
 
-    Ok(Book {
-        page_count: page,
-        ..Book::from(gallery_block_data)
-    })
+    context.token.with_token(|token| {
+        file_client.upload(
+            token,
+            &format!("image/library/{}/manifest.json", manifest.id),
+            manifest_json.as_str(),
+        )
+    })?;
+
+    if let Some(storage) = &context.storage {
+        storage.write_manifest(manifest)?;
+    }
+
+    confirm_upload(manifest, context)
+}
+
+/// `UnconfirmedUpload` unless the file repository has every file of `manifest` as it is
+fn confirm_upload(manifest: &Manifest, context: &Context) -> anyhow::Result<()> {
+    let uploads = match &context.uploads {
+        Some(uploads) => uploads,
+        None => return Ok(()),
+    };
+
+    let acknowledgement =
+        uploads.confirm_upload(&context.token, manifest.id, &manifest.checksums())?;
+
+    let files = iter::once(manifest.thumbnail.clone())
+        .chain(manifest.files.iter().cloned())
+        .collect::<Vec<_>>();
+    let bad_files = verify_acknowledgement(&files, &acknowledgement.files);
+
+    if !bad_files.is_empty() {
+        return Err(UnconfirmedUpload {
+            id: manifest.id,
+            bad_files,
+        }
+        .into());
+    }
+
+    debug!("{}: {} files are confirmed", manifest.id, files.len());
+
+    Ok(())
+}
+
+/// Uploads a gallery in local storage instead of downloading it, which was imported
+fn add_stored_gallery(id: u32, storage: &Storage, context: &Context) -> anyhow::Result<()> {
+    let sink = Sink::new(FILE_REPOSITORY_URL)?;
+
+    let manifest = storage.manifest(id)?;
+
+    for file in iter::once(&manifest.thumbnail).chain(manifest.files.iter()) {
+        sink.upload_file(&context.token, &file.path, storage.path(id, &file.filename))?;
+    }
+
+    let image_list = manifest
+        .files
+        .iter()
+        .map(|file| file.path.clone())
+        .collect::<Vec<_>>();
+
+    add_image_list_txt(id, &image_list, context)?;
+    add_manifest(&manifest, context)
 }
 
-fn add_book(book: &Book, token: &Token) -> anyhow::Result<()> {
+fn add_book(book: &Book, context: &Context) -> anyhow::Result<()> {
     let book_client = BookClient::new(MADOME_URL);
 
     // let book: Book = book.into();
-    book_client.create_book(TokenLens::get(token).unwrap(), book)
+    context
+        .token
+        .with_token(|token| book_client.create_book(token, book))
+}
+
+/// Sends the failed gallery to the store its `Policy` says
+fn route_failure(id: u32, err: &anyhow::Error, context: &Context) {
+    context.cover_hashes.lock().unwrap().remove(&id);
+
+    // not a failure of the gallery, `sync_after_downtime` synchronizes it again
+    if is_under_maintenance(err) {
+        info!("{}: {}", id, err);
+        return;
+    }
+
+    if is_skipped(err) {
+        record(
+            Event::Skipped {
+                id,
+                reason: err.to_string(),
+            },
+            &context.events,
+        );
+
+        if is_deferred(err) {
+            context.defer_store.lock().unwrap().add(id);
+        }
+
+        return;
+    }
+
+    let class = classify(err);
+
+    record(
+        Event::Failed {
+            id,
+            class: class.to_string(),
+            policy: context.error_policy.get(class).to_string(),
+            error: secret::redact(&err.to_string()),
+        },
+        &context.events,
+    );
+
+    if context.error_policy.get(class) != Policy::Skip {
+        context.failures.lock().unwrap().push(Failure::new(id, err));
+    }
+
+    context
+        .dashboard
+        .error(id, secret::redact(&format!("{} error: {}", class, err)));
+
+    match context.error_policy.get(class) {
+        Policy::Skip => info!("{}: Skipped {} error", id, class),
+        Policy::Retry => context.fail_store.lock().unwrap().add(id),
+        Policy::Backoff => {
+            context.fail_store.lock().unwrap().add(id);
+
+            // every request to the host waits as long as it asked
+            if let Some(wait) = retry_after(err) {
+                let host = rate_limited_host(err).unwrap_or_default();
+
+                info!(
+                    "{}: Hold requests to {} {} secs for Retry-After of {} error",
+                    id,
+                    host,
+                    wait.as_secs(),
+                    class
+                );
+                limit_of_host(&host, context).pause(wait);
+                context.progress.pause(wait);
+                return;
+            }
+
+            info!(
+                "{}: Back off {} secs after {} error",
+                id, BACKOFF_SECS, class
+            );
+            thread::sleep(Duration::from_secs(BACKOFF_SECS));
+        }
+        Policy::Quarantine => {
+            info!("{}: Quarantined after {} error: {}", id, class, err);
+            context.quarantine_store.lock().unwrap().add(id);
+        }
+    }
+}
+
+/// Limiter of the requests to `host`
+fn limit_of_host<'a>(host: &str, context: &'a Context) -> &'a RateLimiter {
+    let is_host_of = |url: &str| urls::host(url) == Some(host);
+
+    if is_host_of(MADOME_URL) || is_host_of(FILE_REPOSITORY_URL) {
+        &context.madome_limit
+    } else if is_host_of(urls::hitomi()) || is_host_of(urls::ltn()) {
+        &context.metadata_limit
+    } else {
+        &context.image_limit
+    }
+}
+
+/// `sync_gallery`, but a panic of it fails only the gallery
+fn sync(id: u32, context: &Context, sync_images: bool, sync_info: bool) -> anyhow::Result<()> {
+    let r = catch_panic(|| sync_gallery(id, context, sync_images, sync_info)).map_err(|err| {
+        if is_panicked(&err) {
+            error!("{}: {}", id, err);
+            route_failure(id, &err, context);
+        }
+        err
+    });
+
+    // images are removed from the spool as they are uploaded, only the directory is left
+    if context.storage.is_none() {
+        let _ = fs::remove_dir(context.spool.gallery_dir(id));
+    }
+
+    r
 }
 
-fn sync(
+/// Blocks until hitomi.la is back from maintenance, checked with the first page of nozomi
+fn wait_downtime(context: &Context) {
+    context.downtime.pause_until(|| {
+        parser::Nozomi::new(1, 1, SYNC_LANGUAGE).request()?;
+        Ok(())
+    });
+}
+
+/// `sync`, again after the downtime if hitomi.la went under maintenance
+fn sync_after_downtime(
     id: u32,
-    token: &Token,
-    fail_store: &Mutex<TextStore<u32>>,
+    context: &Context,
     sync_images: bool,
     sync_info: bool,
 ) -> anyhow::Result<()> {
-    let stage_updater = StageUpdater::new(id);
+    loop {
+        context.downtime.wait();
+
+        match sync(id, context, sync_images, sync_info) {
+            Err(err) if is_under_maintenance(&err) => wait_downtime(context),
+            r => return r,
+        }
+    }
+}
+
+fn sync_gallery(
+    id: u32,
+    context: &Context,
+    sync_images: bool,
+    sync_info: bool,
+) -> anyhow::Result<()> {
+    let stage_updater = StageUpdater::new(id).with_dashboard(Arc::clone(&context.dashboard));
 
     let parse_images = |id: u32| {
         stage::update(&stage_updater, Stage::ParseImages, || {
-            let r = parse_images(id);
+            let r = parse_images(id, context);
             StageR(State::Fulfilled, None, r)
         })
     };
 
-    let add_thumbnail = |id: u32, image: &parser::File, token: &Token| {
+    let add_thumbnail = |id: u32, image: &parser::File| {
         stage::update(&stage_updater, Stage::AddThumbnail, || {
-            let r = add_thumbnail(id, image, token);
+            let r = add_thumbnail(id, image, context);
             StageR(State::Fulfilled, None, r)
         })
     };
 
-    let add_image =
-        |id: u32, current_page: usize, max_page: usize, image: &parser::File, token: &Token| {
-            stage::update(&stage_updater, Stage::AddImages, || {
-                let r = add_image(id, current_page, image, token);
-                StageR(State::Pending, Some(max_page), r)
-            })
-        };
+    let add_image = |id: u32, current_page: usize, max_page: usize, image: &parser::File| {
+        stage::update(&stage_updater, Stage::AddImages, || {
+            let r = add_image(id, current_page, image, context);
+            StageR(State::Pending, Some(max_page), r)
+        })
+    };
 
-    let add_image_list_txt = |id: u32, image_list: &Vec<String>, token: &Token| {
+    let add_image_list_txt = |id: u32, image_list: &Vec<String>| {
         stage::update(&stage_updater, Stage::AddImageList, || {
-            let r = add_image_list_txt(id, image_list, token);
+            let r = add_image_list_txt(id, image_list, context);
             StageR(State::Fulfilled, None, r)
         })
     };
 
-    let parse_book = |id: u32, page: usize| {
+    let add_manifest = |manifest: &Manifest| {
+        stage::update(&stage_updater, Stage::AddManifest, || {
+            let r = add_manifest(manifest, context);
+            StageR(State::Fulfilled, None, r)
+        })
+    };
+
+    let parse_book = |gallery_info: &parser::GalleryInfoData| {
         stage::update(&stage_updater, Stage::ParseBook, || {
-            let r = parse_book(id, page);
+            let r = book::parse_book(gallery_info, &context.registry, context.validation);
             StageR(State::Fulfilled, None, r)
         })
     };
 
-    let add_book = |book: Book, token: &Token| {
+    let add_book = |book: Book| {
         stage::update(&stage_updater, Stage::AddBook, || {
-            let r = add_book(&book, token);
+            let r = add_book(&book, context);
             StageR(State::Fulfilled, None, r)
         })
     };
 
+    let fail_store = &context.fail_store;
+
     if sync_info {
         return parse_images(id)
-            .and_then(|images| Ok(images.len()))
-            .and_then(|images_len| {
-                parse_book(id, images_len)
-                    .and_then(|book| add_book(book, token))
+            .and_then(|gallery_info| {
+                parse_book(&gallery_info)
+                    .and_then(|(book, extra)| {
+                        debug!("{}: translation group = {:?}", id, extra.translation_group);
+                        add_book(book)?;
+
+                        if let Some(work) = extra.work {
+                            context.catalog.lock().unwrap().set_work(id, work);
+                        }
+
+                        record(Event::BookSynced { id }, &context.events);
+
+                        Ok(())
+                    })
                     .and_then(|_| {
                         fail_store.lock().unwrap().remove(&id);
                         Ok(())
                     })
-                    .map_err(|err| {
-                        fail_store.lock().unwrap().add(id);
-                        err
-                    })
+            })
+            .map_err(|err| {
+                route_failure(id, &err, context);
+                err
             });
     }
 
     if sync_images {
+        let imported = context.catalog.lock().unwrap().status(&id) == Some(Status::Imported);
+
+        if let (true, Some(storage)) = (imported, &context.storage) {
+            return stage::update(&stage_updater, Stage::AddImages, || {
+                let r = add_stored_gallery(id, storage, context);
+                StageR(State::Fulfilled, None, r)
+            })
+            .and_then(|_| {
+                let mut catalog = context.catalog.lock().unwrap();
+                catalog.set_status(id, Status::Synced);
+                catalog.set_language(id, SYNC_LANGUAGE);
+                drop(catalog);
+
+                record(
+                    Event::Synced {
+                        id,
+                        pages: PageSubset::All.to_string(),
+                    },
+                    &context.events,
+                );
+                context.progress.add_gallery();
+                fail_store.lock().unwrap().remove(&id);
+                Ok(())
+            })
+            .map_err(|err| {
+                route_failure(id, &err, context);
+                err
+            });
+        }
+
+        let deadline = context.gallery_timeout.map(|x| Instant::now() + x);
+
         return parse_images(id)
-            .and_then(|images| {
-                add_thumbnail(id, &images[0], token)
+            .and_then(|gallery_info| {
+                let images = &gallery_info.files;
+
+                add_thumbnail(id, &images[0])
                     // add images and image_list.txt
-                    .and_then(|_| {
+                    .and_then(|thumbnail| {
                         let images_len = images.len();
-                        images
-                            .par_iter()
+                        let is_timed_out = || deadline.map_or(false, |x| Instant::now() >= x);
+
+                        let pages = images
+                            .iter()
                             .enumerate()
                             .map(|(i, image)| (i + 1, image))
-                            .map(|(page, image)| add_image(id, page, images_len, image, token))
-                            .collect::<Vec<_>>()
-                            .into_result_vec()
+                            .filter(|(page, _)| context.pages.contains(*page))
+                            .collect::<Vec<_>>();
+                        // a run of pages of one subdomain doesn't hold the others
+                        let pages = subdomain::round_robin(pages, |(_, image)| {
+                            image_subdomain(id, image, false)
+                        });
+
+                        let mut files = pages
+                            .par_iter()
+                            .map(|(page, image)| {
+                                if is_timed_out() {
+                                    return (*page, Ok(None));
+                                }
+
+                                (*page, add_image(id, *page, images_len, image).map(Some))
+                            })
+                            .collect::<Vec<_>>();
+                        files.sort_by_key(|(page, _)| *page);
+
+                        let (pages, files): (Vec<_>, Vec<_>) = files.into_iter().unzip();
+
+                        files.into_result_vec().and_then(|files| {
+                            let total = pages.len();
+                            let (pages, files) =
+                                subset::completed(pages.into_iter().zip(files).collect());
+                            // pages done in time, the others are downloaded again with RETRY_FAIL
+                            let done = if files.len() < total {
+                                Some(pages)
+                            } else {
+                                None
+                            };
+
+                            if files.is_empty() {
+                                return Err(anyhow::Error::msg(
+                                    "Gallery timed out before its first page",
+                                ));
+                            }
+
+                            let image_list = files
+                                .iter()
+                                .map(|file| file.path.clone())
+                                .collect::<Vec<_>>();
+
+                            add_image_list_txt(id, &image_list)?;
+
+                            Ok((thumbnail, files, done))
+                        })
+                    })
+                    .and_then(|(thumbnail, files, done)| {
+                        parse_book(&gallery_info).and_then(|(book, extra)| {
+                            let work = extra.work.clone();
+
+                            add_manifest(
+                                &Manifest::new(id, book, extra, thumbnail, files)
+                                    .with_subset(context.pages)
+                                    .with_pages(done.clone()),
+                            )?;
+
+                            Ok((work, done))
+                        })
                     })
-                    .and_then(|image_list| add_image_list_txt(id, &image_list, token))
-                    .and_then(|_| Ok(images.len()))
             })
-            .and_then(|_| {
-                fail_store.lock().unwrap().remove(&id);
+            .and_then(|(work, done)| {
+                let status = if context.pages.is_all() && done.is_none() {
+                    Status::Synced
+                } else {
+                    Status::Partial
+                };
+
+                let mut catalog = context.catalog.lock().unwrap();
+                catalog.set_status(id, status);
+                catalog.set_language(id, SYNC_LANGUAGE);
+                if let Some(work) = work {
+                    catalog.set_work(id, work);
+                }
+                if let Some(hash) = context.cover_hashes.lock().unwrap().remove(&id) {
+                    catalog.set_cover_hash(id, hash);
+                }
+                drop(catalog);
+
+                let pages = match &done {
+                    Some(pages) => subset::format_pages(pages),
+                    None => context.pages.to_string(),
+                };
+
+                record(Event::Synced { id, pages }, &context.events);
+                context.progress.add_gallery();
+
+                // the rest is downloaded by RETRY_FAIL
+                match done {
+                    Some(pages) => {
+                        info!(
+                            "{}: Timed out, committed pages {}",
+                            id,
+                            subset::format_pages(&pages)
+                        );
+                        fail_store.lock().unwrap().add(id);
+                    }
+                    None => {
+                        fail_store.lock().unwrap().remove(&id);
+                    }
+                }
                 Ok(())
             })
             .map_err(|err| {
-                fail_store.lock().unwrap().add(id);
+                route_failure(id, &err, context);
                 err
             });
     }
@@ -273,18 +1238,595 @@ fn sync(
     Ok(())
 }
 
-fn main() -> anyhow::Result<()> {
+/// `verify [--repair]`
+fn verify(
+    storage_dir: Option<String>,
+    hooks: ImageHooks,
+    repair: bool,
+    error_format: ErrorFormat,
+) -> anyhow::Result<ExitCode> {
+    let storage = storage_dir
+        .map(Storage::new)
+        .ok_or_else(|| anyhow::Error::msg("verify needs STORAGE_DIR"))?;
+
+    let mut failures = vec![];
+    let ids = storage.ids()?;
+
+    for id in ids.iter().copied() {
+        let bad_files = match verify_gallery(&storage, id) {
+            Ok(bad_files) => bad_files,
+            Err(err) => {
+                println!("{}: Can't read manifest.json: {}", id, err);
+                failures.push(Failure::new(
+                    id,
+                    format!("Can't read manifest.json: {}", err),
+                ));
+                continue;
+            }
+        };
+
+        for BadFile { file, problem } in &bad_files {
+            println!("{}: {}: {}", id, problem, file.filename);
+        }
+
+        if bad_files.is_empty() {
+            continue;
+        }
+
+        if repair {
+            if let Err(err) = repair_gallery(&storage, id, &bad_files, &hooks) {
+                println!("{}: Can't repair: {}", id, err);
+                failures.push(Failure::new(id, format!("Can't repair: {}", err)));
+            }
+        } else {
+            failures.push(Failure::new(
+                id,
+                format!("{} files are missing or corrupt", bad_files.len()),
+            ));
+        }
+    }
+
+    Ok(finish_command(&failures, ids.len() as u64, error_format))
+}
+
+/// `import <dir>`
+fn import(
+    dir: Option<&String>,
+    config: Config,
+    error_format: ErrorFormat,
+) -> anyhow::Result<ExitCode> {
+    let dir = dir.ok_or_else(|| anyhow::Error::msg("import needs <dir>"))?;
+    let storage = config
+        .storage_dir
+        .map(Storage::new)
+        .ok_or_else(|| anyhow::Error::msg("import needs STORAGE_DIR"))?;
+    let registry = registry(
+        &config.metadata_sources,
+        &config.aliases,
+        &config.language_fallback,
+        &config.created_at,
+    )?;
+    let mut catalog = Catalog::from_file("./catalog.json")?;
+
+    let imported = import_dir(Path::new(dir), &storage, &registry, &mut catalog)?;
+
+    catalog.synchronize("./catalog.json", config.compression)?;
+
+    println!("Imported {} galleries: {:?}", imported.len(), imported);
+
+    Ok(finish_command(&[], imported.len() as u64, error_format))
+}
+
+/// `resync --fields tags,characters`
+///
+/// Patches only given fields of galleries already synchronized, images are untouched
+fn resync(
+    fields: Option<String>,
+    config: Config,
+    error_format: ErrorFormat,
+) -> anyhow::Result<ExitCode> {
+    let fields =
+        fields.ok_or_else(|| anyhow::Error::msg("resync needs --fields, e.g. tags,characters"))?;
+    let fields = parse_fields(&fields)?;
+    let registry = registry(
+        &config.metadata_sources,
+        &config.aliases,
+        &config.language_fallback,
+        &config.created_at,
+    )?;
+    let mut catalog = Catalog::from_file("./catalog.json")?;
+
+    let auth_client = AuthClient::new(MADOME_URL);
+    let token = load_token(&auth_client, &config.token_source)?;
+    let token = TokenStore::new(auth_client, config.token_source.clone(), token)
+        .with_limit(Arc::new(RateLimiter::new(config.madome_rate)));
+    let sink = Sink::new(MADOME_URL)?;
+
+    let ids = match config.specified_id {
+        Some(id) => vec![id],
+        None => catalog
+            .iter()
+            .filter(|(_, entry)| entry.status == Status::Synced)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>(),
+    };
+
+    let mut failures = vec![];
+    let mut done = 0;
+
+    for id in ids {
+        let r = registry.fetch(id).and_then(|metadata_book| {
+            sink.patch_book(&token, id, &book_patch(&metadata_book, &fields))?;
+
+            Ok(Work::of(&metadata_book))
+        });
+
+        match r {
+            Ok(work) => {
+                for field in &fields {
+                    catalog.set_refreshed(id, field.as_str());
+                }
+                if let Some(work) = work {
+                    catalog.set_work(id, work);
+                }
+                info!("Resynchronized {}", id);
+                done += 1;
+            }
+            Err(err) => {
+                println!("{}: Can't resync: {}", id, err);
+                failures.push(Failure::new(id, format!("Can't resync: {}", err)));
+            }
+        }
+    }
+
+    catalog.synchronize("./catalog.json", config.compression)?;
+
+    Ok(finish_command(&failures, done, error_format))
+}
+
+/// `reconcile`, checks that synchronized galleries (or ID) are still on hitomi
+fn reconcile(config: Config, error_format: ErrorFormat) -> anyhow::Result<ExitCode> {
+    let mut catalog = Catalog::from_file("./catalog.json")?;
+    let compression = config.compression;
+    let events = config
+        .event_log
+        .map(|path| EventLog::open(path, compression))
+        .transpose()?;
+
+    let tombstone = match config.tombstone {
+        Some(action) => {
+            let auth_client = AuthClient::new(MADOME_URL);
+            let token = load_token(&auth_client, &config.token_source)?;
+
+            Some((
+                action,
+                TokenStore::new(auth_client, config.token_source.clone(), token)
+                    .with_limit(Arc::new(RateLimiter::new(config.madome_rate))),
+                Sink::new(MADOME_URL)?,
+            ))
+        }
+        None => None,
+    };
+
+    let ids = match config.specified_id {
+        Some(id) => vec![id],
+        None => catalog
+            .iter()
+            .filter(|(_, entry)| entry.status != Status::Imported && entry.tombstone.is_none())
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>(),
+    };
+
+    let mut failures = vec![];
+    let mut done = 0;
+
+    client::limit_metadata(Arc::new(RateLimiter::new(config.metadata_rate)));
+
+    for id in ids {
+        let r = parser::exists(id).and_then(|exists| match (exists, &tombstone) {
+            (true, _) => Ok(()),
+            (false, Some((action, token, sink))) => {
+                sink.tombstone(token, id, *action)?;
+                catalog.set_tombstone(id, *action);
+                info!("{}: Removed upstream, sent {}", id, action);
+                record(
+                    Event::RemovedUpstream {
+                        id,
+                        action: Some(*action),
+                    },
+                    &events,
+                );
+                Ok(())
+            }
+            (false, None) => {
+                info!("{}: Removed upstream, TOMBSTONE is not set", id);
+                record(Event::RemovedUpstream { id, action: None }, &events);
+                Ok(())
+            }
+        });
+
+        match r {
+            Ok(_) => done += 1,
+            Err(err) => {
+                println!("{}: Can't reconcile: {}", id, err);
+                failures.push(Failure::new(id, format!("Can't reconcile: {}", err)));
+            }
+        }
+    }
+
+    catalog.synchronize("./catalog.json", compression)?;
+
+    Ok(finish_command(&failures, done, error_format))
+}
+
+/// `snapshot [--json]`, the whole index of SYNC_LANGUAGE (of SHARD) against the last snapshot
+fn snapshot(config: Config, json: bool) -> anyhow::Result<ExitCode> {
+    let path = match config.shard {
+        Some(shard) => format!(
+            "./snapshot.{}.{}-{}.roaring",
+            SYNC_LANGUAGE, shard.index, shard.count
+        ),
+        None => format!("./snapshot.{}.roaring", SYNC_LANGUAGE),
+    };
+    client::limit_metadata(Arc::new(RateLimiter::new(config.metadata_rate)));
+
+    let per_page = config.memory_profile.snapshot_per_page();
+
+    let mut ids = IdSet::new();
+
+    for page in 1.. {
+        let nozomi = parser::Nozomi::new(page, per_page, SYNC_LANGUAGE).request()?;
+        let mut len = 0;
+
+        // into the set as they are decoded, not through a `Vec` of the page
+        for id in nozomi.ids()? {
+            ids.insert(id?);
+            len += 1;
+        }
+
+        if len < per_page {
+            break;
+        }
+    }
+
+    if let Some(shard) = config.shard {
+        ids = ids.partition(shard);
+    }
+
+    let last = IdSet::load(&path)?;
+    let diff = last.diff(&ids);
+    let not_synced = ids.difference(&Catalog::from_file("./catalog.json")?.ids());
+
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "total": ids.len(),
+                "added": diff.added,
+                "removed": diff.removed,
+                "not_synced": not_synced.len(),
+            })
+        );
+    } else {
+        println!(
+            "total {}, added {}, removed {}, not synchronized {}",
+            ids.len(),
+            diff.added.len(),
+            diff.removed.len(),
+            not_synced.len()
+        );
+
+        if !diff.removed.is_empty() {
+            info!("Removed ids can be sent to Madome with reconcile");
+        }
+    }
+
+    ids.save(&path, config.compression)?;
+
+    Ok(ExitCode::Success)
+}
+
+/// `catalog export --out <path>`, `catalog import <path>`
+fn catalog(args: &[String], config: Config) -> anyhow::Result<ExitCode> {
+    let storage = config.storage_dir.map(Storage::new);
+    let mut catalog = Catalog::from_file("./catalog.json")?;
+
+    match args.first().map(|arg| arg.as_str()) {
+        Some("export") => {
+            let out = arg_value("--out")
+                .ok_or_else(|| anyhow::Error::msg("catalog export needs --out <path>"))?;
+
+            let export = CatalogExport::new(&catalog, storage.as_ref(), ".")?;
+            export.write(&out)?;
+
+            println!(
+                "Exported {} galleries and {} manifests to {}",
+                export.catalog.len(),
+                export.manifests.len(),
+                out
+            );
+
+            Ok(ExitCode::Success)
+        }
+        Some("import") => {
+            let path = args
+                .get(1)
+                .ok_or_else(|| anyhow::Error::msg("catalog import needs <path>"))?;
+
+            let export = CatalogExport::read(path)?;
+            let len = export.catalog.len();
+
+            export.restore(&mut catalog, storage.as_ref(), ".")?;
+            catalog.synchronize("./catalog.json", config.compression)?;
+
+            println!("Imported {} galleries from {}", len, path);
+
+            Ok(ExitCode::Success)
+        }
+        _ => Err(anyhow::Error::msg(
+            "catalog needs export --out <path> or import <path>",
+        )),
+    }
+}
+
+/// `stats [--languages korean,english] [--json]`
+fn stats(languages: Option<String>, json: bool) -> anyhow::Result<ExitCode> {
+    let languages = languages.unwrap_or_else(|| SYNC_LANGUAGE.to_string());
+    let catalog = Catalog::from_file("./catalog.json")?;
+
+    // failed ids aren't in catalog, they are all of the synchronized language
+    let failures = ["./fail_store.txt", "./quarantine_store.txt"]
+        .iter()
+        .map(|path| {
+            TextStore::<u32>::from_file(path)
+                .map(|store| store.iter().count())
+                .unwrap_or(0)
+        })
+        .sum::<usize>();
+
+    for language in languages
+        .split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+    {
+        let remote_total = parser::Nozomi::new(1, 1, language)
+            .request()
+            .map(|nozomi| nozomi.total_ids());
+
+        let remote_total = match remote_total {
+            Ok(total) => total,
+            Err(err) => {
+                error!("{}: Can't get total ids: {}", language, err);
+                None
+            }
+        };
+
+        let is_synced = language.eq_ignore_ascii_case(SYNC_LANGUAGE);
+        let stats = LanguageStats::of(
+            language,
+            is_synced,
+            remote_total,
+            if is_synced { failures } else { 0 },
+            &catalog,
+        );
+
+        if json {
+            println!("{}", serde_json::to_string(&stats)?);
+        } else {
+            println!("{}", stats);
+        }
+    }
+
+    Ok(ExitCode::Success)
+}
+
+/// `selftest`, one request of each kind without synchronizing anything
+fn selftest(config: Config) -> anyhow::Result<ExitCode> {
+    client::limit_metadata(Arc::new(RateLimiter::new(config.metadata_rate)));
+
+    let selftest = SelfTest::run(
+        SYNC_LANGUAGE,
+        &registry(
+            &config.metadata_sources,
+            &config.aliases,
+            &config.language_fallback,
+            &config.created_at,
+        )?,
+        &RateLimiter::new(config.image_rate),
+    );
+
+    print!("{}", selftest);
+
+    if selftest.passed() {
+        Ok(ExitCode::Success)
+    } else {
+        Ok(ExitCode::Fatal)
+    }
+}
+
+/// `duplicates [--json]`, galleries of the same title and artists in catalog.json
+fn duplicates(json: bool) -> anyhow::Result<ExitCode> {
+    let catalog = Catalog::from_file("./catalog.json")?;
+
+    let unknown = catalog
+        .iter()
+        .filter(|(_, entry)| entry.work.is_none())
+        .count();
+
+    if unknown > 0 {
+        info!(
+            "{} galleries have no title recorded, resync --fields title,artists records them",
+            unknown
+        );
+    }
+
+    let groups = find_duplicates(&catalog);
+
+    for group in &groups {
+        if json {
+            println!("{}", serde_json::to_string(group)?);
+        } else {
+            println!("{}", group);
+        }
+    }
+
+    info!("{} groups of duplicates", groups.len());
+
+    Ok(ExitCode::Success)
+}
+
+/// `find-similar [--distance N] [--json]`, galleries of near-duplicate covers in catalog.json
+fn find_similar_covers(distance: Option<String>, json: bool) -> anyhow::Result<ExitCode> {
+    let distance = match distance {
+        Some(x) => x
+            .parse::<u32>()
+            .map_err(|_| anyhow::Error::msg(format!("Can't parse --distance `{}`", x)))?,
+        None => SIMILAR_DISTANCE,
+    };
+    let catalog = Catalog::from_file("./catalog.json")?;
+
+    let pairs = find_similar(&catalog, distance);
+
+    for pair in &pairs {
+        if json {
+            println!("{}", serde_json::to_string(pair)?);
+        } else {
+            println!("{}", pair);
+        }
+    }
+
+    info!("{} pairs of similar covers", pairs.len());
+
+    Ok(ExitCode::Success)
+}
+
+/// Reads the token of TOKEN_FILE or TOKEN_KEYRING and refreshes it
+fn load_token(auth_client: &AuthClient, source: &TokenSource) -> anyhow::Result<Token> {
+    let token = source.load()?;
+
+    TokenManager::refresh(auth_client, source, token)
+}
+
+/// `exists <id>...`
+fn exists(ids: &[String], error_format: ErrorFormat) -> anyhow::Result<ExitCode> {
+    if ids.is_empty() {
+        return Err(anyhow::Error::msg("exists needs <id>..."));
+    }
+
+    let mut failures = vec![];
+
+    for id in ids {
+        let id = id
+            .parse::<u32>()
+            .map_err(|_| anyhow::Error::msg(format!("`{}` is not a gallery id", id)))?;
+
+        if parser::exists(id)? {
+            println!("{}: exists", id);
+        } else {
+            println!("{}: not found", id);
+            failures.push(Failure::new(id, "not found"));
+        }
+    }
+
+    Ok(finish_command(&failures, ids.len() as u64, error_format))
+}
+
+fn main() {
     init_logger();
 
+    let error_format = match arg_value("--error-format").map(|x| x.parse::<ErrorFormat>()) {
+        Some(Ok(error_format)) => error_format,
+        Some(Err(err)) => {
+            ErrorFormat::Text.report(ExitCode::Fatal, Some(&err), &[]);
+            std::process::exit(ExitCode::Fatal.code())
+        }
+        None => ErrorFormat::default(),
+    };
+
+    let exit_code = match run(error_format) {
+        Ok(exit_code) => exit_code,
+        Err(err) => {
+            error_format.report(ExitCode::Fatal, Some(&err), &[]);
+            ExitCode::Fatal
+        }
+    };
+
+    std::process::exit(exit_code.code())
+}
+
+/// Options of the shared client, read once at the start
+fn client_config() -> anyhow::Result<ClientConfig> {
+    let resolve = match env::var("RESOLVE") {
+        Ok(x) => parse_resolve(&x)?,
+        Err(_) => vec![],
+    };
+    let pool_max_idle_per_host = env::var("POOL_MAX_IDLE_PER_HOST")
+        .ok()
+        .map(|x| x.parse::<usize>())
+        .transpose()?;
+    let http2 = env::var("HTTP2").map_or(true, |x| x != "0" && x != "false");
+    let tcp_keepalive = env::var("TCP_KEEPALIVE")
+        .ok()
+        .map(|x| x.parse::<u64>().map(Duration::from_secs))
+        .transpose()?;
+
+    Ok(ClientConfig {
+        resolve,
+        pool_max_idle_per_host,
+        http2,
+        tcp_keepalive,
+    })
+}
+
+fn run(error_format: ErrorFormat) -> anyhow::Result<ExitCode> {
+    client::init(&client_config()?)?;
+
+    let args = env::args().collect::<Vec<_>>();
+    let has_flag = |flag: &str| args.iter().any(|arg| arg == flag);
+
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("verify") => {
+            let config = Config::new()?;
+
+            return verify(
+                config.storage_dir,
+                ImageHooks::from_names(&config.image_hooks)?,
+                has_flag("--repair"),
+                error_format,
+            );
+        }
+        Some("import") => return import(args.get(2), Config::new()?, error_format),
+        Some("catalog") => return catalog(&args[2..], Config::new()?),
+        Some("exists") => {
+            let ids = args[2..]
+                .iter()
+                .take_while(|arg| !arg.starts_with("--"))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            return exists(&ids, error_format);
+        }
+        Some("reconcile") => return reconcile(Config::new()?, error_format),
+        Some("stats") => return stats(arg_value("--languages"), has_flag("--json")),
+        Some("duplicates") => return duplicates(has_flag("--json")),
+        Some("find-similar") => {
+            return find_similar_covers(arg_value("--distance"), has_flag("--json"))
+        }
+        Some("selftest") => return selftest(Config::new()?),
+        Some("snapshot") => return snapshot(Config::new()?, has_flag("--json")),
+        Some("resync") => return resync(arg_value("--fields"), Config::new()?, error_format),
+        _ => {}
+    }
+
     rayon::ThreadPoolBuilder::new()
-        .num_threads(25)
+        .num_threads(memory_profile()?.workers())
         .build_global()
         .unwrap();
 
     loop {
         thread::sleep(Duration::from_secs(3));
 
-        let config = Config::new();
+        let config = Config::new()?;
 
         info!("{:#?}", config);
 
@@ -294,17 +1836,188 @@ fn main() -> anyhow::Result<()> {
             latency,
             infinity_synchronize,
             retry_fail,
+            confirm_uploads,
             specified_id,
+            size_limit,
+            metadata_sources,
+            aliases,
+            language_fallback,
+            created_at,
+            storage_dir,
+            image_hooks,
+            token_source,
+            shard,
+            error_policy,
+            nozomi_index,
+            target,
+            metadata_rate,
+            image_rate,
+            madome_rate,
+            subdomain_concurrency,
+            pages,
+            memory_profile,
+            queue_size,
+            gallery_timeout,
+            seen_db,
+            event_log,
+            compression,
+            validation,
+            maintenance_interval,
+            maintenance_hook,
+            downtime_recheck,
+            webhook_addr,
+            webhook_token,
+            tombstone: _,
+            max_duration,
+            max_galleries,
         } = config;
 
+        let checkpoint = match &target {
+            Some(target) => Checkpoint::named(shard, &target.slug()),
+            None => Checkpoint::new(shard),
+        };
+        let budget = RunBudget::new(max_duration, max_galleries);
+
+        // backfill resumes where it stopped
+        if infinity_synchronize && !retry_fail {
+            if let Some(checkpoint_page) = checkpoint.load() {
+                info!("Resume from page {}", checkpoint_page);
+                page = checkpoint_page;
+            }
+        }
+
         let auth_client = AuthClient::new(MADOME_URL);
         let book_client = BookClient::new(MADOME_URL);
 
-        let token = fs::read("./.token")?;
-        let token = String::from_utf8(token)?.trim().to_string();
-        let token = Token { token };
-        let token = TokenManager::refresh(&auth_client, token)?;
-        let fail_store = Mutex::new(TextStore::from_file("./fail_store.txt")?);
+        let token = load_token(&auth_client, &token_source)?;
+        let madome_limit = Arc::new(RateLimiter::new(madome_rate));
+
+        let context = Context {
+            token: TokenStore::new(auth_client, token_source, token)
+                .with_limit(Arc::clone(&madome_limit)),
+            fail_store: Mutex::new(TextStore::from_file("./fail_store.txt")?),
+            defer_store: Mutex::new(TextStore::from_file("./defer_store.txt")?),
+            quarantine_store: Mutex::new(TextStore::from_file("./quarantine_store.txt")?),
+            error_policy,
+            // specified id is synchronized regardless of size
+            size_limit: if specified_id.is_some() {
+                SizeLimit::default()
+            } else {
+                size_limit
+            },
+            registry: registry(&metadata_sources, &aliases, &language_fallback, &created_at)?
+                .with_parse_limit(memory_profile.html_parses()),
+            storage: storage_dir.map(Storage::new),
+            spool: Storage::new(env::temp_dir().join("madome_synchronizer_spool")),
+            hooks: ImageHooks::from_names(&image_hooks)?,
+            uploads: if confirm_uploads {
+                Some(Sink::new(FILE_REPOSITORY_URL)?)
+            } else {
+                None
+            },
+            catalog: Mutex::new(Catalog::from_file("./catalog.json")?),
+            progress: Arc::new(Progress::new()),
+            metadata_limit: Arc::new(RateLimiter::new(metadata_rate)),
+            image_limit: Arc::new(RateLimiter::new(image_rate)),
+            madome_limit,
+            subdomain_limits: subdomain_concurrency.parse()?,
+            dashboard: Arc::new(Dashboard::new()),
+            pages,
+            gallery_timeout,
+            validation,
+            failures: Mutex::new(vec![]),
+            maintenance: maintenance_interval
+                .map(|secs| Maintenance::new(Duration::from_secs(secs), maintenance_hook)),
+            downtime: Downtime::new(Duration::from_secs(downtime_recheck)),
+            requested: Arc::new(PriorityLane::new()),
+            cover_hashes: Mutex::new(BTreeMap::new()),
+            image_buffers: Semaphore::new(memory_profile.image_buffers()),
+            seen: seen_db.map(SeenSet::open).transpose()?,
+            events: event_log
+                .map(|path| EventLog::open(path, compression))
+                .transpose()?,
+        };
+
+        client::limit_metadata(Arc::clone(&context.metadata_limit));
+
+        // pages before the checkpoint are done, they count toward the total
+        context.progress.skip_ids(page.saturating_sub(1) * per_page);
+
+        for (id, entry) in context.catalog.lock().unwrap().iter() {
+            if let Some(content_url) = &entry.content_url {
+                context
+                    .registry
+                    .content_urls()
+                    .insert(*id, content_url.clone());
+            }
+        }
+
+        let _tui = if has_flag("--tui") {
+            #[cfg(feature = "tui")]
+            {
+                Some(Tui::spawn(Sources {
+                    dashboard: Arc::clone(&context.dashboard),
+                    progress: Arc::clone(&context.progress),
+                    limiters: vec![
+                        ("metadata", Arc::clone(&context.metadata_limit)),
+                        ("image", Arc::clone(&context.image_limit)),
+                        ("madome", Arc::clone(&context.madome_limit)),
+                    ],
+                })?)
+            }
+            #[cfg(not(feature = "tui"))]
+            {
+                return Err(anyhow::anyhow!("--tui needs cargo build --features tui"));
+            }
+        } else {
+            None
+        };
+
+        if let Some(addr) = &webhook_addr {
+            #[cfg(feature = "webhook")]
+            {
+                Webhook::new(Arc::clone(&context.requested), webhook_token).spawn(addr)?;
+            }
+            #[cfg(not(feature = "webhook"))]
+            {
+                let _ = (addr, webhook_token);
+                return Err(anyhow::anyhow!(
+                    "WEBHOOK_ADDR needs cargo build --features webhook"
+                ));
+            }
+        }
+
+        // the whole series into the priority lane, oldest first, before the pages of its index
+        if has_flag("--backfill") {
+            let name = match &target {
+                Some(NozomiTarget::Series(name)) => name,
+                _ => return Err(anyhow::Error::msg("--backfill needs --series <name>")),
+            };
+            let listing = parser::SeriesListing::new(name.as_str(), SYNC_LANGUAGE);
+
+            let ids = listing.walk(|page| listing.request(page))?;
+            let ids = ids
+                .into_iter()
+                .filter(|id| shard.map_or(true, |shard| shard.contains(*id)))
+                .collect::<Vec<_>>();
+
+            info!(
+                "Backfill {} galleries of series `{}` in publication order",
+                ids.len(),
+                name
+            );
+
+            for id in ids {
+                context.requested.push(id);
+            }
+        }
+
+        let Context {
+            token,
+            fail_store,
+            defer_store,
+            ..
+        } = &context;
 
         /* let is_not_fail = |id: &u32| {
             if retry_fail {
@@ -319,12 +2032,12 @@ fn main() -> anyhow::Result<()> {
         }; */
 
         if let Some(id) = specified_id {
-            let already_images = book_client
-                .get_image_list(TokenLens::get(&token).unwrap(), id)
+            let already_images = token
+                .with_token(|token| book_client.get_image_list(token, id))
                 .is_ok();
 
-            let already_book_info = book_client
-                .get_book_by_id(TokenLens::get(&token).unwrap(), id as i32)
+            let already_book_info = token
+                .with_token(|token| book_client.get_book_by_id(token, id as i32))
                 .is_ok();
 
             if already_images && already_book_info {
@@ -332,17 +2045,26 @@ fn main() -> anyhow::Result<()> {
             }
 
             if !already_images {
-                sync(id, &token, &fail_store, true, false).unwrap_or_else(|_| {});
+                sync(id, &context, true, false).unwrap_or_else(|_| {});
             }
 
             if !already_book_info {
-                sync(id, &token, &fail_store, false, true).unwrap_or_else(|_| {});
+                sync(id, &context, false, true).unwrap_or_else(|_| {});
             }
 
-            std::process::exit(0)
+            // synchronized manually, no longer deferred
+            {
+                let mut defer_store = defer_store.lock().unwrap();
+                defer_store.remove(&id);
+                defer_store.synchronize("./defer_store.txt")?;
+            }
+
+            return Ok(finish(&context, error_format));
         }
 
         let mut prev_last_id: u32 = 0;
+        // of this cycle, cross-checked every CROSS_CHECK_PAGES from it
+        let mut first_page = page;
 
         'a: loop {
             // 파싱할 작품이 존재하는지부터 체크해야됨
@@ -361,17 +2083,27 @@ fn main() -> anyhow::Result<()> {
 
                 Ok(r)
             } else {
-                parse_ids(page, per_page, Language::Korean)
+                parse_ids(
+                    page,
+                    per_page,
+                    Language::Korean,
+                    nozomi_index,
+                    target.clone(),
+                    (page - first_page) % CROSS_CHECK_PAGES == 0,
+                    &context.progress,
+                )
             };
 
             let r = ids
                 .and_then(|ids| {
+                    context.progress.add_ids(ids.len());
+
                     if infinity_synchronize {
                         let curr_last_id = *ids.last().unwrap();
 
                         if curr_last_id == prev_last_id {
                             info!("The end infinity parse, Last ID = {}", curr_last_id);
-                            std::process::exit(0)
+                            std::process::exit(finish(&context, error_format).code())
                         } else {
                             prev_last_id = curr_last_id;
                         }
@@ -380,32 +2112,69 @@ fn main() -> anyhow::Result<()> {
                     Ok(ids)
                 })
                 .and_then(|ids| {
+                    // discovery stops with the budget, the page is resumed from the checkpoint
                     let ids = ids
-                        .into_par_iter()
-                        .filter(|id| {
-                            let already_images = book_client
-                                .get_image_list(TokenLens::get(&token).unwrap(), *id)
-                                .is_ok();
+                        .into_iter()
+                        .filter(|id| shard.map_or(true, |shard| shard.contains(*id)))
+                        // failed ids are seen already, RETRY_FAIL synchronizes them again
+                        .filter(|id| retry_fail || !is_seen(*id, &context));
+                    // requested ids go first, whatever the shard or seen ids are
+                    let mut ids = pipeline::prioritized(&context.requested, ids);
+                    // the budget is checked before an id is pulled, a requested id stays in the lane
+                    let ids = std::iter::from_fn(|| {
+                        if budget.is_exhausted() {
+                            None
+                        } else {
+                            ids.next()
+                        }
+                    })
+                    .inspect(|_| context.dashboard.enqueue());
+
+                    let synced =
+                        pipeline::bounded(ids, queue_size, memory_profile.workers(), |id| {
+                            context.dashboard.dequeue();
+
+                            // a full synchronize downloads the pages left out before
+                            let partial = context.pages.is_all()
+                                && context.catalog.lock().unwrap().status(&id)
+                                    == Some(Status::Partial);
+
+                            let already_images = !partial
+                                && token
+                                    .with_token(|token| book_client.get_image_list(token, id))
+                                    .is_ok();
 
-                            let already_book_info = book_client
-                                .get_book_by_id(TokenLens::get(&token).unwrap(), *id as i32)
+                            let already_book_info = token
+                                .with_token(|token| book_client.get_book_by_id(token, id as i32))
                                 .is_ok();
 
                             /* if already_images && already_book_info {
                                 info!("Already has book in Madome");
                             } */
 
+                            if (!already_images || !already_book_info) && !budget.take() {
+                                warn!("{}: Run budget is exhausted, left for the next run", id);
+                                return false;
+                            }
+
                             if !already_images {
-                                sync(*id, &token, &fail_store, true, false).unwrap_or_else(|_| {});
+                                sync_after_downtime(id, &context, true, false)
+                                    .unwrap_or_else(|_| {});
                             }
 
                             if !already_book_info {
-                                sync(*id, &token, &fail_store, false, true).unwrap_or_else(|_| {});
+                                sync_after_downtime(id, &context, false, true)
+                                    .unwrap_or_else(|_| {});
+                            }
+
+                            if let Some(seen) = &context.seen {
+                                if let Err(err) = seen.insert(id) {
+                                    error!("{}: Can't record seen id: {}", id, err);
+                                }
                             }
 
                             !already_book_info || !already_images
-                        })
-                        .collect::<Vec<_>>();
+                        });
 
                     /* let images_not_ready_ids = ids
                         .clone()
@@ -432,10 +2201,10 @@ fn main() -> anyhow::Result<()> {
                         })
                         .collect::<Vec<_>>(); */
 
-                    Ok(ids)
+                    Ok(synced)
                 })
-                .and_then(|ids| {
-                    if ids.is_empty() && !infinity_synchronize {
+                .and_then(|synced| {
+                    if synced == 0 && !infinity_synchronize && !budget.is_exhausted() {
                         return Err(anyhow::Error::msg("empty ids"));
                     }
 
@@ -470,15 +2239,72 @@ fn main() -> anyhow::Result<()> {
                         .unwrap()
                         .synchronize("./fail_store.txt")
                         .expect("Can't synchronize fail_store");
+                    {
+                        let mut catalog = context.catalog.lock().unwrap();
+
+                        for (id, content_url) in context.registry.content_urls().drain() {
+                            catalog.set_content_url(id, content_url);
+                        }
+
+                        catalog
+                            .synchronize("./catalog.json", compression)
+                            .expect("Can't synchronize catalog");
+                    }
+                    if let Some(events) = &context.events {
+                        events.flush().expect("Can't append events");
+                    }
+                    defer_store
+                        .lock()
+                        .unwrap()
+                        .synchronize("./defer_store.txt")
+                        .expect("Can't synchronize defer_store");
+                    context
+                        .quarantine_store
+                        .lock()
+                        .unwrap()
+                        .synchronize("./quarantine_store.txt")
+                        .expect("Can't synchronize quarantine_store");
+
+                    if let Some(seen) = &context.seen {
+                        seen.flush().expect("Can't synchronize seen ids");
+                    }
+
+                    let snapshot = context.progress.snapshot();
+
+                    if io::stderr().is_terminal() && !has_flag("--tui") {
+                        let _ = progress::draw_line(&mut io::stderr(), &snapshot);
+                    } else {
+                        info!("Progress: {}", snapshot);
+                    }
+
+                    if let Some(maintenance) = &context.maintenance {
+                        if let Err(err) = maintenance.run_if_due(context.storage.as_ref()) {
+                            error!("Maintenance: {}", err);
+                        }
+                    }
 
                     Ok(())
                 });
 
             if let Err(err) = r {
+                // the same page again once it is back
+                if is_under_maintenance(&err) {
+                    wait_downtime(&context);
+                    continue 'a;
+                }
+
                 if err.to_string() == "empty ids" {
+                    // the next scheduled run takes over instead
+                    if budget.has_deadline() {
+                        info!("Nothing new, exit before the next synchronize cycle.");
+                        return Ok(finish(&context, error_format));
+                    }
+
                     info!("Waiting next synchronize cycle.");
                     page = 1;
-                    thread::sleep(Duration::from_secs(latency));
+                    first_page = page;
+                    // a webhook request starts the next cycle early
+                    context.requested.wait(Duration::from_secs(latency));
                     continue 'a;
                 }
 
@@ -486,10 +2312,36 @@ fn main() -> anyhow::Result<()> {
             }
 
             if retry_fail {
-                return Ok(());
+                return Ok(finish(&context, error_format));
+            }
+
+            if budget.is_exhausted() {
+                // galleries of this page left out are synchronized by the next run
+                if infinity_synchronize {
+                    checkpoint.save(page)?;
+                }
+
+                info!(
+                    "Run budget is exhausted after {} galleries, exit at page {}",
+                    budget.taken(),
+                    page
+                );
+
+                if !context.requested.is_empty() {
+                    warn!(
+                        "{} requested galleries are left for the next run",
+                        context.requested.len()
+                    );
+                }
+
+                return Ok(finish(&context, error_format));
             }
 
             page += 1;
+
+            if infinity_synchronize {
+                checkpoint.save(page)?;
+            }
         }
     }
 }
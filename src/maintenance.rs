@@ -0,0 +1,137 @@
+use std::fs;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow;
+use log::info;
+
+use crate::storage::Storage;
+
+/// `.part` files younger than it may still be written
+const PART_FILE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// # Maintenance
+/// Chores of a long-running synchronizer, run at most once per `interval`
+pub struct Maintenance {
+    interval: Duration,
+    /// Shell command run after the other tasks, such as rotating logs
+    hook: Option<String>,
+    last_run: Mutex<Option<Instant>>,
+}
+
+impl Maintenance {
+    pub fn new(interval: Duration, hook: Option<String>) -> Self {
+        Self {
+            interval,
+            hook,
+            last_run: Mutex::new(None),
+        }
+    }
+
+    /// Runs tasks if `interval` has passed since the last run, the first call only starts the clock
+    pub fn run_if_due(&self, storage: Option<&Storage>) -> anyhow::Result<bool> {
+        {
+            let mut last_run = self.last_run.lock().unwrap();
+
+            match *last_run {
+                Some(at) if at.elapsed() < self.interval => return Ok(false),
+                None => {
+                    *last_run = Some(Instant::now());
+                    return Ok(false);
+                }
+                _ => *last_run = Some(Instant::now()),
+            }
+        }
+
+        self.run(storage)?;
+
+        Ok(true)
+    }
+
+    pub fn run(&self, storage: Option<&Storage>) -> anyhow::Result<()> {
+        if let Some(storage) = storage {
+            let purged = purge_part_files(storage, PART_FILE_TTL)?;
+            info!("Maintenance: purged {} orphaned .part files", purged);
+        }
+
+        if let Some(hook) = &self.hook {
+            let status = Command::new("sh").arg("-c").arg(hook).status()?;
+            info!("Maintenance: `{}` exited with {}", hook, status);
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes `.part` files left by interrupted downloads, returns how many
+pub fn purge_part_files(storage: &Storage, older_than: Duration) -> anyhow::Result<usize> {
+    let now = SystemTime::now();
+    let mut purged = 0;
+
+    for id in storage.ids()? {
+        for entry in fs::read_dir(storage.gallery_dir(id))? {
+            let entry = entry?;
+            let path = entry.path();
+
+            let is_part = path.extension().map_or(false, |ext| ext == "part");
+            let age = entry
+                .metadata()?
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .unwrap_or_default();
+
+            if is_part && age >= older_than {
+                fs::remove_file(&path)?;
+                purged += 1;
+            }
+        }
+    }
+
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::time::Duration;
+
+    use super::{purge_part_files, Maintenance};
+    use crate::storage::Storage;
+
+    #[test]
+    fn purge_orphaned_part_files() -> anyhow::Result<()> {
+        let root = env::temp_dir().join("madome_synchronizer_maintenance");
+        let _ = fs::remove_dir_all(&root);
+        let storage = Storage::new(&root);
+
+        storage.write(1724122, "1.jpg", b"image")?;
+        storage.write(1724122, "2.jpg.part", b"ima")?;
+
+        assert_eq!(0, purge_part_files(&storage, Duration::from_secs(60))?);
+        assert_eq!(1, purge_part_files(&storage, Duration::from_secs(0))?);
+        assert!(storage.read(1724122, "1.jpg").is_ok());
+        assert!(storage.read(1724122, "2.jpg.part").is_err());
+
+        fs::remove_dir_all(&root)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_only_when_due() -> anyhow::Result<()> {
+        let maintenance = Maintenance::new(Duration::from_secs(3600), None);
+
+        assert!(!maintenance.run_if_due(None)?);
+        assert!(!maintenance.run_if_due(None)?);
+
+        let maintenance = Maintenance::new(Duration::from_secs(0), None);
+
+        assert!(!maintenance.run_if_due(None)?);
+        assert!(maintenance.run_if_due(None)?);
+
+        Ok(())
+    }
+}
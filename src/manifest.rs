@@ -0,0 +1,236 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use anyhow;
+use madome_client::book::Book;
+use serde::{Deserialize, Serialize};
+use serde_json;
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+use crate::book::BookExtra;
+use crate::subset::PageSubset;
+
+/// A file of gallery uploaded to the file repository
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManifestFile {
+    /// `1.jpg`
+    pub filename: String,
+    /// `image/library/{id}/1.jpg`
+    pub path: String,
+    pub source_url: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl ManifestFile {
+    pub fn new(filename: String, path: String, source_url: String, buf: &[u8]) -> Self {
+        Self {
+            filename,
+            path,
+            source_url,
+            size: buf.len() as u64,
+            sha256: sha256(buf),
+        }
+    }
+
+    /// Of a file already hashed by `HashWriter`
+    pub fn from_digest(
+        filename: String,
+        path: String,
+        source_url: String,
+        (size, sha256): (u64, String),
+    ) -> Self {
+        Self {
+            filename,
+            path,
+            source_url,
+            size,
+            sha256,
+        }
+    }
+
+    pub fn is_same(&self, buf: &[u8]) -> bool {
+        self.size == buf.len() as u64 && self.sha256 == sha256(buf)
+    }
+}
+
+/// Path, size and sha256 of a file, sent with an upload and acknowledged by the file repository
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Checksum {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl From<&ManifestFile> for Checksum {
+    fn from(file: &ManifestFile) -> Self {
+        Self {
+            path: file.path.clone(),
+            size: file.size,
+            sha256: file.sha256.clone(),
+        }
+    }
+}
+
+pub fn sha256(buf: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(buf))
+}
+
+/// Counts size and sha256 of what goes through it
+pub struct HashWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<W: Write> HashWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    /// (size, sha256)
+    pub fn finish(self) -> (u64, String) {
+        (self.size, format!("{:x}", self.hasher.finalize()))
+    }
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+
+        self.hasher.update(&buf[..n]);
+        self.size += n as u64;
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// # Manifest
+/// `manifest.json` written alongside images of a gallery
+///
+/// Enough to verify, re-upload or import a gallery without scraping hitomi again
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Manifest {
+    pub id: u32,
+    pub book: Book,
+    pub extra: BookExtra,
+    pub thumbnail: ManifestFile,
+    /// In order of page
+    pub files: Vec<ManifestFile>,
+    /// Pages that were downloaded, the others are missing from `files`
+    #[serde(default)]
+    pub subset: PageSubset,
+    /// Pages of `subset` done before GALLERY_TIMEOUT, all of them if `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pages: Option<Vec<usize>>,
+    /// Unix timestamp
+    pub synced_at: i64,
+}
+
+impl Manifest {
+    pub fn new(
+        id: u32,
+        book: Book,
+        extra: BookExtra,
+        thumbnail: ManifestFile,
+        files: Vec<ManifestFile>,
+    ) -> Self {
+        Self {
+            id,
+            book,
+            extra,
+            thumbnail,
+            files,
+            subset: PageSubset::All,
+            pages: None,
+            synced_at: OffsetDateTime::now_utc().unix_timestamp(),
+        }
+    }
+
+    pub fn with_subset(mut self, subset: PageSubset) -> Self {
+        self.subset = subset;
+        self
+    }
+
+    pub fn with_pages(mut self, pages: Option<Vec<usize>>) -> Self {
+        self.pages = pages;
+        self
+    }
+
+    /// Thumbnail and every page, what the file repository has to confirm
+    pub fn checksums(&self) -> Vec<Checksum> {
+        std::iter::once(&self.thumbnail)
+            .chain(self.files.iter())
+            .map(Checksum::from)
+            .collect()
+    }
+
+    /// Pages a full synchronize still has to download
+    pub fn missing_pages(&self) -> Vec<usize> {
+        match &self.pages {
+            Some(pages) => (1..=self.book.page_count as usize)
+                .filter(|page| !pages.contains(page))
+                .collect(),
+            None => self.subset.missing_pages(self.book.page_count as usize),
+        }
+    }
+
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(path)?;
+
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{sha256, HashWriter, ManifestFile};
+
+    #[test]
+    fn hash_while_writing() -> anyhow::Result<()> {
+        let mut writer = HashWriter::new(vec![]);
+
+        writer.write_all(b"hel")?;
+        writer.write_all(b"lo")?;
+
+        assert_eq!((5, sha256(b"hello")), writer.finish());
+
+        Ok(())
+    }
+
+    #[test]
+    fn manifest_file_is_same() -> anyhow::Result<()> {
+        let file = ManifestFile::new(
+            "1.jpg".to_string(),
+            "image/library/1/1.jpg".to_string(),
+            "https://aa.hitomi.la/images/1.jpg".to_string(),
+            b"hello",
+        );
+
+        assert_eq!(5, file.size);
+        assert_eq!(
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+            sha256(b"hello")
+        );
+        assert!(file.is_same(b"hello"));
+        assert!(!file.is_same(b"hellp"));
+
+        Ok(())
+    }
+}
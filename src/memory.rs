@@ -0,0 +1,182 @@
+use std::str::FromStr;
+use std::sync::{Condvar, Mutex};
+
+use anyhow;
+
+/// # Semaphore
+/// At most `permits` holders at once, `None` is unlimited
+#[derive(Debug)]
+pub struct Semaphore {
+    permits: Option<usize>,
+    held: Mutex<usize>,
+    released: Condvar,
+}
+
+impl Semaphore {
+    pub fn new(permits: Option<usize>) -> Self {
+        Self {
+            permits,
+            held: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(None)
+    }
+
+    /// Blocks while every permit is held
+    pub fn acquire(&self) -> Permit<'_> {
+        let mut held = self.held.lock().unwrap();
+
+        while self.permits.map_or(false, |permits| *held >= permits) {
+            held = self.released.wait(held).unwrap();
+        }
+
+        *held += 1;
+
+        Permit { semaphore: self }
+    }
+
+    pub fn held(&self) -> usize {
+        *self.held.lock().unwrap()
+    }
+}
+
+impl Default for Semaphore {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+pub struct Permit<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl<'a> Drop for Permit<'a> {
+    fn drop(&mut self) {
+        *self.semaphore.held.lock().unwrap() -= 1;
+        self.semaphore.released.notify_one();
+    }
+}
+
+/// How much a run may hold in memory at once, MEMORY_PROFILE
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryProfile {
+    Default,
+    /// A Raspberry Pi, a few galleries at once and one HTML document parsed at a time
+    Low,
+}
+
+impl Default for MemoryProfile {
+    fn default() -> Self {
+        Self::Default
+    }
+}
+
+impl MemoryProfile {
+    /// Galleries synchronized at once, and threads downloading their pages
+    pub fn workers(&self) -> usize {
+        match self {
+            Self::Default => 25,
+            Self::Low => 4,
+        }
+    }
+
+    /// Ids waiting for a free worker, QUEUE_SIZE overrides it
+    pub fn queue_size(&self) -> usize {
+        match self {
+            Self::Default => 100,
+            Self::Low => 8,
+        }
+    }
+
+    /// Metadata sources fetching and parsing their HTML at once
+    pub fn html_parses(&self) -> Option<usize> {
+        match self {
+            Self::Default => None,
+            Self::Low => Some(1),
+        }
+    }
+
+    /// Images between download and upload at once
+    pub fn image_buffers(&self) -> Option<usize> {
+        match self {
+            Self::Default => None,
+            Self::Low => Some(2),
+        }
+    }
+
+    /// Ids of a nozomi request of `snapshot`, 4 bytes each
+    pub fn snapshot_per_page(&self) -> usize {
+        match self {
+            Self::Default => 100_000,
+            Self::Low => 10_000,
+        }
+    }
+}
+
+impl FromStr for MemoryProfile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let r = match s.trim() {
+            "default" => Self::Default,
+            "low" => Self::Low,
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "Unknown memory profile `{}`",
+                    s
+                )))
+            }
+        };
+
+        Ok(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{MemoryProfile, Semaphore};
+
+    #[test]
+    fn hold_at_most_permits() -> anyhow::Result<()> {
+        let semaphore = Semaphore::new(Some(2));
+        let max_held = AtomicUsize::new(0);
+
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    let _permit = semaphore.acquire();
+                    max_held.fetch_max(semaphore.held(), Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(5));
+                });
+            }
+        });
+
+        assert_eq!(2, max_held.load(Ordering::SeqCst));
+        assert_eq!(0, semaphore.held());
+
+        let unlimited = Semaphore::unlimited();
+        let permits = (0..100).map(|_| unlimited.acquire()).collect::<Vec<_>>();
+        assert_eq!(100, unlimited.held());
+        drop(permits);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_profile() -> anyhow::Result<()> {
+        let low = "low".parse::<MemoryProfile>()?;
+
+        assert_eq!(Some(1), low.html_parses());
+        assert!(low.workers() < MemoryProfile::default().workers());
+        assert!("tiny".parse::<MemoryProfile>().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+use std::io::{self, Write};
+use std::net::TcpListener;
+use std::thread::{self, JoinHandle};
+
+use anyhow;
+
+use crate::http::{read_request, Request};
+
+/// # MockServer
+/// HTTP/1.1 on a local port answering requests with canned responses in order,
+/// a connection for each since every response closes it
+pub struct MockServer {
+    url: String,
+    handle: JoinHandle<anyhow::Result<Vec<Request>>>,
+}
+
+impl MockServer {
+    /// (status, body) of each request expected
+    pub fn start(responses: Vec<(u16, String)>) -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}", listener.local_addr()?);
+
+        let handle = thread::spawn(move || {
+            let mut recorded = vec![];
+
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept()?;
+
+                recorded.push(read_request(&mut stream, None)?);
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )?;
+                stream.flush()?;
+            }
+
+            Ok(recorded)
+        });
+
+        Ok(Self { url, handle })
+    }
+
+    /// `http://127.0.0.1:{port}`
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Every request, after all responses are served
+    pub fn requests(self) -> anyhow::Result<Vec<Request>> {
+        self.handle.join().unwrap()
+    }
+}
@@ -0,0 +1,128 @@
+#[cfg(feature = "net")]
+pub use madome_client::book::{ContentType, Language, Metadata, MetadataBook};
+
+#[cfg(not(feature = "net"))]
+pub use self::parse_only::{ContentType, Language, Metadata, MetadataBook};
+
+/// Book of the tests, only `id` and `title` are set
+#[cfg(test)]
+pub(crate) fn book(id: Option<u32>, title: Option<&str>) -> MetadataBook {
+    MetadataBook {
+        id: Metadata::ID(id),
+        title: Metadata::Title(title.map(|x| x.to_string())),
+        artists: Metadata::Artists(None),
+        series: Metadata::Series(None),
+        groups: Metadata::Groups(None),
+        characters: Metadata::Characters(None),
+        tags: Metadata::Tags(None),
+        language: Metadata::Language(None),
+        content_type: Metadata::ContentType(None),
+        created_at: Metadata::CreatedAt(None),
+        thumbnail_url: Metadata::ThumbnailURL(None),
+        page_count: Metadata::Page(None),
+    }
+}
+
+/// What the parsers use of `madome_client::book`
+#[cfg(not(feature = "net"))]
+mod parse_only {
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Language {
+        Korean,
+        Japanese,
+        English,
+        Chinese,
+        /// Local name of any other
+        Other(String),
+    }
+
+    /// Local name of hitomi, `한국어`
+    impl From<&str> for Language {
+        fn from(localname: &str) -> Self {
+            match localname.trim() {
+                "한국어" => Self::Korean,
+                "日本語" => Self::Japanese,
+                "English" => Self::English,
+                "中文" => Self::Chinese,
+                x => Self::Other(x.to_string()),
+            }
+        }
+    }
+
+    /// Name of nozomi indexes, `korean`
+    impl From<Language> for String {
+        fn from(language: Language) -> Self {
+            match language {
+                Language::Korean => "korean".to_string(),
+                Language::Japanese => "japanese".to_string(),
+                Language::English => "english".to_string(),
+                Language::Chinese => "chinese".to_string(),
+                Language::Other(x) => x,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum ContentType {
+        Doujinshi,
+        Manga,
+        ArtistCG,
+        GameCG,
+        Anime,
+        /// Type of any other
+        Other(String),
+    }
+
+    /// `manga` of gallery info, `Manga` or `artist CG` of gallery blocks
+    impl From<String> for ContentType {
+        fn from(s: String) -> Self {
+            let name = s
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<String>()
+                .to_lowercase();
+
+            match name.as_str() {
+                "doujinshi" => Self::Doujinshi,
+                "manga" => Self::Manga,
+                "artistcg" => Self::ArtistCG,
+                "gamecg" => Self::GameCG,
+                "anime" => Self::Anime,
+                _ => Self::Other(s),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Metadata {
+        ID(Option<u32>),
+        Title(Option<String>),
+        Artists(Option<Vec<String>>),
+        Series(Option<Vec<String>>),
+        Groups(Option<Vec<String>>),
+        Characters(Option<Vec<String>>),
+        Tags(Option<Vec<String>>),
+        Language(Option<Language>),
+        ContentType(Option<ContentType>),
+        CreatedAt(Option<String>),
+        ThumbnailURL(Option<String>),
+        ContentURL(Option<String>),
+        Page(Option<usize>),
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct MetadataBook {
+        pub id: Metadata,
+        pub title: Metadata,
+        pub artists: Metadata,
+        pub series: Metadata,
+        pub groups: Metadata,
+        pub characters: Metadata,
+        pub tags: Metadata,
+        pub language: Metadata,
+        pub content_type: Metadata,
+        pub created_at: Metadata,
+        pub thumbnail_url: Metadata,
+        pub page_count: Metadata,
+    }
+}
@@ -0,0 +1,361 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow;
+use time::{Date, OffsetDateTime, Time, UtcOffset};
+
+use super::Provenance;
+use crate::models::{Metadata, MetadataBook};
+use crate::validate::{Invalid, Violation};
+
+/// Offsets of time zones are within -12:00 and +14:00, larger ones are broken
+const MAX_OFFSET_SECS: i32 = 14 * 3600;
+
+/// `+09`, `-05:00`, `+0530` or `Z`
+fn parse_offset(s: &str) -> Option<UtcOffset> {
+    if s == "Z" {
+        return Some(UtcOffset::UTC);
+    }
+
+    let sign = match s.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let digits = s[1..].replace(':', "");
+
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let (hours, minutes) = match digits.len() {
+        2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (
+            digits[..2].parse::<i32>().ok()?,
+            digits[2..].parse::<i32>().ok()?,
+        ),
+        _ => return None,
+    };
+    let secs = hours * 3600 + minutes * 60;
+
+    if minutes >= 60 || secs > MAX_OFFSET_SECS {
+        return None;
+    }
+
+    Some(UtcOffset::seconds(sign * secs))
+}
+
+/// `HH:MM:SS` or `HH:MM`
+fn parse_time(s: &str) -> Option<Time> {
+    let hms = s
+        .split(':')
+        .map(|x| x.parse::<u8>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    match hms.as_slice() {
+        [h, m] => Time::try_from_hms(*h, *m, 0).ok(),
+        [h, m, s] => Time::try_from_hms(*h, *m, *s).ok(),
+        _ => None,
+    }
+}
+
+/// `2020-09-02 10:01:00-05` of gallery blocks, `2020-09-02 10:01:00 -05:00`,
+/// `2020-09-02T15:01:00Z` or `2020-09-02`, at midnight of UTC
+pub fn parse_created_at(s: &str) -> Option<OffsetDateTime> {
+    let s = s.trim();
+
+    let mut ymd = s.get(..10)?.split('-');
+    let year = ymd.next()?.parse().ok()?;
+    let month = ymd.next()?.parse().ok()?;
+    let day = ymd.next()?.parse().ok()?;
+    let date = Date::try_from_ymd(year, month, day).ok()?;
+
+    let rest = s[10..].trim_start_matches(|c| c == ' ' || c == 'T');
+
+    if rest.is_empty() {
+        return Some(date.midnight().assume_utc());
+    }
+
+    let (time, offset) = match rest.find(|c| c == '+' || c == '-' || c == 'Z') {
+        Some(i) => (rest[..i].trim(), parse_offset(rest[i..].trim())?),
+        None => (rest.trim(), UtcOffset::UTC),
+    };
+
+    Some(date.with_time(parse_time(time)?).assume_offset(offset))
+}
+
+/// In UTC as gallery blocks write it, `2020-09-02 15:01:00+00`, sorted as strings by Madome
+pub fn format_created_at(date: OffsetDateTime) -> String {
+    let date = date.to_offset(UtcOffset::UTC);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}+00",
+        date.year(),
+        date.month(),
+        date.day(),
+        date.hour(),
+        date.minute(),
+        date.second()
+    )
+}
+
+/// What is done with a date ahead of the clock, FUTURE_DATES
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FutureDates {
+    /// Now instead, the upstream date goes to provenance as `created_at_future`
+    Clamp,
+    /// `validate::Invalid`, `invalid` class of `ERROR_POLICY`
+    Reject,
+    /// Uploaded as it is with `created_at_future` in provenance
+    Flag,
+}
+
+impl FromStr for FutureDates {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let r = match s.trim() {
+            "clamp" => Self::Clamp,
+            "reject" => Self::Reject,
+            "flag" => Self::Flag,
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "Unknown future dates policy `{}`",
+                    s
+                )))
+            }
+        };
+
+        Ok(r)
+    }
+}
+
+/// # CreatedAtPolicy
+/// `created_at` of a merged book normalized to UTC, and what is done when it is in the future
+///
+/// Dates within `skew` of the clock aren't in the future, clocks of hitomi and ours differ a little
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreatedAtPolicy {
+    future: FutureDates,
+    skew: Duration,
+}
+
+impl Default for CreatedAtPolicy {
+    fn default() -> Self {
+        Self {
+            future: FutureDates::Clamp,
+            skew: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl CreatedAtPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_future(mut self, future: FutureDates) -> Self {
+        self.future = future;
+        self
+    }
+
+    pub fn with_skew(mut self, skew: Duration) -> Self {
+        self.skew = skew;
+        self
+    }
+
+    /// Dates it can't parse are left to `validate`
+    pub fn apply(
+        &self,
+        id: u32,
+        book: MetadataBook,
+        provenance: &mut Provenance,
+        now: OffsetDateTime,
+    ) -> anyhow::Result<MetadataBook> {
+        let upstream = match &book.created_at {
+            Metadata::CreatedAt(Some(upstream)) => upstream.clone(),
+            _ => return Ok(book),
+        };
+
+        let date = match parse_created_at(&upstream) {
+            Some(date) => date,
+            None => return Ok(book),
+        };
+
+        let date = if date <= now + self.skew {
+            date
+        } else {
+            match self.future {
+                FutureDates::Reject => {
+                    return Err(Invalid {
+                        id,
+                        violations: vec![Violation::FutureDate(upstream)],
+                    }
+                    .into())
+                }
+                FutureDates::Clamp => {
+                    provenance.insert("created_at".to_string(), "clamp".to_string());
+                    provenance.insert("created_at_future".to_string(), upstream);
+                    now
+                }
+                FutureDates::Flag => {
+                    provenance.insert("created_at_future".to_string(), upstream);
+                    date
+                }
+            }
+        };
+
+        Ok(MetadataBook {
+            created_at: Metadata::CreatedAt(Some(format_created_at(date))),
+            ..book
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{format_created_at, parse_created_at, CreatedAtPolicy, FutureDates};
+    use crate::models::{self, Metadata, MetadataBook};
+    use crate::parser::Provenance;
+    use crate::validate::is_invalid;
+
+    fn utc(s: &str) -> Option<String> {
+        parse_created_at(s).map(format_created_at)
+    }
+
+    fn book(created_at: &str) -> MetadataBook {
+        MetadataBook {
+            created_at: Metadata::CreatedAt(Some(created_at.to_string())),
+            ..models::book(Some(1724122), Some("Tsundere Imouto"))
+        }
+    }
+
+    #[test]
+    fn normalize_offsets() -> anyhow::Result<()> {
+        let expected = Some("2020-09-02 15:01:00+00".to_string());
+
+        assert_eq!(expected, utc("2020-09-02 10:01:00-05"));
+        assert_eq!(expected, utc("2020-09-02 10:01:00 -05:00"));
+        assert_eq!(expected, utc("2020-09-02 10:01:00-0500"));
+        assert_eq!(expected, utc("2020-09-02T15:01:00Z"));
+        assert_eq!(expected, utc("2020-09-03 00:01:00+09"));
+
+        // half and three quarter hours
+        assert_eq!(expected, utc("2020-09-02 20:31:00+05:30"));
+        assert_eq!(expected, utc("2020-09-02 20:46:00+0545"));
+
+        // a day earlier in UTC, and midnight without a time
+        assert_eq!(
+            Some("2020-12-31 16:00:00+00".to_string()),
+            utc("2021-01-01 01:00:00+09")
+        );
+        assert_eq!(
+            Some("2020-09-02 00:00:00+00".to_string()),
+            utc("2020-09-02")
+        );
+
+        assert_eq!(None, utc("2020-09-02 10:01:00+25"));
+        assert_eq!(None, utc("2020-09-02 10:01:00+05:75"));
+        assert_eq!(None, utc("2020-09-02 10:01:00-5"));
+        assert_eq!(None, utc("2020-09-02 25:01:00"));
+        assert_eq!(None, utc("Sep 2, 2020"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn daylight_saving_time() -> anyhow::Result<()> {
+        let at = |s: &str| parse_created_at(s).unwrap();
+
+        // Berlin springs forward from 02:00 CET to 03:00 CEST, a second apart
+        assert_eq!(
+            time::Duration::seconds(1),
+            at("2021-03-28 03:00:00+02") - at("2021-03-28 01:59:59+01")
+        );
+
+        // and falls back, the same wall clock an hour apart
+        assert_eq!(
+            time::Duration::hours(1),
+            at("2021-10-31 02:30:00+01") - at("2021-10-31 02:30:00+02")
+        );
+        assert_eq!(
+            Some("2021-10-31 00:30:00+00".to_string()),
+            utc("2021-10-31 02:30:00+02")
+        );
+
+        // New York, EST to EDT
+        assert_eq!(utc("2021-03-14 07:00:00+00"), utc("2021-03-14 03:00:00-04"));
+        assert_eq!(
+            time::Duration::seconds(1),
+            at("2021-03-14 03:00:00-04") - at("2021-03-14 01:59:59-05")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn future_dates() -> anyhow::Result<()> {
+        let now = parse_created_at("2021-05-01 12:00:00+00").unwrap();
+        let policy = CreatedAtPolicy::new().with_skew(Duration::from_secs(3600));
+
+        let apply = |policy: &CreatedAtPolicy, created_at| {
+            let mut provenance = Provenance::new();
+            policy
+                .apply(1724122, book(created_at), &mut provenance, now)
+                .map(|book| (book.created_at, provenance))
+        };
+
+        // ahead by the wall clock of Tokyo only
+        let (created_at, provenance) = apply(&policy, "2021-05-01 20:00:00+09")?;
+        assert_eq!(
+            Metadata::CreatedAt(Some("2021-05-01 11:00:00+00".to_string())),
+            created_at
+        );
+        assert!(provenance.is_empty());
+
+        // within the skew
+        let (created_at, _) = apply(&policy, "2021-05-01 12:30:00+00")?;
+        assert_eq!(
+            Metadata::CreatedAt(Some("2021-05-01 12:30:00+00".to_string())),
+            created_at
+        );
+
+        let (created_at, provenance) = apply(&policy, "2021-05-02 01:00:00-05")?;
+        assert_eq!(
+            Metadata::CreatedAt(Some("2021-05-01 12:00:00+00".to_string())),
+            created_at
+        );
+        assert_eq!(Some(&"clamp".to_string()), provenance.get("created_at"));
+        assert_eq!(
+            Some(&"2021-05-02 01:00:00-05".to_string()),
+            provenance.get("created_at_future")
+        );
+
+        let flag = policy.clone().with_future(FutureDates::Flag);
+        let (created_at, provenance) = apply(&flag, "2021-05-02 01:00:00-05")?;
+        assert_eq!(
+            Metadata::CreatedAt(Some("2021-05-02 06:00:00+00".to_string())),
+            created_at
+        );
+        assert!(provenance.contains_key("created_at_future"));
+
+        let reject = policy.with_future(FutureDates::Reject);
+        assert!(is_invalid(
+            &apply(&reject, "2021-05-02 01:00:00-05").unwrap_err()
+        ));
+
+        // left to `validate`
+        let (created_at, _) = apply(&reject, "Sep 2, 2099")?;
+        assert_eq!(
+            Metadata::CreatedAt(Some("Sep 2, 2099".to_string())),
+            created_at
+        );
+
+        assert_eq!(FutureDates::Reject, "reject".parse()?);
+        assert!("drop".parse::<FutureDates>().is_err());
+
+        Ok(())
+    }
+}
@@ -1,16 +1,48 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
 use anyhow;
+#[cfg(feature = "net")]
+use log::debug;
 use log::trace;
-use madome_client::book::{Metadata, MetadataBook};
-use reqwest;
 use scraper::{Html, Selector};
 
-use crate::parser::Parser;
+use crate::models::{Metadata, MetadataBook};
+use crate::parser::{OptionalList, Parser};
+use crate::urls;
 
 pub struct Gallery {
     id: u32,
+    /// Requested instead of following the redirect of `url()`
+    content_url: Option<String>,
     request_data: Option<Box<String>>,
 }
 
+/// Content urls by gallery id, saves the redirect hop of `Gallery`
+#[derive(Default)]
+pub struct ContentUrls {
+    inner: Mutex<HashMap<u32, String>>,
+}
+
+impl ContentUrls {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, id: u32) -> Option<String> {
+        self.inner.lock().unwrap().get(&id).cloned()
+    }
+
+    pub fn insert(&self, id: u32, content_url: String) {
+        self.inner.lock().unwrap().insert(id, content_url);
+    }
+
+    /// Learned since the last drain, to be kept in catalog
+    pub fn drain(&self) -> Vec<(u32, String)> {
+        self.inner.lock().unwrap().drain().collect()
+    }
+}
+
 /// ```html
 /// <!-- Response of https://hitomi.la/galleries/1744332.html -->
 /// <!DOCTYPE html>
@@ -33,55 +65,31 @@ impl Gallery {
     pub fn new(id: u32) -> Gallery {
         Gallery {
             id,
+            content_url: None,
             request_data: None,
         }
     }
 
-    pub fn is_nothing(&self, element: &scraper::ElementRef<'_>) -> bool {
-        element.text().next().unwrap().trim() == "N/A"
+    pub fn with_content_url(mut self, content_url: Option<String>) -> Self {
+        self.content_url = content_url;
+        self
     }
 
-    pub fn parse_multiple_metadata(&self, element: scraper::ElementRef) -> Vec<String> {
-        let ul_selector = Selector::parse("ul").unwrap();
-        let li_selector = Selector::parse("li").unwrap();
-
-        element
-            .select(&ul_selector)
-            .next()
-            .unwrap()
-            .select(&li_selector)
-            .map(|element| element.text().next().unwrap().to_string())
-            .collect::<Vec<_>>()
+    /// Given one, or the one `request` was redirected to
+    pub fn content_url(&self) -> Option<&str> {
+        self.content_url.as_deref()
     }
 
     pub fn parse_characters(&self, element: scraper::ElementRef) -> Option<Vec<String>> {
-        let characters = self.parse_multiple_metadata(element);
-
-        if characters.is_empty() {
-            return None;
-        }
-
-        Some(characters)
+        OptionalList::parse(element)
     }
 
     pub fn parse_groups(&self, element: scraper::ElementRef) -> Option<Vec<String>> {
-        if self.is_nothing(&element) {
-            return None;
-        }
-
-        let groups = self.parse_multiple_metadata(element);
-
-        Some(groups)
+        OptionalList::parse(element)
     }
 
     pub fn parse_tags(&self, element: scraper::ElementRef) -> Option<Vec<String>> {
-        let tags = self.parse_multiple_metadata(element);
-
-        if tags.is_empty() {
-            return None;
-        }
-
-        Some(tags)
+        OptionalList::parse_tags(element)
     }
 
     pub fn parse_metadata(&self, document: &Html, metadata_type: Metadata) -> Metadata {
@@ -125,13 +133,38 @@ impl Parser for Gallery {
         }
     }
 
+    fn with_request_data(mut self, request_data: Self::RequestData) -> Box<Self> {
+        self.request_data = Some(Box::new(request_data));
+        Box::new(self)
+    }
+
+    /// Redirects to the content url
     fn url(&self) -> anyhow::Result<String> {
         trace!("Gallery::url()");
-        let gallery_url = format!("https://hitomi.la/galleries/{}.html", self.id);
+        Ok(urls::gallery(self.id))
+    }
 
-        let client = reqwest::blocking::Client::builder().build()?;
+    #[cfg(feature = "net")]
+    fn request(mut self) -> anyhow::Result<Box<Self>> {
+        trace!("Gallery::request()");
 
-        let gallery_html = client.get(&gallery_url).send()?.text()?;
+        // a content url made from metadata may be outdated
+        if let Some(content_url) = &self.content_url {
+            let response = crate::client::check_rate_limit(
+                crate::client::metadata()?.get(content_url).send()?,
+            )?;
+
+            if response.status().is_success() {
+                self.request_data = Some(Box::new(crate::client::text(response)?));
+                return Ok(Box::new(self));
+            }
+
+            debug!("{}: {} of {}", self.id, response.status(), content_url);
+        }
+
+        let gallery_html = crate::client::text(crate::client::check_rate_limit(
+            crate::client::metadata()?.get(&self.url()?).send()?,
+        )?)?;
 
         let document = Html::parse_document(&gallery_html);
         let content_url_selector = Selector::parse("body > a").unwrap();
@@ -144,17 +177,11 @@ impl Parser for Gallery {
             .expect("Can't find `Content URL` in `parser::Gallery`")
             .to_string();
 
-        Ok(content_url)
-    }
-
-    fn request(mut self) -> anyhow::Result<Box<Self>> {
-        trace!("Gallery::request()");
-        let content_url = self.url()?;
-
-        let client = reqwest::blocking::Client::builder().build()?;
-
-        let content_html = client.get(&content_url).send()?.text()?;
+        let content_html = crate::client::text(crate::client::check_rate_limit(
+            crate::client::metadata()?.get(&content_url).send()?,
+        )?)?;
 
+        self.content_url = Some(content_url);
         self.request_data = Some(Box::new(content_html));
         Ok(Box::new(self))
     }
@@ -190,12 +217,49 @@ impl Parser for Gallery {
 
 #[cfg(test)]
 mod tests {
-    use madome_client::book::Metadata;
     use scraper::Html;
 
     use super::Gallery;
     use super::Parser;
+    use crate::models::Metadata;
+
+    fn gallery_info_of(characters: &str, groups: &str, tags: &str) -> Html {
+        Html::parse_document(&format!(
+            r#"<div class="gallery-info"><table>
+                <tr><td>Groups</td><td>{}</td></tr>
+                <tr><td>Characters</td><td>{}</td></tr>
+                <tr><td>Tags</td><td class="relatedtags">{}</td></tr>
+            </table></div>"#,
+            groups, characters, tags
+        ))
+    }
+
+    #[test]
+    fn parse_optional_lists_of_fixture() -> anyhow::Result<()> {
+        let gallery = Gallery::new(1);
+        let document = gallery_info_of(
+            "<ul><li><a href=\"/1\">a</a></li></ul>",
+            "N/A",
+            "<ul><li><a href=\"/2\">b</a></li></ul>",
+        );
+
+        assert_eq!(
+            Metadata::Characters(Some(vec!["a".to_string()])),
+            gallery.parse_metadata(&document, Metadata::Characters(None))
+        );
+        assert_eq!(
+            Metadata::Groups(None),
+            gallery.parse_metadata(&document, Metadata::Groups(None))
+        );
+        assert_eq!(
+            Metadata::Tags(Some(vec!["b".to_string()])),
+            gallery.parse_metadata(&document, Metadata::Tags(None))
+        );
+
+        Ok(())
+    }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_tags() -> anyhow::Result<()> {
         let gallery = Gallery::new(1724122);
@@ -218,6 +282,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_tags_is_nothing() -> anyhow::Result<()> {
         let gallery = Gallery::new(1752881);
@@ -235,6 +300,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_characters() -> anyhow::Result<()> {
         let gallery = Gallery::new(1277807);
@@ -269,6 +335,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_characters_is_nothing() -> anyhow::Result<()> {
         let gallery = Gallery::new(1745756);
@@ -286,6 +353,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_groups() -> anyhow::Result<()> {
         let gallery = Gallery::new(1705277);
@@ -303,6 +371,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_groups_is_nothing() -> anyhow::Result<()> {
         let gallery = Gallery::new(1454325);
@@ -1,10 +1,12 @@
 use anyhow;
 use log::trace;
-use madome_client::book::{ContentType, Language, Metadata, MetadataBook};
+#[cfg(feature = "net")]
 use reqwest;
 use scraper::{Html, Selector};
 
-use crate::parser::Parser;
+use crate::models::{ContentType, Language, Metadata, MetadataBook};
+use crate::parser::{OptionalList, Parser, Title};
+use crate::urls;
 
 /// Can't parse Groups, Characters
 pub struct GalleryBlock {
@@ -20,32 +22,6 @@ impl GalleryBlock {
         }
     }
 
-    pub fn parse_single_metadata(&self, element: scraper::ElementRef) -> String {
-        let anchor_selector = Selector::parse("a").unwrap();
-
-        element
-            .select(&anchor_selector)
-            .next()
-            .unwrap()
-            .text()
-            .next()
-            .unwrap()
-            .to_string()
-    }
-
-    pub fn parse_multiple_metadata(&self, element: scraper::ElementRef) -> Vec<String> {
-        let ul_selector = Selector::parse("ul").unwrap();
-        let li_selector = Selector::parse("li").unwrap();
-
-        element
-            .select(&ul_selector)
-            .next()
-            .unwrap()
-            .select(&li_selector)
-            .map(|element| element.text().next().unwrap().to_string())
-            .collect::<Vec<_>>()
-    }
-
     pub fn parse_title(&self, fragment: &Html) -> String {
         let title_selector = Selector::parse("h1.lillie > a").unwrap();
 
@@ -59,38 +35,16 @@ impl GalleryBlock {
             .to_string()
     }
 
-    pub fn is_nothing(&self, element: &scraper::ElementRef<'_>) -> bool {
-        element.text().next().unwrap().trim() == "N/A"
-    }
-
-    /// Change return type to Option<Vec<String>>
-    /// and check N/A
     pub fn parse_artists(&self, fragment: &Html) -> Option<Vec<String>> {
         let artist_list_selector = Selector::parse(".artist-list").unwrap();
-        let ul_selector = Selector::parse("ul").unwrap();
-        let li_selector = Selector::parse("li").unwrap();
 
         let artist_list = fragment.select(&artist_list_selector).next().unwrap();
 
-        if self.is_nothing(&artist_list) {
-            return None;
-        }
-
-        let ul = artist_list.select(&ul_selector).next().unwrap();
-
-        Some(
-            ul.select(&li_selector)
-                .map(|element| element.text().next().unwrap().to_string())
-                .collect::<Vec<_>>(),
-        )
+        OptionalList::parse(artist_list)
     }
 
     pub fn parse_series(&self, element: scraper::ElementRef) -> Option<Vec<String>> {
-        if self.is_nothing(&element) {
-            return None;
-        }
-
-        Some(self.parse_multiple_metadata(element))
+        OptionalList::parse(element)
     }
 
     pub fn parse_tags(&self, element: scraper::ElementRef) -> Option<Vec<String>> {
@@ -101,33 +55,15 @@ impl GalleryBlock {
         // </ul>
         // </td>
         // </tr>
-        /* if self.is_nothing(&element) {
-            return None;
-        } */
-
-        let tags = self.parse_multiple_metadata(element);
-
-        if tags.is_empty() {
-            return None;
-        }
-
-        Some(tags)
+        OptionalList::parse_tags(element)
     }
 
     pub fn parse_content_type(&self, element: scraper::ElementRef) -> Option<ContentType> {
-        if self.is_nothing(&element) {
-            return None;
-        }
-
-        Some(ContentType::from(self.parse_single_metadata(element)))
+        OptionalList::parse_one(element).map(ContentType::from)
     }
 
     pub fn parse_language(&self, element: scraper::ElementRef) -> Option<Language> {
-        if self.is_nothing(&element) {
-            return None;
-        }
-
-        Some(Language::from(self.parse_single_metadata(element).as_str()))
+        OptionalList::parse_one(element).map(|x| Language::from(x.as_str()))
     }
 
     /// uncomment on v2 api
@@ -163,14 +99,15 @@ impl GalleryBlock {
 
         let anchor = fragment.select(&anchor_selector).next().unwrap();
 
-        anchor
+        let src = anchor
             .select(&img_selector)
             .next()
             .unwrap()
             .value()
             .attr("src")
-            .unwrap()[("//tn.hitomi.la").len()..]
-            .replace("smallbig", "big")
+            .unwrap();
+
+        urls::thumbnail_path(src).replace("smallbig", "big")
     }
 
     #[deprecated]
@@ -189,7 +126,9 @@ impl GalleryBlock {
 
     pub fn parse_metadata(&self, fragment: &Html, metadata_type: Metadata) -> Metadata {
         match metadata_type {
-            Metadata::Title(_) => Metadata::Title(Some(self.parse_title(fragment))),
+            Metadata::Title(_) => {
+                Metadata::Title(Some(Title::parse(&self.parse_title(fragment)).title))
+            }
             Metadata::Artists(_) => Metadata::Artists(self.parse_artists(fragment)),
             Metadata::CreatedAt(_) => Metadata::CreatedAt(self.parse_created_at(fragment)),
             //  Metadata::ContentURL(_) => Metadata::ContentURL(Some(self.parse_content_url(fragment))),
@@ -252,19 +191,24 @@ impl Parser for GalleryBlock {
         }
     }
 
+    fn with_request_data(mut self, request_data: Self::RequestData) -> Box<Self> {
+        self.request_data = Some(Box::new(request_data));
+        Box::new(self)
+    }
+
     fn url(&self) -> anyhow::Result<String> {
         trace!("GalleryBlock::url()");
-        Ok(format!(
-            "https://ltn.hitomi.la/galleryblock/{}.html",
-            self.id
-        ))
+        Ok(urls::gallery_block(self.id))
     }
 
+    #[cfg(feature = "net")]
     fn request(mut self) -> anyhow::Result<Box<Self>> {
         trace!("GalleryBlock::request()");
-        let client = reqwest::blocking::Client::builder().build()?;
+        let client = crate::client::metadata()?;
 
-        let gallery_block_html = client.get(&self.url()?).send()?.text()?;
+        let gallery_block_html = crate::client::text(crate::client::check_rate_limit(
+            client.get(&self.url()?).send()?,
+        )?)?;
 
         self.request_data = Some(Box::new(gallery_block_html));
 
@@ -305,16 +249,71 @@ impl Parser for GalleryBlock {
     }
 }
 
+/// Whether hitomi has the gallery, asks only the headers of its gallery block
+#[cfg(feature = "net")]
+pub fn exists(id: u32) -> anyhow::Result<bool> {
+    trace!("exists({})", id);
+    let client = crate::client::metadata()?;
+
+    let response = client.head(&GalleryBlock::new(id).url()?).send()?;
+
+    match response.status() {
+        status if status.is_success() => Ok(true),
+        reqwest::StatusCode::NOT_FOUND => Ok(false),
+        status => Err(anyhow::Error::msg(status.to_string())),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use scraper::Html;
 
+    #[cfg(feature = "net")]
+    use super::exists;
     use super::ContentType;
     use super::GalleryBlock;
     use super::Language;
     use super::Metadata;
     use super::Parser;
 
+    fn gallery_block_of(artists: &str, series: &str, tags: &str) -> Html {
+        Html::parse_fragment(&format!(
+            r#"<div class="dj">
+                <div class="artist-list">{}</div>
+                <div class="dj-content"><table class="dj-desc">
+                    <tr><td>Series</td><td>{}</td></tr>
+                    <tr><td>Tags</td><td class="relatedtags">{}</td></tr>
+                </table></div>
+            </div>"#,
+            artists, series, tags
+        ))
+    }
+
+    #[test]
+    fn parse_optional_lists_of_fixture() -> anyhow::Result<()> {
+        let gallery_block = GalleryBlock::new(1);
+        let fragment = gallery_block_of(
+            "<ul><li><a href=\"/1\">a</a></li></ul>",
+            "N/A",
+            "<ul><li><a href=\"/2\">b</a></li></ul>",
+        );
+
+        assert_eq!(
+            Metadata::Artists(Some(vec!["a".to_string()])),
+            gallery_block.parse_metadata(&fragment, Metadata::Artists(None))
+        );
+        assert_eq!(
+            Metadata::Series(None),
+            gallery_block.parse_metadata(&fragment, Metadata::Series(None))
+        );
+        assert_eq!(
+            Metadata::Tags(Some(vec!["b".to_string()])),
+            gallery_block.parse_metadata(&fragment, Metadata::Tags(None))
+        );
+
+        Ok(())
+    }
+
     /* #[test]
      fn parse_gallery_block() -> anyhow::Result<()> {
         let gallery_block = GalleryBlock::new(1724122);
@@ -358,6 +357,7 @@ mod tests {
         Ok(())
     } */
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_title() -> anyhow::Result<()> {
         let gallery_block = GalleryBlock::new(1399900);
@@ -394,6 +394,7 @@ mod tests {
         Ok(())
     } */
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_thumbnail_url() -> anyhow::Result<()> {
         let gallery_block = GalleryBlock::new(1399900);
@@ -414,6 +415,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_artists() -> anyhow::Result<()> {
         let gallery_block = GalleryBlock::new(1399900);
@@ -455,6 +457,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_artists_is_nothing() -> anyhow::Result<()> {
         let gallery_block = GalleryBlock::new(1722267);
@@ -472,6 +475,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_language() -> anyhow::Result<()> {
         let gallery_block = GalleryBlock::new(1399900);
@@ -489,6 +493,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_content_type() -> anyhow::Result<()> {
         let gallery_block = GalleryBlock::new(1399900);
@@ -506,6 +511,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_series() -> anyhow::Result<()> {
         let gallery_block = GalleryBlock::new(1277807);
@@ -539,6 +545,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_series_is_nothing() -> anyhow::Result<()> {
         let gallery_block = GalleryBlock::new(1399900);
@@ -556,6 +563,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_tags() -> anyhow::Result<()> {
         let gallery_block = GalleryBlock::new(1724122);
@@ -578,6 +586,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_tags_is_nothing() -> anyhow::Result<()> {
         let gallery_block = GalleryBlock::new(1686905);
@@ -595,6 +604,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_created_at() -> anyhow::Result<()> {
         let gallery_block = GalleryBlock::new(1724122);
@@ -614,4 +624,13 @@ mod tests {
 
         Ok(())
     }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn check_exists() -> anyhow::Result<()> {
+        assert!(exists(1724122)?);
+        assert!(!exists(1)?);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,469 @@
+use std::iter;
+
+use anyhow;
+use log::trace;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json;
+
+use super::{File, Title};
+use crate::models::{ContentType, Language, Metadata, MetadataBook};
+use crate::parser::Parser;
+use crate::urls;
+
+/// # GalleryInfo Parser
+/// Parses `galleryinfo` of https://ltn.hitomi.la/galleries/{id}.js
+///
+/// It has what both of html parsers can't see
+/// such as other translations of the same work
+pub struct GalleryInfo {
+    id: u32,
+    request_data: Option<Box<String>>,
+}
+
+impl GalleryInfo {
+    pub fn new(id: u32) -> GalleryInfo {
+        GalleryInfo {
+            id,
+            request_data: None,
+        }
+    }
+}
+
+/// hitomi sends ids as either string or number
+fn deserialize_id<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        Number(u32),
+        Text(String),
+    }
+
+    match Id::deserialize(deserializer)? {
+        Id::Number(id) => Ok(id),
+        Id::Text(id) => id.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+/// hitomi sends flags as "1", "", 1 or null
+fn deserialize_flag<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Flag {
+        Number(u8),
+        Text(String),
+        Bool(bool),
+    }
+
+    let flag = Option::<Flag>::deserialize(deserializer)?;
+
+    Ok(match flag {
+        Some(Flag::Number(x)) => x != 0,
+        Some(Flag::Text(x)) => !x.is_empty() && x != "0",
+        Some(Flag::Bool(x)) => x,
+        None => false,
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Tag {
+    pub tag: String,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub female: bool,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub male: bool,
+    pub url: String,
+}
+
+impl Tag {
+    /// Same as the text of tag in html, `loli ♀`
+    pub fn display_name(&self) -> String {
+        if self.female {
+            format!("{} ♀", self.tag)
+        } else if self.male {
+            format!("{} ♂", self.tag)
+        } else {
+            self.tag.clone()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Artist {
+    pub artist: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Group {
+    pub group: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Character {
+    pub character: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Parody {
+    pub parody: String,
+}
+
+/// Other translation of the same work
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Translation {
+    #[serde(deserialize_with = "deserialize_id")]
+    pub galleryid: u32,
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GalleryInfoData {
+    #[serde(deserialize_with = "deserialize_id")]
+    pub id: u32,
+    pub title: String,
+    pub japanese_title: Option<String>,
+    #[serde(rename = "type")]
+    pub content_type: Option<String>,
+    pub language: Option<String>,
+    pub language_localname: Option<String>,
+    pub date: Option<String>,
+    #[serde(default)]
+    pub languages: Vec<Translation>,
+    pub tags: Option<Vec<Tag>>,
+    pub artists: Option<Vec<Artist>>,
+    pub groups: Option<Vec<Group>>,
+    pub characters: Option<Vec<Character>>,
+    pub parodys: Option<Vec<Parody>>,
+    #[serde(default)]
+    pub files: Vec<File>,
+    /// Only anime galleries have it
+    pub videofilename: Option<String>,
+    /// Index of files where a scene starts
+    #[serde(default)]
+    pub scene_indexes: Vec<usize>,
+}
+
+impl GalleryInfoData {
+    /// Every translation of a work shares the smallest gallery id among them
+    pub fn translation_group(&self) -> u32 {
+        self.languages
+            .iter()
+            .map(|translation| translation.galleryid)
+            .chain(iter::once(self.id))
+            .min()
+            .unwrap()
+    }
+
+    /// Anime galleries have a video instead of images
+    pub fn is_video(&self) -> bool {
+        self.content_type.as_deref() == Some("anime") || self.videofilename.is_some()
+    }
+
+    pub fn video_url(&self) -> Option<String> {
+        self.videofilename
+            .as_ref()
+            .map(|filename| urls::video(filename))
+    }
+
+    /// Pages from 1 where chapters start, `None` without scene markers
+    pub fn chapters(&self) -> Option<Vec<usize>> {
+        let mut pages = self
+            .scene_indexes
+            .iter()
+            .filter(|index| self.files.is_empty() || **index < self.files.len())
+            .map(|index| index + 1)
+            .collect::<Vec<_>>();
+
+        pages.sort();
+        pages.dedup();
+
+        Some(pages).filter(|pages| !pages.is_empty())
+    }
+
+    /// What `galleries/{id}.html` redirects to, `None` without type
+    pub fn content_url(&self) -> Option<String> {
+        let content_type = self.content_type.as_ref()?;
+
+        let mut slug = self.title.clone();
+
+        if let Some(language) = &self.language_localname {
+            slug.push('-');
+            slug.push_str(language);
+        }
+
+        let slug = slug
+            .to_lowercase()
+            .replace(|c| c == '/' || c == '#' || c == '?', "")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join("-");
+
+        Some(urls::content(content_type, &slug, self.id))
+    }
+
+    /// Original title after `|`, or `japanese_title`
+    pub fn title(&self) -> Title {
+        let title = Title::parse(&self.title);
+
+        Title {
+            title_original: title.title_original.or_else(|| self.japanese_title.clone()),
+            ..title
+        }
+    }
+
+    pub fn to_metadata_book(&self) -> MetadataBook {
+        fn names<T>(xs: &Option<Vec<T>>, name: impl Fn(&T) -> String) -> Option<Vec<String>> {
+            xs.as_ref()
+                .map(|xs| xs.iter().map(name).collect::<Vec<_>>())
+                .filter(|xs| !xs.is_empty())
+        }
+
+        // same as thumbnail url of gallery block, /bigtn/e/0a/{hash}.jpg
+        let thumbnail_url = self.files.first().and_then(|file| {
+            file.url(self.id)
+                .ok()
+                .map(|(_, thumbnail_url)| urls::thumbnail_path(&thumbnail_url).to_string())
+        });
+
+        MetadataBook {
+            id: Metadata::ID(Some(self.id)),
+            title: Metadata::Title(Some(self.title().title)),
+            artists: Metadata::Artists(names(&self.artists, |x| x.artist.clone())),
+            series: Metadata::Series(names(&self.parodys, |x| x.parody.clone())),
+            groups: Metadata::Groups(names(&self.groups, |x| x.group.clone())),
+            characters: Metadata::Characters(names(&self.characters, |x| x.character.clone())),
+            tags: Metadata::Tags(names(&self.tags, Tag::display_name)),
+            language: Metadata::Language(
+                self.language_localname
+                    .as_ref()
+                    .map(|x| Language::from(x.as_str())),
+            ),
+            content_type: Metadata::ContentType(self.content_type.clone().map(ContentType::from)),
+            created_at: Metadata::CreatedAt(self.date.clone()),
+            thumbnail_url: Metadata::ThumbnailURL(thumbnail_url),
+            page_count: Metadata::Page(None),
+        }
+    }
+}
+
+impl Parser for GalleryInfo {
+    type RequestData = String;
+    type ParseData = GalleryInfoData;
+
+    fn request_data(&self) -> anyhow::Result<&Box<Self::RequestData>> {
+        trace!("GalleryInfo::request_data()");
+        match self.request_data {
+            Some(ref rd) => Ok(rd),
+            None => Err(anyhow::Error::msg("Can't get request_data")),
+        }
+    }
+
+    fn with_request_data(mut self, request_data: Self::RequestData) -> Box<Self> {
+        self.request_data = Some(Box::new(request_data));
+        Box::new(self)
+    }
+
+    fn url(&self) -> anyhow::Result<String> {
+        trace!("GalleryInfo::url()");
+        Ok(urls::gallery_info(self.id))
+    }
+
+    #[cfg(feature = "net")]
+    fn request(mut self) -> anyhow::Result<Box<Self>> {
+        trace!("GalleryInfo::request()");
+        let client = crate::client::metadata()?;
+
+        let response = crate::client::check_rate_limit(client.get(&self.url()?).send()?)?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::Error::msg(response.status().to_string()));
+        }
+
+        let rd = crate::client::text(response)?;
+
+        let i = rd.find("=").ok_or_else(|| {
+            anyhow::Error::msg(format!(
+                "error occurs `request_data.find(\"=\")` in parser::GalleryInfo::request(), {}",
+                rd
+            ))
+        })?;
+        let rd = &rd[i + 1..];
+
+        self.request_data = Some(Box::new(rd.to_string()));
+        Ok(Box::new(self))
+    }
+
+    fn parse(&self) -> anyhow::Result<Self::ParseData> {
+        trace!("GalleryInfo::parse()");
+        let request_data = self.request_data()?;
+
+        let gallery_info = serde_json::from_str::<'_, GalleryInfoData>(&request_data)?;
+
+        Ok(gallery_info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::GalleryInfo;
+    use super::Parser;
+    use crate::models::Metadata;
+
+    const FIXTURE: &'static str = r#"{
+        "id": "1724122",
+        "title": "Tsundere Imouto",
+        "japanese_title": null,
+        "type": "manga",
+        "language": "korean",
+        "languages": [
+            {"galleryid": "1724122", "name": "korean", "url": "/galleries/1724122.html"},
+            {"galleryid": 1700000, "name": "english", "url": "/galleries/1700000.html"},
+            {"galleryid": "1710000", "name": "chinese", "url": "/galleries/1710000.html"}
+        ],
+        "files": []
+    }"#;
+
+    #[test]
+    fn parse_translation_group() -> anyhow::Result<()> {
+        let gallery_info = GalleryInfo::new(1724122).with_request_data(FIXTURE.to_string());
+
+        let pd = gallery_info.parse()?;
+
+        assert_eq!(1724122, pd.id);
+        assert_eq!("Tsundere Imouto", pd.title().title);
+        assert_eq!(None, pd.title().title_original);
+        assert_eq!(3, pd.languages.len());
+        assert_eq!(1700000, pd.translation_group());
+        assert!(!pd.is_video());
+        assert_eq!(None, pd.chapters());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_tags() -> anyhow::Result<()> {
+        let gallery_info = GalleryInfo::new(1724122).with_request_data(
+            r#"{
+                "id": "1724122",
+                "title": "Tsundere Imouto | 츤데레 여동생",
+                "type": "manga",
+                "tags": [
+                    {"tag": "footjob", "female": "1", "male": "", "url": "/tag/female:footjob-all.html"},
+                    {"tag": "shota", "female": "", "male": 1, "url": "/tag/male:shota-all.html"},
+                    {"tag": "incest", "url": "/tag/incest-all.html"}
+                ],
+                "artists": null
+            }"#
+            .to_string(),
+        );
+
+        let pd = gallery_info.parse()?.to_metadata_book();
+
+        assert_eq!(Metadata::Title(Some("츤데레 여동생".to_string())), pd.title);
+
+        let expected = Metadata::Tags(Some(
+            ["footjob ♀", "shota ♂", "incest"]
+                .iter()
+                .map(|st| st.to_string())
+                .collect::<Vec<_>>(),
+        ));
+
+        assert_eq!(expected, pd.tags);
+        assert_eq!(Metadata::Artists(None), pd.artists);
+
+        Ok(())
+    }
+
+    #[test]
+    fn content_url_from_metadata() -> anyhow::Result<()> {
+        let gallery_info = GalleryInfo::new(1744332).with_request_data(
+            r#"{
+                "id": 1744332,
+                "title": "Kuro no Ugomeku Rougoku de | 검은 꿈틀대는 감옥에서",
+                "type": "doujinshi",
+                "language_localname": "한국어"
+            }"#
+            .to_string(),
+        );
+
+        let pd = gallery_info.parse()?;
+
+        assert_eq!(
+            Some("https://hitomi.la/doujinshi/kuro-no-ugomeku-rougoku-de-|-검은-꿈틀대는-감옥에서-한국어-1744332.html".to_string()),
+            pd.content_url()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_chapters() -> anyhow::Result<()> {
+        let gallery_info = GalleryInfo::new(1724122).with_request_data(
+            r#"{
+                "id": 1724122,
+                "title": "Tsundere Imouto",
+                "scene_indexes": [12, 0, 5, 5],
+                "files": []
+            }"#
+            .to_string(),
+        );
+
+        let pd = gallery_info.parse()?;
+
+        assert_eq!(Some(vec![1, 6, 13]), pd.chapters());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_video() -> anyhow::Result<()> {
+        let gallery_info = GalleryInfo::new(1404009).with_request_data(
+            r#"{
+                "id": 1404009,
+                "title": "Anime",
+                "japanese_title": null,
+                "type": "anime",
+                "language": null,
+                "files": [],
+                "videofilename": "anime.mp4"
+            }"#
+            .to_string(),
+        );
+
+        let pd = gallery_info.parse()?;
+
+        assert!(pd.is_video());
+        assert_eq!(
+            Some("https://streaming.hitomi.la/videos/anime.mp4".to_string()),
+            pd.video_url()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_translation_group_without_translations() -> anyhow::Result<()> {
+        let gallery_info = GalleryInfo::new(1721169).with_request_data(
+            r#"{
+                "id": 1721169,
+                "title": "Untranslated",
+                "languages": [],
+                "files": []
+            }"#
+            .to_string(),
+        );
+
+        let pd = gallery_info.parse()?;
+
+        assert_eq!(1721169, pd.translation_group());
+
+        Ok(())
+    }
+}
@@ -1,13 +1,18 @@
 use std::char;
+#[cfg(feature = "net")]
+use std::io::Write;
 
 use anyhow;
+#[cfg(feature = "net")]
 use bytes::Bytes;
 use log::{debug, trace};
+#[cfg(feature = "net")]
 use reqwest;
 use serde::{Deserialize, Serialize};
 use serde_json;
 
 use crate::parser::Parser;
+use crate::urls;
 
 pub struct Image {
     id: u32,
@@ -50,6 +55,11 @@ impl File {
         }
     }
 
+    /// Rough size of the image, assuming about half a byte per pixel
+    pub fn estimated_bytes(&self) -> u64 {
+        self.width as u64 * self.height as u64 / 2
+    }
+
     /* pub fn ext(&self) -> &str {
         Path::new(self.name.as_str())
             .extension()
@@ -85,16 +95,17 @@ impl File {
 
         debug!("1st subdomain {}", subdomain);
 
-        let postfix = &self.hash[self.hash.len() - 3..].chars().collect::<Vec<_>>();
+        let path = urls::HashPath::new(&self.hash)
+            .ok_or_else(|| anyhow::Error::msg(format!("Hash is too short `{}`", self.hash)))?;
 
         debug!("hash {}", self.hash);
-        debug!("postfix {:?}", postfix);
+        debug!("postfix {}{}", path.middle(), path.last());
 
-        let x = format!("{}{}", postfix[0], postfix[1]);
+        let x = path.middle();
 
         debug!("x {}", x);
 
-        if let Ok(x) = u32::from_str_radix(&x, 16) {
+        if let Ok(x) = u32::from_str_radix(x, 16) {
             let mut n: u32 = 0;
 
             debug!("x {}", x);
@@ -132,20 +143,9 @@ impl File {
             )
         }; */
 
-        let image_url = format!(
-            "https://{}b.hitomi.la/images/{}/{}{}/{}.{}",
-            subdomain,
-            postfix[2],
-            postfix[0],
-            postfix[1],
-            self.hash,
-            self.name.split(".").last().unwrap()
-        );
-
-        let thumbnail_url = format!(
-            "https://tn.hitomi.la/bigtn/{}/{}{}/{}.jpg",
-            postfix[2], postfix[0], postfix[1], self.hash
-        );
+        let image_url = urls::image(&subdomain, path, self.name.split(".").last().unwrap());
+
+        let thumbnail_url = urls::thumbnail(path);
 
         debug!("image_url = {}", image_url);
         debug!("thumbnail_url = {}", thumbnail_url);
@@ -154,42 +154,63 @@ impl File {
     }
 
     /// (URL, buf)
+    #[cfg(feature = "net")]
     pub fn download(&self, content_id: u32, is_thumbnail: bool) -> anyhow::Result<(String, Bytes)> {
         let (image_url, thumbnail_url) = self.url(content_id)?;
 
         if is_thumbnail {
-            let r = self.download_(content_id, &thumbnail_url)?;
+            let r = download_image(content_id, &thumbnail_url)?;
             Ok((thumbnail_url, r))
         } else {
-            let r = self.download_(content_id, &image_url)?;
+            let r = download_image(content_id, &image_url)?;
             Ok((image_url, r))
         }
     }
+}
 
-    fn download_<U: reqwest::IntoUrl>(&self, content_id: u32, url: U) -> anyhow::Result<Bytes> {
-        trace!("File::download()");
-        let client = reqwest::blocking::Client::builder().build()?;
+#[cfg(feature = "net")]
+fn request_image<U: reqwest::IntoUrl>(
+    content_id: u32,
+    url: U,
+) -> anyhow::Result<reqwest::blocking::Response> {
+    let client = crate::client::shared()?;
+
+    let response = client
+        .get(url)
+        .header("Referer", urls::reader(content_id))
+        .send()?;
+    let response = crate::client::check_rate_limit(response)?;
+
+    if !response.status().is_success() {
+        return Err(anyhow::Error::msg(format!(
+            "Image Download Error! {}",
+            response.status().to_string()
+        )));
+    }
 
-        let response = client
-            .get(url)
-            .header(
-                "Referer",
-                format!("https://hitomi.la/reader/{}.html", content_id),
-            )
-            .send()?;
+    Ok(response)
+}
 
-        if response.status().is_success() {
-            let bytes = response.bytes()?;
-            Ok(bytes)
-        } else {
-            // debug!("{}", response.text()?);
+/// Same as `download_image`, but streams the image into `writer`, returns its size
+#[cfg(feature = "net")]
+pub fn download_image_to<U: reqwest::IntoUrl, W: Write>(
+    content_id: u32,
+    url: U,
+    writer: &mut W,
+) -> anyhow::Result<u64> {
+    trace!("download_image_to()");
+    let mut response = request_image(content_id, url)?;
+
+    Ok(response.copy_to(writer)?)
+}
 
-            Err(anyhow::Error::msg(format!(
-                "Image Download Error! {}",
-                response.status().to_string()
-            )))
-        }
-    }
+/// Downloads an image of gallery from hitomi CDN, which needs `Referer` of the reader
+#[cfg(feature = "net")]
+pub fn download_image<U: reqwest::IntoUrl>(content_id: u32, url: U) -> anyhow::Result<Bytes> {
+    trace!("download_image()");
+    let response = request_image(content_id, url)?;
+
+    Ok(Bytes::from(response.bytes()?.to_vec()))
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -209,16 +230,22 @@ impl Parser for Image {
         }
     }
 
+    fn with_request_data(mut self, request_data: Self::RequestData) -> Box<Self> {
+        self.request_data = Some(Box::new(request_data));
+        Box::new(self)
+    }
+
     fn url(&self) -> anyhow::Result<String> {
         trace!("Image::url()");
-        Ok(format!("https://ltn.hitomi.la/galleries/{}.js", self.id))
+        Ok(urls::gallery_info(self.id))
     }
 
+    #[cfg(feature = "net")]
     fn request(mut self) -> anyhow::Result<Box<Self>> {
         trace!("Image::request()");
-        let client = reqwest::blocking::Client::builder().build()?;
+        let client = crate::client::metadata()?;
 
-        let response = client.get(&self.url()?).send()?;
+        let response = crate::client::check_rate_limit(client.get(&self.url()?).send()?)?;
 
         if !response.status().is_success() {
             return Err(anyhow::Error::msg(response.status().to_string()));
@@ -254,7 +281,7 @@ impl Parser for Image {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "net"))]
 mod tests {
     use anyhow;
 
@@ -0,0 +1,185 @@
+use anyhow;
+
+use super::Provenance;
+use crate::models::{Language, Metadata, MetadataBook};
+
+/// Names of nozomi indexes and the local names `Language::from` takes
+const LOCALNAMES: [(&'static str, &'static str); 4] = [
+    ("korean", "한국어"),
+    ("japanese", "日本語"),
+    ("english", "English"),
+    ("chinese", "中文"),
+];
+
+/// `korean` => `Language::Korean`
+pub fn language_of(name: &str) -> Option<Language> {
+    LOCALNAMES
+        .iter()
+        .find(|(x, _)| x.eq_ignore_ascii_case(name))
+        .map(|(_, localname)| Language::from(*localname))
+}
+
+fn is_hangul(c: char) -> bool {
+    ('\u{AC00}'..='\u{D7A3}').contains(&c) || ('\u{1100}'..='\u{11FF}').contains(&c)
+}
+
+fn is_kana(c: char) -> bool {
+    ('\u{3040}'..='\u{30FF}').contains(&c)
+}
+
+/// Name of the language the title is written in by its script, `korean`
+///
+/// Latin and Han alone are left out, they don't tell English from others or Chinese from Japanese
+pub fn language_of_script(title: &str) -> Option<&'static str> {
+    if title.chars().any(is_hangul) {
+        Some("korean")
+    } else if title.chars().any(is_kana) {
+        Some("japanese")
+    } else {
+        None
+    }
+}
+
+/// # LanguageFallback
+/// Language of a book none of the metadata sources had, some galleries have no Language row
+///
+/// The inference is recorded in provenance, `language: index:korean` or `language: script:korean`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LanguageFallback {
+    /// Nozomi index the ids come from
+    index: Option<String>,
+    script: bool,
+}
+
+impl LanguageFallback {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_index(mut self, language: impl Into<String>) -> Self {
+        self.index = Some(language.into());
+        self
+    }
+
+    pub fn with_script(mut self, script: bool) -> Self {
+        self.script = script;
+        self
+    }
+
+    pub fn apply(&self, book: MetadataBook, provenance: &mut Provenance) -> MetadataBook {
+        if book.language != Metadata::Language(None) {
+            return book;
+        }
+
+        let of_script = || match &book.title {
+            Metadata::Title(Some(title)) if self.script => {
+                language_of_script(title).map(|name| ("script", name.to_string()))
+            }
+            _ => None,
+        };
+
+        let inferred = self
+            .index
+            .as_ref()
+            .map(|name| ("index", name.clone()))
+            .or_else(of_script)
+            .and_then(|(source, name)| language_of(&name).map(|language| (source, name, language)));
+
+        match inferred {
+            Some((source, name, language)) => {
+                provenance.insert("language".to_string(), format!("{}:{}", source, name));
+
+                MetadataBook {
+                    language: Metadata::Language(Some(language)),
+                    ..book
+                }
+            }
+            None => book,
+        }
+    }
+
+    /// `index,script`, `index` takes `index_language`, the language of the index the ids come from
+    pub fn parse(s: &str, index_language: &str) -> anyhow::Result<Self> {
+        let mut fallback = Self::new();
+
+        for name in s.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+            match name {
+                "index" => fallback = fallback.with_index(index_language),
+                "script" => fallback = fallback.with_script(true),
+                _ => {
+                    return Err(anyhow::Error::msg(format!(
+                        "Unknown language fallback `{}`",
+                        name
+                    )))
+                }
+            }
+        }
+
+        Ok(fallback)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{language_of_script, LanguageFallback};
+    use crate::models::{self, Language, Metadata, MetadataBook};
+    use crate::parser::Provenance;
+
+    fn book(title: &str) -> MetadataBook {
+        models::book(Some(1724122), Some(title))
+    }
+
+    #[test]
+    fn infer_language() -> anyhow::Result<()> {
+        assert_eq!(
+            Some("korean"),
+            language_of_script("Tsundere Imouto | 츤데레 여동생")
+        );
+        assert_eq!(Some("japanese"), language_of_script("ツンデレ妹"));
+        assert_eq!(None, language_of_script("Tsundere Imouto"));
+
+        let mut provenance = Provenance::new();
+        let fallback = LanguageFallback::new().with_script(true);
+        let inferred = fallback.apply(book("츤데레 여동생"), &mut provenance);
+
+        assert_eq!(
+            Metadata::Language(Some(Language::Korean)),
+            inferred.language
+        );
+        assert_eq!(
+            Some(&"script:korean".to_string()),
+            provenance.get("language")
+        );
+
+        // the index comes first
+        let mut provenance = Provenance::new();
+        let fallback = fallback.with_index("korean");
+        let inferred = fallback.apply(book("ツンデレ妹"), &mut provenance);
+
+        assert_eq!(
+            Metadata::Language(Some(Language::Korean)),
+            inferred.language
+        );
+        assert_eq!(
+            Some(&"index:korean".to_string()),
+            provenance.get("language")
+        );
+
+        let mut provenance = Provenance::new();
+        let inferred = LanguageFallback::new().apply(book("츤데레 여동생"), &mut provenance);
+
+        assert_eq!(Metadata::Language(None), inferred.language);
+        assert!(provenance.is_empty());
+
+        assert_eq!(
+            LanguageFallback::new()
+                .with_index("korean")
+                .with_script(true),
+            LanguageFallback::parse("index,script", "korean")?
+        );
+        assert!(LanguageFallback::parse("index,title", "korean").is_err());
+
+        Ok(())
+    }
+}
@@ -1,14 +1,36 @@
 use anyhow;
 
+mod created_at;
 mod gallery;
 mod gallery_block;
+mod gallery_info;
 mod image;
+mod language;
 mod nozomi;
+mod optional_list;
+mod registry;
+mod series;
+mod title;
 
-pub use gallery::Gallery;
+pub use created_at::{format_created_at, parse_created_at, CreatedAtPolicy, FutureDates};
+pub use gallery::{ContentUrls, Gallery};
+#[cfg(feature = "net")]
+pub use gallery_block::exists;
 pub use gallery_block::GalleryBlock;
+pub use gallery_info::{GalleryInfo, GalleryInfoData, Translation};
+#[cfg(feature = "net")]
+pub use image::{download_image, download_image_to};
 pub use image::{File, Image};
-pub use nozomi::Nozomi;
+pub use language::{language_of, language_of_script, LanguageFallback};
+#[cfg(feature = "net")]
+pub use nozomi::cross_check;
+pub use nozomi::{check_samples, FormatChanged, Nozomi, NozomiIndex, NozomiTarget};
+pub use optional_list::OptionalList;
+pub use registry::{sort_book, MetadataSource, ParserRegistry, Provenance};
+#[cfg(feature = "net")]
+pub use registry::{GalleryBlockSource, GalleryInfoSource, GallerySource};
+pub use series::SeriesListing;
+pub use title::Title;
 
 pub trait Parser {
     // self.request_data;
@@ -17,8 +39,12 @@ pub trait Parser {
 
     fn request_data(&self) -> anyhow::Result<&Box<Self::RequestData>>;
 
+    /// Parses given data instead of requesting it
+    fn with_request_data(self, request_data: Self::RequestData) -> Box<Self>;
+
     fn url(&self) -> anyhow::Result<String>;
 
+    #[cfg(feature = "net")]
     fn request(self) -> anyhow::Result<Box<Self>>;
 
     fn parse(&self) -> anyhow::Result<Self::ParseData>;
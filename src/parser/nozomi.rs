@@ -1,12 +1,114 @@
 use std::convert::TryInto;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
 
 use anyhow;
 use bytes::Bytes;
-use log::{debug, trace};
-use madome_client::book::Language;
-use reqwest;
+use log::{debug, trace, warn};
 
 use super::Parser;
+use crate::urls;
+
+/// Which nozomi file of a language
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NozomiIndex {
+    /// `index-{language}.nozomi`, newest first
+    Latest,
+    /// `popular/{period}-{language}.nozomi`, most popular first
+    Today,
+    Week,
+    Month,
+    Year,
+}
+
+impl NozomiIndex {
+    pub fn path(&self) -> &'static str {
+        match self {
+            Self::Latest => "index",
+            Self::Today => "popular/today",
+            Self::Week => "popular/week",
+            Self::Month => "popular/month",
+            Self::Year => "popular/year",
+        }
+    }
+
+    pub fn is_popular(&self) -> bool {
+        *self != Self::Latest
+    }
+}
+
+impl Default for NozomiIndex {
+    fn default() -> Self {
+        Self::Latest
+    }
+}
+
+impl Display for NozomiIndex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path())
+    }
+}
+
+/// `index`, `popular/today`, `popular/week`, `popular/month` or `popular/year`
+impl FromStr for NozomiIndex {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let r = match s.trim() {
+            "index" => Self::Latest,
+            "popular/today" => Self::Today,
+            "popular/week" => Self::Week,
+            "popular/month" => Self::Month,
+            "popular/year" => Self::Year,
+            _ => return Err(anyhow::Error::msg(format!("Unknown nozomi index `{}`", s))),
+        };
+
+        Ok(r)
+    }
+}
+
+/// Galleries of one artist or series instead of the whole language,
+/// `sync --artist airandou`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NozomiTarget {
+    Artist(String),
+    Series(String),
+}
+
+impl NozomiTarget {
+    /// Tag area of hitomi
+    pub fn area(&self) -> &'static str {
+        match self {
+            Self::Artist(_) => "artist",
+            Self::Series(_) => "series",
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Artist(name) | Self::Series(name) => name,
+        }
+    }
+
+    /// For file names, `artist-kisaragi_gunma`
+    pub fn slug(&self) -> String {
+        let name = self
+            .name()
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+
+        format!("{}-{}", self.area(), name)
+    }
+}
+
+impl Display for NozomiTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} `{}`", self.area(), self.name())
+    }
+}
 
 /// # Nozomi Parser
 /// Not needed VPN for Nozomi Parser
@@ -16,18 +118,181 @@ pub struct Nozomi {
     page: usize,
     per_page: usize,
     language: String,
+    index: NozomiIndex,
+    /// Overrides `index`
+    target: Option<NozomiTarget>,
     request_data: Option<Box<Bytes>>,
+    /// Number of ids in the whole index, from `Content-Range`
+    total: Option<usize>,
 }
 
 impl Nozomi {
-    pub fn new(page: usize, per_page: usize, language: Language) -> Nozomi {
+    /// `Language::Korean` or a name of hitomi such as `korean`
+    pub fn new(page: usize, per_page: usize, language: impl Into<String>) -> Nozomi {
         Nozomi {
             page,
             per_page,
             language: language.into(),
+            index: NozomiIndex::Latest,
+            target: None,
             request_data: None,
+            total: None,
+        }
+    }
+
+    pub fn with_index(mut self, index: NozomiIndex) -> Self {
+        self.index = index;
+        self
+    }
+
+    pub fn with_target(mut self, target: Option<NozomiTarget>) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn total_ids(&self) -> Option<usize> {
+        self.total
+    }
+
+    /// Ids decoded as they are iterated, without a `Vec` of the whole response
+    ///
+    /// Implausible ids are errors, unlike `parse` the order isn't checked
+    pub fn ids(&self) -> anyhow::Result<impl Iterator<Item = anyhow::Result<u32>> + '_> {
+        let request_data = self.request_data()?;
+
+        Ok(request_data.chunks_exact(4).map(|chunk| {
+            let id = u32::from_be_bytes(chunk.try_into()?);
+
+            if id == 0 || id >= MAX_PLAUSIBLE_ID {
+                return Err(format_changed(format!("implausible id {}", id)));
+            }
+
+            Ok(id)
+        }))
+    }
+
+    /// Indexes of tags are sorted by date as the latest one
+    fn is_ordered(&self) -> bool {
+        self.target.is_some() || !self.index.is_popular()
+    }
+}
+
+/// Ids above it can't be real for years, hitomi has about 2 million galleries
+pub const MAX_PLAUSIBLE_ID: u32 = 1 << 24;
+
+/// Decoded ids look like garbage, hitomi may have changed the encoding of nozomi
+///
+/// It stops the synchronize instead of syncing junk ids
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatChanged {
+    pub reason: String,
+}
+
+impl Display for FormatChanged {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Nozomi format changed: {}", self.reason)
+    }
+}
+
+impl Error for FormatChanged {}
+
+fn format_changed(reason: String) -> anyhow::Error {
+    FormatChanged { reason }.into()
+}
+
+/// Checks ids in the order of the index
+///
+/// Only the latest index is sorted, `ordered` is false for popular indexes
+pub fn check_format(ids: &[u32], ordered: bool) -> anyhow::Result<()> {
+    if let Some(id) = ids.iter().find(|id| **id == 0 || **id >= MAX_PLAUSIBLE_ID) {
+        return Err(format_changed(format!("implausible id {}", id)));
+    }
+
+    // the index is sorted by date, mostly by descending id as well
+    if ordered && ids.len() >= 8 {
+        let descending = ids.windows(2).filter(|x| x[0] > x[1]).count();
+
+        if descending * 2 < ids.len() - 1 {
+            return Err(format_changed(format!(
+                "only {} of {} ids are descending",
+                descending,
+                ids.len() - 1
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Ids of a page whose gallery block `cross_check` asks for
+const CROSS_CHECK_SAMPLES: usize = 5;
+
+/// Evenly spaced from the first id to the last one
+fn samples(ids: &[u32]) -> Vec<u32> {
+    if ids.len() <= CROSS_CHECK_SAMPLES {
+        return ids.to_vec();
+    }
+
+    let mut samples = (0..CROSS_CHECK_SAMPLES)
+        .map(|i| ids[i * (ids.len() - 1) / (CROSS_CHECK_SAMPLES - 1)])
+        .collect::<Vec<_>>();
+    samples.dedup();
+
+    samples
+}
+
+/// Most of the sampled ids must have their gallery block
+///
+/// A gallery deleted after the nozomi was fetched, or one whose gallery block isn't published yet,
+/// is only a warning. A sample that can't be asked, e.g. a timeout, tells nothing either way
+pub fn check_samples(
+    ids: &[u32],
+    mut exists: impl FnMut(u32) -> anyhow::Result<bool>,
+) -> anyhow::Result<()> {
+    let mut checked = 0;
+    let mut missing = vec![];
+
+    for id in samples(ids) {
+        match exists(id) {
+            Ok(true) => checked += 1,
+            Ok(false) => {
+                checked += 1;
+                missing.push(id);
+            }
+            Err(err) => debug!("{}: Can't cross-check gallery block: {}", id, err),
         }
     }
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    if missing.len() * 2 > checked {
+        return Err(format_changed(format!(
+            "gallery blocks of {} of {} sampled ids are not found, {:?}",
+            missing.len(),
+            checked,
+            missing
+        )));
+    }
+
+    warn!(
+        "Gallery blocks of {:?} are not found, deleted or not published yet",
+        missing
+    );
+
+    Ok(())
+}
+
+/// `check_samples` asking hitomi
+#[cfg(feature = "net")]
+pub fn cross_check(ids: &[u32]) -> anyhow::Result<()> {
+    check_samples(ids, super::exists)
+}
+
+/// `bytes 0-99/2147940` => 2147940
+pub fn parse_content_range_total(content_range: &str) -> Option<usize> {
+    content_range.rsplit('/').next()?.trim().parse().ok()
 }
 
 impl Parser for Nozomi {
@@ -41,16 +306,26 @@ impl Parser for Nozomi {
         }
     }
 
+    fn with_request_data(mut self, request_data: Self::RequestData) -> Box<Self> {
+        self.request_data = Some(Box::new(request_data));
+        Box::new(self)
+    }
+
     fn url(&self) -> anyhow::Result<String> {
-        Ok(format!(
-            "https://ltn.hitomi.la/index-{}.nozomi",
-            self.language.to_lowercase()
-        ))
+        match &self.target {
+            Some(target) => Ok(urls::tag_nozomi(
+                target.area(),
+                target.name(),
+                &self.language,
+            )),
+            None => Ok(urls::nozomi(self.index.path(), &self.language)),
+        }
     }
 
+    #[cfg(feature = "net")]
     fn request(mut self) -> anyhow::Result<Box<Self>> {
         trace!("Nozomi::request()");
-        let client = reqwest::blocking::Client::builder().build()?;
+        let client = crate::client::metadata()?;
 
         let start_bytes = (self.page - 1) * self.per_page * 4;
         let end_bytes = start_bytes + self.per_page * 4 - 1;
@@ -58,11 +333,37 @@ impl Parser for Nozomi {
         debug!("start_bytes = {}", start_bytes);
         debug!("end_bytes = {}", end_bytes);
 
-        let bytes = client
+        let response = client
             .get(&self.url()?)
             .header("Range", format!("bytes={}-{}", start_bytes, end_bytes))
-            .send()?
-            .bytes()?;
+            .send()?;
+        let response = crate::client::check_rate_limit(response)?;
+
+        // a nozomi is never html, the maintenance page is
+        let is_html = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|x| x.to_str().ok())
+            .map_or(false, |x| x.starts_with("text/html"));
+
+        if is_html {
+            let url = self.url()?;
+            crate::client::text(response)?;
+
+            return Err(anyhow::Error::msg(format!(
+                "error occurs html instead of nozomi in parser::Nozomi::request(), {}",
+                url
+            )));
+        }
+
+        self.total = response
+            .headers()
+            .get("Content-Range")
+            .and_then(|x| x.to_str().ok())
+            .and_then(parse_content_range_total)
+            .map(|total_bytes| total_bytes / 4);
+
+        let bytes = Bytes::from(response.bytes()?.to_vec());
 
         self.request_data = Some(Box::new(bytes));
         Ok(Box::new(self))
@@ -74,25 +375,21 @@ impl Parser for Nozomi {
 
         let mut res = vec![];
 
-        'a: for i in (0..request_data.len()).step_by(4) {
-            let mut temp: u32 = 0;
-
-            for j in 0..3 {
-                // https://github.com/Project-Madome/Madome-Synchronizer/issues/1
-                // temp += TryInto::<i32>::try_into(request_data[i + (3 - j)])? << (j << 3);
-                if let Some(a) = request_data.get(i + (3 - j)) {
-                    temp += TryInto::<u32>::try_into(*a)? << (j << 3);
-                } else {
-                    break 'a;
-                }
-            }
+        // https://github.com/Project-Madome/Madome-Synchronizer/issues/1
+        for chunk in request_data.chunks_exact(4) {
+            let temp = u32::from_be_bytes(chunk.try_into()?);
 
             debug!("id = {}", temp);
 
             res.push(temp);
         }
 
-        res.sort_by(|a, b| b.cmp(a));
+        check_format(&res, self.is_ordered())?;
+
+        // popular indexes keep their order
+        if self.is_ordered() {
+            res.sort_by(|a, b| b.cmp(a));
+        }
 
         Ok(res)
     }
@@ -100,11 +397,158 @@ impl Parser for Nozomi {
 
 #[cfg(test)]
 mod test {
-    use madome_client::book::Language;
+    use bytes::Bytes;
 
-    use super::Nozomi;
     use super::Parser;
+    use super::{
+        check_format, check_samples, parse_content_range_total, FormatChanged, Nozomi, NozomiIndex,
+        NozomiTarget,
+    };
+    use crate::models::Language;
+
+    fn nozomi_of(ids: &[u32], to_bytes: fn(u32) -> [u8; 4]) -> Box<Nozomi> {
+        let bytes = ids
+            .iter()
+            .flat_map(|id| to_bytes(*id).to_vec())
+            .collect::<Vec<_>>();
+
+        Nozomi::new(1, ids.len(), Language::Korean).with_request_data(Bytes::from(bytes))
+    }
+
+    #[test]
+    fn parse_nozomi_fixture() -> anyhow::Result<()> {
+        let ids = [1724122, 1724121, 1724100, 1723999];
+
+        let pd = nozomi_of(&ids, u32::to_be_bytes).parse()?;
+
+        assert_eq!(ids.to_vec(), pd);
+
+        Ok(())
+    }
+
+    #[test]
+    fn detect_changed_byte_order() -> anyhow::Result<()> {
+        let ids = [1724122, 1724121, 1724100, 1723999];
+
+        let err = nozomi_of(&ids, u32::to_le_bytes).parse().unwrap_err();
+
+        assert!(err.downcast_ref::<FormatChanged>().is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn detect_implausible_ids() -> anyhow::Result<()> {
+        assert!(check_format(&[1724122, 0], true).is_err());
+
+        let shuffled = [10, 20, 30, 40, 50, 60, 70, 80, 90];
+        assert!(check_format(&shuffled, true).is_err());
+        assert!(check_format(&shuffled, false).is_ok());
+
+        let mostly_descending = [90, 80, 85, 70, 60, 50, 40, 30, 20];
+        assert!(check_format(&mostly_descending, true).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tolerate_a_missing_gallery_block() -> anyhow::Result<()> {
+        let ids = (1724100..1724122).rev().collect::<Vec<_>>();
+        let mut asked = vec![];
+
+        // deleted between the nozomi and the check
+        check_samples(&ids, |id| {
+            asked.push(id);
+            Ok(id != 1724121)
+        })?;
+
+        assert_eq!(5, asked.len());
+        assert_eq!(Some(&1724121), asked.first());
+        assert_eq!(Some(&1724100), asked.last());
+
+        let err = check_samples(&ids, |id| Ok(id == 1724121)).unwrap_err();
+        assert!(err.downcast_ref::<FormatChanged>().is_some());
+
+        // inconclusive, only the answered samples count
+        assert!(check_samples(&ids, |_| Err(anyhow::Error::msg("503"))).is_ok());
+        assert!(check_samples(&ids, |id| match id {
+            1724121 => Ok(false),
+            1724100 => Ok(true),
+            _ => Err(anyhow::Error::msg("503")),
+        })
+        .is_ok());
+        assert!(check_samples(&ids, |id| match id {
+            1724121 => Ok(false),
+            _ => Err(anyhow::Error::msg("503")),
+        })
+        .is_err());
+        assert!(check_samples(&[], |_| Ok(false)).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_popular_index() -> anyhow::Result<()> {
+        let ids = [1724100, 1724122, 1723999];
+
+        let nozomi = Nozomi::new(1, 3, Language::Korean).with_index("popular/week".parse()?);
+        let bytes = ids
+            .iter()
+            .flat_map(|id| id.to_be_bytes().to_vec())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            "https://ltn.hitomi.la/popular/week-korean.nozomi",
+            nozomi.url()?
+        );
+        assert_eq!(
+            ids.to_vec(),
+            nozomi.with_request_data(Bytes::from(bytes)).parse()?
+        );
+        assert_eq!(NozomiIndex::Latest, "index".parse()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_artist_index() -> anyhow::Result<()> {
+        let ids = [1724122, 1724100, 1723999];
+        let target = NozomiTarget::Artist("Kisaragi Gunma".to_string());
+
+        assert_eq!("artist-kisaragi_gunma", target.slug());
+
+        let nozomi = Nozomi::new(1, 3, Language::Korean)
+            .with_index("popular/week".parse()?)
+            .with_target(Some(target));
+        let bytes = ids
+            .iter()
+            .flat_map(|id| id.to_be_bytes().to_vec())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            "https://ltn.hitomi.la/n/artist/kisaragi%20gunma-korean.nozomi",
+            nozomi.url()?
+        );
+        assert_eq!(
+            ids.to_vec(),
+            nozomi.with_request_data(Bytes::from(bytes)).parse()?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_total_of_content_range() -> anyhow::Result<()> {
+        assert_eq!(
+            Some(2147940),
+            parse_content_range_total("bytes 0-99/2147940")
+        );
+        assert_eq!(None, parse_content_range_total("bytes 0-99/*"));
+
+        Ok(())
+    }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_nozomi() -> anyhow::Result<()> {
         let nozomi_parser = Nozomi::new(1, 25, Language::Korean);
@@ -118,6 +562,7 @@ mod test {
         Ok(())
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn parse_nozomi_index_out_of_bounds() -> anyhow::Result<()> {
         let nozomi_parser = Nozomi::new(20, 1000000, Language::Korean);
@@ -0,0 +1,258 @@
+use scraper::{ElementRef, Selector};
+
+/// # OptionalList
+/// A metadata cell of hitomi that has a list, such as artists, series, characters, groups or tags
+///
+/// `N/A`, no items and blank items all mean that there is nothing, so they are `None`.
+/// `Some` always has at least one item.
+pub struct OptionalList;
+
+impl OptionalList {
+    pub fn is_nothing(element: &ElementRef<'_>) -> bool {
+        let text = element.text().collect::<String>();
+        let text = text.trim();
+
+        text.is_empty() || text == "N/A"
+    }
+
+    /// A cell of one value such as the type or the language, the text of its anchor if any
+    pub fn parse_one(element: ElementRef<'_>) -> Option<String> {
+        if Self::is_nothing(&element) {
+            return None;
+        }
+
+        let a_selector = Selector::parse("a").unwrap();
+
+        let text = match element.select(&a_selector).next() {
+            Some(a) => a.text().collect::<String>(),
+            None => element.text().collect::<String>(),
+        };
+        let text = text.trim();
+
+        if text.is_empty() {
+            return None;
+        }
+
+        Some(text.to_string())
+    }
+
+    pub fn parse(element: ElementRef<'_>) -> Option<Vec<String>> {
+        if Self::is_nothing(&element) {
+            return None;
+        }
+
+        let ul_selector = Selector::parse("ul").unwrap();
+        let li_selector = Selector::parse("li").unwrap();
+
+        let items = element
+            .select(&ul_selector)
+            .next()?
+            .select(&li_selector)
+            .filter_map(|li| {
+                li.text()
+                    .map(|text| text.trim())
+                    .find(|text| !text.is_empty())
+                    .map(|text| text.to_string())
+            })
+            .collect::<Vec<_>>();
+
+        if items.is_empty() {
+            return None;
+        }
+
+        Some(items)
+    }
+
+    /// Tags from the hrefs of their anchors, `/tag/female%3Aloli-all.html` is `loli ♀`
+    ///
+    /// The text is `loli ♀` on some pages and `female:loli` on others,
+    /// it is only used when an anchor has no tag href
+    pub fn parse_tags(element: ElementRef<'_>) -> Option<Vec<String>> {
+        if Self::is_nothing(&element) {
+            return None;
+        }
+
+        let ul_selector = Selector::parse("ul").unwrap();
+        let li_selector = Selector::parse("li").unwrap();
+        let a_selector = Selector::parse("a").unwrap();
+
+        let items = element
+            .select(&ul_selector)
+            .next()?
+            .select(&li_selector)
+            .filter_map(|li| {
+                let href_tag = li
+                    .select(&a_selector)
+                    .filter_map(|a| a.value().attr("href"))
+                    .find_map(tag_of_href);
+
+                href_tag.or_else(|| {
+                    let text = li.text().collect::<String>();
+                    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+                    Some(tag_of_text(&text)).filter(|text| !text.is_empty())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if items.is_empty() {
+            return None;
+        }
+
+        Some(items)
+    }
+}
+
+/// `/tag/female%3Aloli-all.html` -> `loli ♀`
+pub fn tag_of_href(href: &str) -> Option<String> {
+    let tag = href.trim().strip_prefix("/tag/")?;
+    // `-all.html`, or the language of the index
+    let tag = &tag[..tag.rfind('-')?];
+
+    Some(tag_of_text(&percent_decode(tag)?)).filter(|tag| !tag.is_empty())
+}
+
+/// `female:loli` -> `loli ♀`, `loli ♀` as it is
+fn tag_of_text(text: &str) -> String {
+    let text = text.trim();
+
+    if let Some(tag) = text.strip_prefix("female:") {
+        format!("{} ♀", tag.trim())
+    } else if let Some(tag) = text.strip_prefix("male:") {
+        format!("{} ♂", tag.trim())
+    } else {
+        text.to_string()
+    }
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut r = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3)?;
+            r.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            r.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(r).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use scraper::{Html, Selector};
+
+    use super::{tag_of_href, OptionalList};
+
+    fn parse(html: &str) -> Option<Vec<String>> {
+        let fragment = Html::parse_fragment(&format!("<div class=\"cell\">{}</div>", html));
+        let selector = Selector::parse(".cell").unwrap();
+
+        OptionalList::parse(fragment.select(&selector).next().unwrap())
+    }
+
+    fn parse_one(html: &str) -> Option<String> {
+        let fragment = Html::parse_fragment(&format!("<div class=\"cell\">{}</div>", html));
+        let selector = Selector::parse(".cell").unwrap();
+
+        OptionalList::parse_one(fragment.select(&selector).next().unwrap())
+    }
+
+    fn parse_tags(html: &str) -> Option<Vec<String>> {
+        let fragment = Html::parse_fragment(&format!("<div class=\"cell\">{}</div>", html));
+        let selector = Selector::parse(".cell").unwrap();
+
+        OptionalList::parse_tags(fragment.select(&selector).next().unwrap())
+    }
+
+    fn tags(tags: &[&str]) -> Option<Vec<String>> {
+        Some(tags.iter().map(|x| x.to_string()).collect())
+    }
+
+    #[test]
+    fn parse_optional_list() -> anyhow::Result<()> {
+        assert_eq!(None, parse("N/A"));
+        assert_eq!(None, parse("\n  N/A\n"));
+        assert_eq!(None, parse(""));
+        assert_eq!(None, parse("<ul>\n</ul>"));
+        assert_eq!(None, parse("<ul><li> </li></ul>"));
+        assert_eq!(
+            Some(vec!["lum".to_string(), "shampoo".to_string()]),
+            parse("<ul><li><a href=\"/1\">lum</a></li>\n<li> <a href=\"/2\">shampoo</a></li></ul>")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_one_value() -> anyhow::Result<()> {
+        assert_eq!(None, parse_one("N/A"));
+        assert_eq!(None, parse_one(""));
+        assert_eq!(None, parse_one("<a href=\"/1\"> </a>"));
+        assert_eq!(
+            Some("한국어".to_string()),
+            parse_one("\n<a href=\"/index-korean.html\">한국어</a>\n")
+        );
+        assert_eq!(Some("manga".to_string()), parse_one(" manga "));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_tags_of_symbol_suffixes() -> anyhow::Result<()> {
+        let html = r#"<ul>
+            <li><a href="/tag/female%3Afootjob-all.html">footjob ♀</a></li>
+            <li><a href="/tag/female%3Aloli-all.html">loli <span>♀</span></a></li>
+            <li><a href="/tag/male%3Ashota-all.html">shota ♂</a></li>
+            <li><a href="/tag/incest-all.html">incest</a></li>
+        </ul>"#;
+
+        assert_eq!(
+            tags(&["footjob ♀", "loli ♀", "shota ♂", "incest"]),
+            parse_tags(html)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_tags_of_url_prefixes() -> anyhow::Result<()> {
+        let html = r#"<ul>
+            <li><a href="/tag/female%3Abig%20breasts-korean.html">female:big breasts</a></li>
+            <li><a href="/tag/male%3Ashota-all.html">male:shota</a></li>
+            <li><a href="/tag/full%20color-all.html">full color</a></li>
+            <li>female:sister</li>
+        </ul>"#;
+
+        assert_eq!(
+            tags(&["big breasts ♀", "shota ♂", "full color", "sister ♀"]),
+            parse_tags(html)
+        );
+        assert_eq!(None, parse_tags("N/A"));
+        assert_eq!(None, parse_tags("<ul></ul>"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn tag_of_hrefs() -> anyhow::Result<()> {
+        assert_eq!(
+            Some("loli ♀".to_string()),
+            tag_of_href("/tag/female%3Aloli-all.html")
+        );
+        assert_eq!(
+            Some("x-ray".to_string()),
+            tag_of_href("/tag/x-ray-all.html")
+        );
+        assert_eq!(None, tag_of_href("/artist/loli-all.html"));
+        assert_eq!(None, tag_of_href("/tag/%E3-all.html"));
+
+        Ok(())
+    }
+}
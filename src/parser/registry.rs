@@ -0,0 +1,405 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use anyhow;
+use log::debug;
+use time::OffsetDateTime;
+
+use super::{ContentUrls, CreatedAtPolicy, LanguageFallback};
+use crate::alias::AliasTable;
+use crate::memory::Semaphore;
+use crate::models::{Metadata, MetadataBook};
+
+#[cfg(feature = "net")]
+use super::{Gallery, GalleryBlock, GalleryInfo, Parser};
+
+/// Parser fetching `MetadataBook` of a gallery,
+/// usable as a trait object in `ParserRegistry`
+pub trait MetadataSource: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn fetch(&self, id: u32) -> anyhow::Result<MetadataBook>;
+}
+
+/// https://ltn.hitomi.la/galleryblock/{id}.html
+#[cfg(feature = "net")]
+pub struct GalleryBlockSource;
+
+#[cfg(feature = "net")]
+impl MetadataSource for GalleryBlockSource {
+    fn name(&self) -> &'static str {
+        "block"
+    }
+
+    fn fetch(&self, id: u32) -> anyhow::Result<MetadataBook> {
+        GalleryBlock::new(id).request()?.parse()
+    }
+}
+
+/// https://hitomi.la/galleries/{id}.html
+#[cfg(feature = "net")]
+pub struct GallerySource {
+    content_urls: Arc<ContentUrls>,
+}
+
+#[cfg(feature = "net")]
+impl GallerySource {
+    pub fn new(content_urls: Arc<ContentUrls>) -> Self {
+        Self { content_urls }
+    }
+}
+
+#[cfg(feature = "net")]
+impl MetadataSource for GallerySource {
+    fn name(&self) -> &'static str {
+        "html"
+    }
+
+    fn fetch(&self, id: u32) -> anyhow::Result<MetadataBook> {
+        let gallery = Gallery::new(id)
+            .with_content_url(self.content_urls.get(id))
+            .request()?;
+
+        if let Some(content_url) = gallery.content_url() {
+            self.content_urls.insert(id, content_url.to_string());
+        }
+
+        gallery.parse()
+    }
+}
+
+/// https://ltn.hitomi.la/galleries/{id}.js
+#[cfg(feature = "net")]
+pub struct GalleryInfoSource;
+
+#[cfg(feature = "net")]
+impl MetadataSource for GalleryInfoSource {
+    fn name(&self) -> &'static str {
+        "js"
+    }
+
+    fn fetch(&self, id: u32) -> anyhow::Result<MetadataBook> {
+        Ok(GalleryInfo::new(id).request()?.parse()?.to_metadata_book())
+    }
+}
+
+/// Name of the source each field of a merged book came from, `tags: js`
+pub type Provenance = BTreeMap<String, String>;
+
+/// # ParserRegistry
+/// Metadata sources in order of priority
+///
+/// Each field of `MetadataBook` is taken from the first source having it
+pub struct ParserRegistry {
+    sources: Vec<Box<dyn MetadataSource>>,
+    /// Applied to the merged book
+    aliases: AliasTable,
+    /// Applied to the merged book after `aliases`
+    language_fallback: LanguageFallback,
+    /// Applied to the merged book last
+    created_at: CreatedAtPolicy,
+    /// Sources fetching and parsing at once, each holds a whole document
+    parses: Semaphore,
+    content_urls: Arc<ContentUrls>,
+}
+
+impl ParserRegistry {
+    pub fn new() -> Self {
+        Self {
+            sources: vec![],
+            aliases: AliasTable::new(),
+            language_fallback: LanguageFallback::new(),
+            created_at: CreatedAtPolicy::new(),
+            parses: Semaphore::unlimited(),
+            content_urls: Arc::new(ContentUrls::new()),
+        }
+    }
+
+    /// Shared with `GallerySource`
+    pub fn content_urls(&self) -> &ContentUrls {
+        &self.content_urls
+    }
+
+    pub fn with_aliases(mut self, aliases: AliasTable) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    pub fn with_language_fallback(mut self, language_fallback: LanguageFallback) -> Self {
+        self.language_fallback = language_fallback;
+        self
+    }
+
+    pub fn with_created_at(mut self, created_at: CreatedAtPolicy) -> Self {
+        self.created_at = created_at;
+        self
+    }
+
+    /// At most `parses` sources fetch and parse at once, `None` is unlimited
+    pub fn with_parse_limit(mut self, parses: Option<usize>) -> Self {
+        self.parses = Semaphore::new(parses);
+        self
+    }
+
+    /// gallery block, and gallery html for groups and characters
+    #[cfg(feature = "net")]
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+
+        let gallery_source = GallerySource::new(Arc::clone(&registry.content_urls));
+
+        registry.register(Box::new(GalleryBlockSource));
+        registry.register(Box::new(gallery_source));
+
+        registry
+    }
+
+    /// Comma separated source names, `block,html`
+    #[cfg(feature = "net")]
+    pub fn from_names(names: &str) -> anyhow::Result<Self> {
+        let mut registry = Self::new();
+
+        for name in names.split(',').map(|name| name.trim()) {
+            let source: Box<dyn MetadataSource> = match name {
+                "block" => Box::new(GalleryBlockSource),
+                "html" => Box::new(GallerySource::new(Arc::clone(&registry.content_urls))),
+                "js" => Box::new(GalleryInfoSource),
+                _ => {
+                    return Err(anyhow::Error::msg(format!(
+                        "Unknown metadata source `{}`",
+                        name
+                    )))
+                }
+            };
+
+            registry.register(source);
+        }
+
+        Ok(registry)
+    }
+
+    pub fn register(&mut self, source: Box<dyn MetadataSource>) {
+        self.sources.push(source);
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.sources.iter().map(|source| source.name()).collect()
+    }
+
+    pub fn fetch(&self, id: u32) -> anyhow::Result<MetadataBook> {
+        Ok(self.fetch_with_provenance(id)?.0)
+    }
+
+    pub fn fetch_with_provenance(&self, id: u32) -> anyhow::Result<(MetadataBook, Provenance)> {
+        let mut sources = self.sources.iter();
+
+        let first = sources
+            .next()
+            .ok_or_else(|| anyhow::Error::msg("No metadata source is registered"))?;
+
+        debug!("{}: fetch metadata from {}", id, first.name());
+        let mut metadata_book = {
+            let _permit = self.parses.acquire();
+            first.fetch(id)?
+        };
+        let mut provenance = Provenance::new();
+
+        record(&mut provenance, &metadata_book, first.name());
+
+        for source in sources {
+            debug!("{}: fetch metadata from {}", id, source.name());
+            let next = {
+                let _permit = self.parses.acquire();
+                source.fetch(id)?
+            };
+
+            record(&mut provenance, &next, source.name());
+            metadata_book = merge_book(metadata_book, next);
+        }
+
+        let metadata_book = self.aliases.apply_book(metadata_book)?;
+        let metadata_book = self.language_fallback.apply(metadata_book, &mut provenance);
+        let metadata_book = self.created_at.apply(
+            id,
+            metadata_book,
+            &mut provenance,
+            OffsetDateTime::now_utc(),
+        )?;
+
+        Ok((sort_book(metadata_book), provenance))
+    }
+}
+
+/// Fields of `book` nothing has supplied yet came from `source`, same as `merge_book`
+fn record(provenance: &mut Provenance, book: &MetadataBook, source: &str) {
+    let fields = [
+        ("id", book.id != Metadata::ID(None)),
+        ("title", book.title != Metadata::Title(None)),
+        ("artists", book.artists != Metadata::Artists(None)),
+        ("series", book.series != Metadata::Series(None)),
+        ("groups", book.groups != Metadata::Groups(None)),
+        ("characters", book.characters != Metadata::Characters(None)),
+        ("tags", book.tags != Metadata::Tags(None)),
+        ("language", book.language != Metadata::Language(None)),
+        (
+            "content_type",
+            book.content_type != Metadata::ContentType(None),
+        ),
+        ("created_at", book.created_at != Metadata::CreatedAt(None)),
+        (
+            "thumbnail_url",
+            book.thumbnail_url != Metadata::ThumbnailURL(None),
+        ),
+        ("page_count", book.page_count != Metadata::Page(None)),
+    ];
+
+    for (field, _) in fields.iter().filter(|(_, has)| *has) {
+        provenance
+            .entry(field.to_string())
+            .or_insert_with(|| source.to_string());
+    }
+}
+
+fn sorted(names: Option<Vec<String>>) -> Option<Vec<String>> {
+    names.map(|mut names| {
+        names.sort();
+        names.dedup();
+        names
+    })
+}
+
+/// Name lists sorted, a book is the same whatever order its sources listed them in
+pub fn sort_book(book: MetadataBook) -> MetadataBook {
+    let sort = |names| match names {
+        Metadata::Artists(x) => Metadata::Artists(sorted(x)),
+        Metadata::Series(x) => Metadata::Series(sorted(x)),
+        Metadata::Groups(x) => Metadata::Groups(sorted(x)),
+        Metadata::Characters(x) => Metadata::Characters(sorted(x)),
+        Metadata::Tags(x) => Metadata::Tags(sorted(x)),
+        x => x,
+    };
+
+    MetadataBook {
+        artists: sort(book.artists),
+        series: sort(book.series),
+        groups: sort(book.groups),
+        characters: sort(book.characters),
+        tags: sort(book.tags),
+        ..book
+    }
+}
+
+fn merge(a: Metadata, b: Metadata, nothing: Metadata) -> Metadata {
+    if a == nothing {
+        b
+    } else {
+        a
+    }
+}
+
+fn merge_book(a: MetadataBook, b: MetadataBook) -> MetadataBook {
+    MetadataBook {
+        id: merge(a.id, b.id, Metadata::ID(None)),
+        title: merge(a.title, b.title, Metadata::Title(None)),
+        artists: merge(a.artists, b.artists, Metadata::Artists(None)),
+        series: merge(a.series, b.series, Metadata::Series(None)),
+        groups: merge(a.groups, b.groups, Metadata::Groups(None)),
+        characters: merge(a.characters, b.characters, Metadata::Characters(None)),
+        tags: merge(a.tags, b.tags, Metadata::Tags(None)),
+        language: merge(a.language, b.language, Metadata::Language(None)),
+        content_type: merge(a.content_type, b.content_type, Metadata::ContentType(None)),
+        created_at: merge(a.created_at, b.created_at, Metadata::CreatedAt(None)),
+        thumbnail_url: merge(
+            a.thumbnail_url,
+            b.thumbnail_url,
+            Metadata::ThumbnailURL(None),
+        ),
+        page_count: merge(a.page_count, b.page_count, Metadata::Page(None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow;
+
+    use super::{sort_book, MetadataSource, ParserRegistry};
+    use crate::models::{book, Metadata, MetadataBook};
+
+    struct Fixture(&'static str, Option<u32>, Option<&'static str>);
+
+    impl MetadataSource for Fixture {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn fetch(&self, _id: u32) -> anyhow::Result<MetadataBook> {
+            Ok(book(self.1, self.2))
+        }
+    }
+
+    #[test]
+    fn fetch_first_source_wins() -> anyhow::Result<()> {
+        let mut registry = ParserRegistry::new();
+        registry.register(Box::new(Fixture("a", None, Some("a"))));
+        registry.register(Box::new(Fixture("b", Some(1), Some("b"))));
+
+        let pd = registry.fetch(1)?;
+
+        assert_eq!(Metadata::ID(Some(1)), pd.id);
+        assert_eq!(Metadata::Title(Some("a".to_string())), pd.title);
+
+        Ok(())
+    }
+
+    #[test]
+    fn record_provenance() -> anyhow::Result<()> {
+        let mut registry = ParserRegistry::new();
+        registry.register(Box::new(Fixture("a", None, Some("a"))));
+        registry.register(Box::new(Fixture("b", Some(1), Some("b"))));
+
+        let (_, provenance) = registry.fetch_with_provenance(1)?;
+
+        assert_eq!(Some("b"), provenance.get("id").map(|x| x.as_str()));
+        assert_eq!(Some("a"), provenance.get("title").map(|x| x.as_str()));
+        assert_eq!(None, provenance.get("tags"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn sort_name_lists() -> anyhow::Result<()> {
+        let names = |x: &[&str]| Some(x.iter().map(|x| x.to_string()).collect::<Vec<_>>());
+
+        let mut a = book(Some(1), Some("a"));
+        a.tags = Metadata::Tags(names(&["sole female ♀", "incest", "loli ♀", "incest"]));
+        a.artists = Metadata::Artists(names(&["mizone", "airandou"]));
+
+        let mut b = book(Some(1), Some("a"));
+        b.tags = Metadata::Tags(names(&["loli ♀", "sole female ♀", "incest"]));
+        b.artists = Metadata::Artists(names(&["airandou", "mizone"]));
+
+        let (a, b) = (sort_book(a), sort_book(b));
+
+        assert_eq!(a.tags, b.tags);
+        assert_eq!(a.artists, b.artists);
+        assert_eq!(
+            Metadata::Tags(names(&["incest", "loli ♀", "sole female ♀"])),
+            a.tags
+        );
+        assert_eq!(Metadata::Series(None), a.series);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn from_names() -> anyhow::Result<()> {
+        let registry = ParserRegistry::from_names("js, block")?;
+
+        assert_eq!(vec!["js", "block"], registry.names());
+        assert!(ParserRegistry::from_names("block,xml").is_err());
+        assert!(ParserRegistry::new().fetch(1).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashSet;
+
+use anyhow;
+use log::debug;
+
+use super::NozomiTarget;
+#[cfg(feature = "net")]
+use super::{Nozomi, Parser};
+
+/// Ids of a request, 4KB of nozomi
+const PER_PAGE: usize = 1000;
+
+/// # SeriesListing
+/// Every gallery of a series across the pages of its nozomi, in publication order
+///
+/// The nozomi of a series is newest first like the listing pages of hitomi,
+/// the whole of it is walked before the order is reversed
+pub struct SeriesListing {
+    name: String,
+    language: String,
+    per_page: usize,
+}
+
+impl SeriesListing {
+    /// `touhou project`, the name as hitomi lists it
+    pub fn new(name: impl Into<String>, language: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            language: language.into(),
+            per_page: PER_PAGE,
+        }
+    }
+
+    pub fn with_per_page(mut self, per_page: usize) -> Self {
+        self.per_page = per_page;
+        self
+    }
+
+    pub fn target(&self) -> NozomiTarget {
+        NozomiTarget::Series(self.name.clone())
+    }
+
+    /// Ids of `page`, newest first
+    #[cfg(feature = "net")]
+    pub fn request(&self, page: usize) -> anyhow::Result<Vec<u32>> {
+        Nozomi::new(page, self.per_page, self.language.as_str())
+            .with_target(Some(self.target()))
+            .request()?
+            .parse()
+    }
+
+    /// Oldest first, `fetch_page` takes pages from 1 until a short one
+    ///
+    /// Galleries added while walking push ids to the next page, they are taken once
+    pub fn walk(
+        &self,
+        mut fetch_page: impl FnMut(usize) -> anyhow::Result<Vec<u32>>,
+    ) -> anyhow::Result<Vec<u32>> {
+        let mut seen = HashSet::new();
+        let mut ids = vec![];
+
+        for page in 1.. {
+            let page_ids = fetch_page(page)?;
+            let len = page_ids.len();
+
+            debug!("series `{}`: {} ids of page {}", self.name, len, page);
+
+            let before = ids.len();
+            ids.extend(page_ids.into_iter().filter(|id| seen.insert(*id)));
+
+            // nothing new, the nozomi is shorter than it told
+            if len < self.per_page || ids.len() == before {
+                break;
+            }
+        }
+
+        ids.reverse();
+
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SeriesListing;
+    use crate::parser::NozomiTarget;
+
+    #[test]
+    fn walk_in_publication_order() -> anyhow::Result<()> {
+        let listing = SeriesListing::new("touhou project", "korean").with_per_page(3);
+
+        // a gallery added after the first page pushed 1724100 to the second one
+        let pages = vec![
+            vec![1724122, 1724110, 1724100],
+            vec![1724100, 1723999, 1723500],
+            vec![1722000],
+        ];
+        let mut requested = vec![];
+
+        let ids = listing.walk(|page| {
+            requested.push(page);
+            Ok(pages[page - 1].clone())
+        })?;
+
+        assert_eq!(
+            vec![1722000, 1723500, 1723999, 1724100, 1724110, 1724122],
+            ids
+        );
+        assert_eq!(vec![1, 2, 3], requested);
+        assert_eq!(
+            NozomiTarget::Series("touhou project".to_string()),
+            listing.target()
+        );
+
+        // a full last page ends with an empty one
+        let ids = listing.walk(|page| match page {
+            1 => Ok(vec![3, 2, 1]),
+            _ => Ok(vec![]),
+        })?;
+
+        assert_eq!(vec![1, 2, 3], ids);
+
+        assert!(listing
+            .walk(|_| Err(anyhow::Error::msg("404 Not Found")))
+            .is_err());
+
+        Ok(())
+    }
+}
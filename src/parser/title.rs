@@ -0,0 +1,71 @@
+/// Title of hitomi such as `Tsundere Imouto | 츤데레 여동생`
+///
+/// The original (japanese or romaji) title comes first,
+/// the translated one follows after `|` only if the gallery is translated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Title {
+    /// Translated title, or the only one
+    pub title: String,
+    pub title_original: Option<String>,
+}
+
+impl Title {
+    pub fn parse(text: &str) -> Self {
+        let mut parts = text
+            .splitn(2, '|')
+            .map(|part| part.trim())
+            .filter(|part| !part.is_empty());
+
+        match (parts.next(), parts.next()) {
+            (Some(original), Some(translated)) => Self {
+                title: translated.to_string(),
+                title_original: Some(original.to_string()),
+            },
+            (Some(title), None) => Self {
+                title: title.to_string(),
+                title_original: None,
+            },
+            _ => Self {
+                title: text.trim().to_string(),
+                title_original: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Title;
+
+    #[test]
+    fn parse_translated_title() -> anyhow::Result<()> {
+        let title = Title::parse("Tsundere Imouto | 츤데레 여동생");
+
+        assert_eq!("츤데레 여동생", title.title);
+        assert_eq!(Some("Tsundere Imouto".to_string()), title.title_original);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_single_title() -> anyhow::Result<()> {
+        assert_eq!(
+            Title {
+                title: "COMIC LO 2019-05".to_string(),
+                title_original: None,
+            },
+            Title::parse("COMIC LO 2019-05")
+        );
+
+        // only one side of `|` is there
+        assert_eq!(
+            Title {
+                title: "Tsundere Imouto".to_string(),
+                title_original: None,
+            },
+            Title::parse("Tsundere Imouto | ")
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use anyhow;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Width of the grid `dhash` compares, one more column than bits in a row
+const GRID_WIDTH: usize = 9;
+const GRID_HEIGHT: usize = 8;
+
+/// # CoverHash
+/// Difference hash of a cover, each bit is whether a cell of a 9x8 grayscale grid
+/// is brighter than the one right of it
+///
+/// Re-encoded, resized or decensored covers differ in a few bits, other covers in about half
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CoverHash(pub u64);
+
+impl CoverHash {
+    /// Bits that differ, 0 for the same cover
+    pub fn distance(&self, other: &Self) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+}
+
+/// `dhash` of 8-bit grayscale `luma`, row by row
+///
+/// Each cell of the grid is the mean of the pixels it covers
+pub fn dhash(luma: &[u8], width: usize, height: usize) -> anyhow::Result<CoverHash> {
+    if width == 0 || height == 0 || luma.len() < width * height {
+        return Err(anyhow::Error::msg(format!(
+            "{} bytes aren't a {}x{} image",
+            luma.len(),
+            width,
+            height
+        )));
+    }
+
+    // cells cover at least a pixel, even of a cover smaller than the grid
+    let span = |i: usize, len: usize, cells: usize| {
+        let start = i * len / cells;
+        let end = ((i + 1) * len / cells).max(start + 1).min(len);
+        start..end
+    };
+
+    let cell = |gx: usize, gy: usize| {
+        let (xs, ys) = (span(gx, width, GRID_WIDTH), span(gy, height, GRID_HEIGHT));
+        let pixels = (xs.len() * ys.len()) as u64;
+
+        let mut sum = 0u64;
+        for y in ys {
+            for x in xs.clone() {
+                sum += luma[y * width + x] as u64;
+            }
+        }
+
+        sum / pixels
+    };
+
+    let mut hash = 0u64;
+
+    for gy in 0..GRID_HEIGHT {
+        let row = (0..GRID_WIDTH).map(|gx| cell(gx, gy)).collect::<Vec<_>>();
+
+        for pair in row.windows(2) {
+            hash = (hash << 1) | (pair[0] > pair[1]) as u64;
+        }
+    }
+
+    Ok(CoverHash(hash))
+}
+
+/// `dhash` of a JPEG, PNG, GIF or WebP cover
+#[cfg(feature = "phash")]
+pub fn cover_hash(buf: &[u8]) -> anyhow::Result<CoverHash> {
+    let luma = image::load_from_memory(buf)?.to_luma8();
+    let (width, height) = luma.dimensions();
+
+    dhash(luma.as_raw(), width as usize, height as usize)
+}
+
+/// 16 hex digits, `f0e1d2c3b4a59687`
+impl Display for CoverHash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for CoverHash {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s.trim(), 16)
+            .map(CoverHash)
+            .map_err(|_| anyhow::Error::msg(format!("Can't parse cover hash `{}`", s)))
+    }
+}
+
+impl Serialize for CoverHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for CoverHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dhash, CoverHash};
+
+    /// Horizontal gradient with a bright square, `shift` brightens every pixel
+    fn cover(width: usize, height: usize, shift: u8) -> Vec<u8> {
+        let mut luma = vec![];
+
+        for y in 0..height {
+            for x in 0..width {
+                let square = x > width / 4 && x < width / 2 && y > height / 4 && y < height / 2;
+                let v = if square { 250 } else { (x * 200 / width) as u8 };
+                luma.push(v.saturating_add(shift));
+            }
+        }
+
+        luma
+    }
+
+    #[test]
+    fn near_covers_are_close() -> anyhow::Result<()> {
+        let original = dhash(&cover(360, 500, 0), 360, 500)?;
+        let resized = dhash(&cover(180, 250, 0), 180, 250)?;
+        let brighter = dhash(&cover(360, 500, 5), 360, 500)?;
+
+        let mut mirrored = cover(360, 500, 0);
+        for row in mirrored.chunks_mut(360) {
+            row.reverse();
+        }
+        let mirrored = dhash(&mirrored, 360, 500)?;
+
+        assert!(original.distance(&resized) <= 4);
+        assert!(original.distance(&brighter) <= 4);
+        assert!(original.distance(&mirrored) > 16);
+        assert!(dhash(&[0; 10], 4, 4).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn hash_as_hex() -> anyhow::Result<()> {
+        let hash = CoverHash(0xf0e1d2c3b4a59687);
+
+        assert_eq!("f0e1d2c3b4a59687", hash.to_string());
+        assert_eq!(hash, "f0e1d2c3b4a59687".parse()?);
+        assert_eq!(
+            "\"000000000000000f\"",
+            serde_json::to_string(&CoverHash(15))?
+        );
+        assert_eq!(
+            CoverHash(15),
+            serde_json::from_str::<CoverHash>("\"000000000000000f\"")?
+        );
+        assert_eq!(4, CoverHash(0).distance(&CoverHash(0b1111)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Runs `stage` over what `source` discovers on `workers` threads
+///
+/// At most `capacity` items wait between them, so `source` pauses
+/// while the workers fall behind instead of queueing every id in memory.
+/// Returns how many items `stage` returned true for.
+pub fn bounded<T, I, F>(source: I, capacity: usize, workers: usize, stage: F) -> usize
+where
+    T: Send,
+    I: IntoIterator<Item = T>,
+    F: Fn(T) -> bool + Sync,
+{
+    let (sender, receiver) = mpsc::sync_channel::<T>(capacity);
+    let receiver = Mutex::new(receiver);
+    let count = AtomicUsize::new(0);
+
+    // not on the rayon pool, a blocked source would hold one of its threads
+    thread::scope(|s| {
+        for _ in 0..workers.max(1) {
+            s.spawn(|| loop {
+                // the lock is released before the stage runs
+                let item = receiver.lock().unwrap().recv();
+
+                match item {
+                    Ok(item) => {
+                        if stage(item) {
+                            count.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                    Err(_) => break,
+                }
+            });
+        }
+
+        for item in source {
+            if sender.send(item).is_err() {
+                break;
+            }
+        }
+
+        drop(sender);
+    });
+
+    count.into_inner()
+}
+
+/// # PriorityLane
+/// Items requested on demand, e.g. by `webhook`, taken before what discovery found
+#[derive(Debug)]
+pub struct PriorityLane<T> {
+    items: Mutex<VecDeque<T>>,
+    pushed: Condvar,
+}
+
+impl<T: PartialEq> PriorityLane<T> {
+    pub fn new() -> Self {
+        Self {
+            items: Mutex::new(VecDeque::new()),
+            pushed: Condvar::new(),
+        }
+    }
+
+    /// Returns how many items wait, an item already waiting isn't added again
+    pub fn push(&self, item: T) -> usize {
+        let mut items = self.items.lock().unwrap();
+
+        if !items.contains(&item) {
+            items.push_back(item);
+        }
+
+        self.pushed.notify_all();
+
+        items.len()
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        self.items.lock().unwrap().pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Sleeps for `timeout` unless an item is pushed, returns whether one waits
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let items = self.items.lock().unwrap();
+
+        let (items, _) = self
+            .pushed
+            .wait_timeout_while(items, timeout, |items| items.is_empty())
+            .unwrap();
+
+        !items.is_empty()
+    }
+}
+
+impl<T: PartialEq> Default for PriorityLane<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `source`, but items of `lane` are taken first whenever one is pulled
+pub fn prioritized<'a, T, I>(lane: &'a PriorityLane<T>, source: I) -> impl Iterator<Item = T> + 'a
+where
+    T: PartialEq,
+    I: IntoIterator<Item = T>,
+    I::IntoIter: 'a,
+{
+    let mut source = source.into_iter();
+
+    std::iter::from_fn(move || lane.pop().or_else(|| source.next()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::{bounded, prioritized, PriorityLane};
+
+    #[test]
+    fn source_waits_for_workers() -> anyhow::Result<()> {
+        let produced = AtomicUsize::new(0);
+        let consumed = AtomicUsize::new(0);
+        let max_waiting = AtomicUsize::new(0);
+
+        let source = (0..100).map(|id| {
+            let waiting = produced.fetch_add(1, Ordering::SeqCst) - consumed.load(Ordering::SeqCst);
+            max_waiting.fetch_max(waiting, Ordering::SeqCst);
+            id
+        });
+
+        let count = bounded(source, 2, 2, |id: u32| {
+            thread::sleep(Duration::from_millis(1));
+            consumed.fetch_add(1, Ordering::SeqCst);
+            id % 2 == 0
+        });
+
+        assert_eq!(50, count);
+        assert_eq!(100, consumed.load(Ordering::SeqCst));
+        // in the channel, in the hands of each worker and the one being sent
+        assert!(max_waiting.load(Ordering::SeqCst) <= 2 + 2 + 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn take_priority_lane_first() -> anyhow::Result<()> {
+        let lane = PriorityLane::new();
+
+        assert_eq!(1, lane.push(1744332));
+        assert_eq!(1, lane.push(1744332));
+
+        let mut ids = prioritized(&lane, vec![1, 2, 3]);
+
+        assert_eq!(Some(1744332), ids.next());
+        assert_eq!(Some(1), ids.next());
+
+        lane.push(1724122);
+
+        assert_eq!(vec![1724122, 2, 3], ids.collect::<Vec<_>>());
+        assert!(lane.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn wake_on_push() -> anyhow::Result<()> {
+        let lane = Arc::new(PriorityLane::new());
+
+        assert!(!lane.wait(Duration::from_millis(1)));
+
+        let pushing = {
+            let lane = Arc::clone(&lane);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(10));
+                lane.push(1744332);
+            })
+        };
+
+        let started_at = Instant::now();
+
+        assert!(lane.wait(Duration::from_secs(60)));
+        assert!(started_at.elapsed() < Duration::from_secs(60));
+
+        pushing.join().unwrap();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use anyhow;
+#[cfg(feature = "net")]
+use reqwest;
+use serde_json;
+
+use crate::rate_limit::RetryAfter;
+use crate::validate::is_invalid;
+
+/// What kind of failure it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// 404, the gallery was deleted
+    NotFound,
+    /// 429
+    RateLimited,
+    /// hitomi changed its pages
+    Parse,
+    /// Metadata `validate` rejected
+    Invalid,
+    Timeout,
+    Other,
+}
+
+impl Display for ErrorClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::NotFound => "not_found",
+            Self::RateLimited => "rate_limited",
+            Self::Parse => "parse",
+            Self::Invalid => "invalid",
+            Self::Timeout => "timeout",
+            Self::Other => "other",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for ErrorClass {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let r = match s.trim() {
+            "not_found" => Self::NotFound,
+            "rate_limited" => Self::RateLimited,
+            "parse" => Self::Parse,
+            "invalid" => Self::Invalid,
+            "timeout" => Self::Timeout,
+            "other" => Self::Other,
+            _ => return Err(anyhow::Error::msg(format!("Unknown error class `{}`", s))),
+        };
+
+        Ok(r)
+    }
+}
+
+/// What to do with the gallery that failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// Forget it
+    Skip,
+    /// `fail_store`, synchronized again with `RETRY_FAIL`
+    Retry,
+    /// `fail_store`, and slow down for a while
+    Backoff,
+    /// `quarantine_store`, never retried until someone looks into it
+    Quarantine,
+}
+
+impl Display for Policy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Skip => "skip",
+            Self::Retry => "retry",
+            Self::Backoff => "backoff",
+            Self::Quarantine => "quarantine",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Policy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let r = match s.trim() {
+            "skip" => Self::Skip,
+            "retry" => Self::Retry,
+            "backoff" => Self::Backoff,
+            "quarantine" => Self::Quarantine,
+            _ => return Err(anyhow::Error::msg(format!("Unknown policy `{}`", s))),
+        };
+
+        Ok(r)
+    }
+}
+
+pub fn classify(err: &anyhow::Error) -> ErrorClass {
+    if err
+        .chain()
+        .any(|cause| cause.downcast_ref::<RetryAfter>().is_some())
+    {
+        return ErrorClass::RateLimited;
+    }
+
+    if is_invalid(err) {
+        return ErrorClass::Invalid;
+    }
+
+    #[cfg(feature = "net")]
+    for cause in err.chain() {
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            if err.is_timeout() {
+                return ErrorClass::Timeout;
+            }
+
+            match err.status() {
+                Some(reqwest::StatusCode::NOT_FOUND) => return ErrorClass::NotFound,
+                Some(reqwest::StatusCode::TOO_MANY_REQUESTS) => return ErrorClass::RateLimited,
+                _ => {}
+            }
+        }
+    }
+
+    if err
+        .chain()
+        .any(|cause| cause.downcast_ref::<serde_json::Error>().is_some())
+    {
+        return ErrorClass::Parse;
+    }
+
+    // parsers return the status line or their own message
+    let message = err.to_string();
+
+    if message.contains("404 Not Found") {
+        ErrorClass::NotFound
+    } else if message.contains("429 Too Many Requests") {
+        ErrorClass::RateLimited
+    } else if message.contains("timed out") {
+        ErrorClass::Timeout
+    } else if message.starts_with("error occurs") {
+        ErrorClass::Parse
+    } else {
+        ErrorClass::Other
+    }
+}
+
+/// Policy by error class
+///
+/// `not_found=skip,rate_limited=backoff,parse=quarantine,invalid=quarantine,timeout=retry,other=retry` by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorPolicy {
+    pub not_found: Policy,
+    pub rate_limited: Policy,
+    pub parse: Policy,
+    pub invalid: Policy,
+    pub timeout: Policy,
+    pub other: Policy,
+}
+
+impl Default for ErrorPolicy {
+    fn default() -> Self {
+        Self {
+            not_found: Policy::Skip,
+            rate_limited: Policy::Backoff,
+            parse: Policy::Quarantine,
+            invalid: Policy::Quarantine,
+            timeout: Policy::Retry,
+            other: Policy::Retry,
+        }
+    }
+}
+
+impl ErrorPolicy {
+    pub fn get(&self, class: ErrorClass) -> Policy {
+        match class {
+            ErrorClass::NotFound => self.not_found,
+            ErrorClass::RateLimited => self.rate_limited,
+            ErrorClass::Parse => self.parse,
+            ErrorClass::Invalid => self.invalid,
+            ErrorClass::Timeout => self.timeout,
+            ErrorClass::Other => self.other,
+        }
+    }
+
+    pub fn policy(&self, err: &anyhow::Error) -> Policy {
+        self.get(classify(err))
+    }
+}
+
+/// `parse=retry,not_found=quarantine`, overrides only given classes
+impl FromStr for ErrorPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut error_policy = Self::default();
+
+        for pair in s.split(',').filter(|pair| !pair.trim().is_empty()) {
+            let mut pair = pair.splitn(2, '=');
+
+            let class = pair.next().unwrap_or("").parse::<ErrorClass>()?;
+            let policy = pair
+                .next()
+                .ok_or_else(|| anyhow::Error::msg(format!("Policy of `{}` is missing", class)))?
+                .parse::<Policy>()?;
+
+            match class {
+                ErrorClass::NotFound => error_policy.not_found = policy,
+                ErrorClass::RateLimited => error_policy.rate_limited = policy,
+                ErrorClass::Parse => error_policy.parse = policy,
+                ErrorClass::Invalid => error_policy.invalid = policy,
+                ErrorClass::Timeout => error_policy.timeout = policy,
+                ErrorClass::Other => error_policy.other = policy,
+            }
+        }
+
+        Ok(error_policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{classify, ErrorClass, ErrorPolicy, Policy};
+    use crate::rate_limit::{rate_limited_host, retry_after, RetryAfter};
+    use crate::validate::{Invalid, Violation};
+
+    #[test]
+    fn classify_errors() -> anyhow::Result<()> {
+        let not_found = anyhow::Error::msg("404 Not Found");
+        let rate_limited = anyhow::Error::msg("429 Too Many Requests");
+        let parse = anyhow::Error::msg(
+            "error occurs `select(gallery_block).next()` in parser::GalleryBlock::parse()",
+        );
+        let json = anyhow::Error::from(serde_json::from_str::<u32>("{").unwrap_err());
+        let other = anyhow::Error::msg("503 Service Unavailable");
+
+        assert_eq!(ErrorClass::NotFound, classify(&not_found));
+        assert_eq!(ErrorClass::RateLimited, classify(&rate_limited));
+        assert_eq!(ErrorClass::Parse, classify(&parse));
+        assert_eq!(ErrorClass::Parse, classify(&json));
+        assert_eq!(ErrorClass::Other, classify(&other));
+
+        let invalid = anyhow::Error::from(Invalid {
+            id: 1724122,
+            violations: vec![Violation::MissingTitle],
+        });
+
+        assert_eq!(ErrorClass::Invalid, classify(&invalid));
+
+        let wait = Some(Duration::from_secs(30));
+        let retry = anyhow::Error::from(RetryAfter {
+            wait,
+            host: Some("ltn.hitomi.la".to_string()),
+        })
+        .context("Can't parse gallery");
+
+        assert_eq!(ErrorClass::RateLimited, classify(&retry));
+        assert_eq!(wait, retry_after(&retry));
+        assert_eq!(Some("ltn.hitomi.la".to_string()), rate_limited_host(&retry));
+        assert_eq!(None, retry_after(&rate_limited));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_error_policy() -> anyhow::Result<()> {
+        let error_policy = "parse=retry, not_found=quarantine".parse::<ErrorPolicy>()?;
+
+        assert_eq!(Policy::Retry, error_policy.parse);
+        assert_eq!(Policy::Quarantine, error_policy.not_found);
+        assert_eq!(Policy::Backoff, error_policy.rate_limited);
+
+        assert!("parse=ignore".parse::<ErrorPolicy>().is_err());
+        assert!("parse".parse::<ErrorPolicy>().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow;
+
+/// Image a hook runs on, after it is downloaded into storage
+#[derive(Debug, Clone, Copy)]
+pub struct ImageInfo<'a> {
+    pub id: u32,
+    /// `1.jpg`
+    pub filename: &'a str,
+    pub source_url: &'a str,
+    pub is_thumbnail: bool,
+}
+
+/// Step run on each downloaded image before it is uploaded,
+/// e.g. deduplication hashing or NSFW classification
+///
+/// It may rewrite the file, it is hashed again afterward
+pub trait ImageHook: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn process(&self, path: &Path, image: &ImageInfo) -> anyhow::Result<()>;
+}
+
+/// # ImageHooks
+/// Hooks in order of registration
+pub struct ImageHooks {
+    hooks: Vec<Box<dyn ImageHook>>,
+}
+
+impl ImageHooks {
+    pub fn new() -> Self {
+        Self { hooks: vec![] }
+    }
+
+    /// Comma separated hook names, `exif`
+    pub fn from_names(names: &str) -> anyhow::Result<Self> {
+        let mut hooks = Self::new();
+
+        for name in names.split(',').map(|name| name.trim()) {
+            let hook: Box<dyn ImageHook> = match name {
+                "" => continue,
+                "exif" => Box::new(StripExif),
+                _ => return Err(anyhow::Error::msg(format!("Unknown image hook `{}`", name))),
+            };
+
+            hooks.register(hook);
+        }
+
+        Ok(hooks)
+    }
+
+    pub fn register(&mut self, hook: Box<dyn ImageHook>) {
+        self.hooks.push(hook);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    pub fn run(&self, path: &Path, image: &ImageInfo) -> anyhow::Result<()> {
+        for hook in &self.hooks {
+            hook.process(path, image).map_err(|err| {
+                anyhow::Error::msg(format!("Image hook {} failed: {}", hook.name(), err))
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes EXIF of JPEG and WebP, GPS and camera of whoever scanned it
+pub struct StripExif;
+
+impl ImageHook for StripExif {
+    fn name(&self) -> &'static str {
+        "exif"
+    }
+
+    fn process(&self, path: &Path, _image: &ImageInfo) -> anyhow::Result<()> {
+        let buf = fs::read(path)?;
+
+        if let Some(stripped) = strip_exif(&buf) {
+            fs::write(path, stripped)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// `None` if it has no EXIF or isn't JPEG or WebP
+pub fn strip_exif(buf: &[u8]) -> Option<Vec<u8>> {
+    strip_jpeg(buf).or_else(|| strip_webp(buf))
+}
+
+/// Drops APP1 segments of `Exif\0\0`
+fn strip_jpeg(buf: &[u8]) -> Option<Vec<u8>> {
+    if !buf.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+
+    let mut r = vec![0xFF, 0xD8];
+    let mut i = 2;
+    let mut stripped = false;
+
+    while i + 4 <= buf.len() {
+        if buf[i] != 0xFF {
+            return None;
+        }
+
+        let marker = buf[i + 1];
+
+        // fill byte
+        if marker == 0xFF {
+            i += 1;
+            continue;
+        }
+
+        // start of scan, the image data follows until the end
+        if marker == 0xDA {
+            break;
+        }
+
+        let len = u16::from_be_bytes([buf[i + 2], buf[i + 3]]) as usize;
+        let end = i + 2 + len;
+
+        if len < 2 || end > buf.len() {
+            return None;
+        }
+
+        if marker == 0xE1 && buf[i + 4..end].starts_with(b"Exif\0\0") {
+            stripped = true;
+        } else {
+            r.extend_from_slice(&buf[i..end]);
+        }
+
+        i = end;
+    }
+
+    if !stripped {
+        return None;
+    }
+
+    r.extend_from_slice(&buf[i..]);
+
+    Some(r)
+}
+
+/// Drops the `EXIF` chunk and its flag of `VP8X`
+fn strip_webp(buf: &[u8]) -> Option<Vec<u8>> {
+    if buf.len() < 12 || &buf[0..4] != b"RIFF" || &buf[8..12] != b"WEBP" {
+        return None;
+    }
+
+    let mut r = buf[..12].to_vec();
+    let mut i = 12;
+    let mut stripped = false;
+
+    while i + 8 <= buf.len() {
+        let size = u32::from_le_bytes([buf[i + 4], buf[i + 5], buf[i + 6], buf[i + 7]]) as usize;
+
+        if i + 8 + size > buf.len() {
+            return None;
+        }
+
+        // chunks are padded to even size
+        let end = (i + 8 + size + (size & 1)).min(buf.len());
+
+        if &buf[i..i + 4] == b"EXIF" {
+            stripped = true;
+        } else {
+            r.extend_from_slice(&buf[i..end]);
+        }
+
+        i = end;
+    }
+
+    if !stripped {
+        return None;
+    }
+
+    if r.len() > 20 && &r[12..16] == b"VP8X" {
+        r[20] &= !0x08;
+    }
+
+    let riff_size = (r.len() - 8) as u32;
+    r[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+    Some(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{strip_exif, ImageHooks};
+
+    #[test]
+    fn strip_exif_of_jpeg() -> anyhow::Result<()> {
+        let app0 = [0xFF, 0xE0, 0x00, 0x07, b'J', b'F', b'I', b'F', 0x00];
+        let app1 = [
+            0xFF, 0xE1, 0x00, 0x0A, b'E', b'x', b'i', b'f', 0x00, 0x00, 0x12, 0x34,
+        ];
+        let scan = [0xFF, 0xDA, 0x00, 0x04, 0x01, 0x02, 0xAB, 0xCD, 0xFF, 0xD9];
+
+        let jpeg = [&[0xFF, 0xD8][..], &app0[..], &app1[..], &scan[..]].concat();
+        let expected = [&[0xFF, 0xD8][..], &app0[..], &scan[..]].concat();
+
+        assert_eq!(Some(expected.clone()), strip_exif(&jpeg));
+        assert_eq!(None, strip_exif(&expected));
+
+        Ok(())
+    }
+
+    #[test]
+    fn strip_exif_of_webp() -> anyhow::Result<()> {
+        let vp8x = [
+            &b"VP8X"[..],
+            &10u32.to_le_bytes()[..],
+            &[0x08, 0, 0, 0, 0, 0, 0, 0, 0, 0][..],
+        ]
+        .concat();
+        let vp8 = [&b"VP8 "[..], &4u32.to_le_bytes()[..], &[1, 2, 3, 4][..]].concat();
+        let exif = [&b"EXIF"[..], &3u32.to_le_bytes()[..], &[1, 2, 3, 0][..]].concat();
+
+        let webp = |chunks: &[&[u8]]| {
+            let body = chunks.concat();
+            [
+                &b"RIFF"[..],
+                &((body.len() + 4) as u32).to_le_bytes()[..],
+                &b"WEBP"[..],
+                &body[..],
+            ]
+            .concat()
+        };
+
+        let stripped = strip_exif(&webp(&[&vp8x, &vp8, &exif])).unwrap();
+
+        let mut vp8x_without_flag = vp8x.clone();
+        vp8x_without_flag[8] = 0;
+
+        assert_eq!(webp(&[&vp8x_without_flag, &vp8]), stripped);
+        assert_eq!(None, strip_exif(&stripped));
+        assert_eq!(None, strip_exif(b"not an image"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn hooks_from_names() -> anyhow::Result<()> {
+        assert!(ImageHooks::from_names("")?.is_empty());
+        assert!(!ImageHooks::from_names("exif")?.is_empty());
+        assert!(ImageHooks::from_names("exif,nsfw").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Throughput is measured over this much recent time
+const WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// Back to the start of the line and erased, a log line after it doesn't run into it
+pub const CLEAR_LINE: &'static str = "\r\x1b[K";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Count {
+    galleries: u64,
+    bytes: u64,
+    ids: u64,
+}
+
+struct Inner {
+    current: Count,
+    /// (when, count at the time)
+    samples: VecDeque<(Instant, Count)>,
+    total_ids: Option<u64>,
+    /// Requests are held back by `Retry-After` until then
+    paused_until: Option<Instant>,
+}
+
+/// # Progress
+/// Throughput of recent `WINDOW` and ETA of a synchronize run
+pub struct Progress {
+    inner: Mutex<Inner>,
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        let mut samples = VecDeque::new();
+        samples.push_back((Instant::now(), Count::default()));
+
+        Self {
+            inner: Mutex::new(Inner {
+                current: Count::default(),
+                samples,
+                total_ids: None,
+                paused_until: None,
+            }),
+        }
+    }
+
+    pub fn add_gallery(&self) {
+        self.inner.lock().unwrap().current.galleries += 1;
+    }
+
+    /// Galleries synchronized so far
+    pub fn galleries(&self) -> u64 {
+        self.inner.lock().unwrap().current.galleries
+    }
+
+    pub fn add_bytes(&self, bytes: usize) {
+        self.inner.lock().unwrap().current.bytes += bytes as u64;
+    }
+
+    /// Ids of the index went through, whether already synchronized or not
+    pub fn add_ids(&self, ids: usize) {
+        self.inner.lock().unwrap().current.ids += ids as u64;
+    }
+
+    /// Ids before the page a run resumes from, done but not part of the throughput
+    pub fn skip_ids(&self, ids: usize) {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.current.ids += ids as u64;
+
+        for (_, count) in inner.samples.iter_mut() {
+            count.ids += ids as u64;
+        }
+    }
+
+    pub fn pause(&self, wait: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        let until = Instant::now() + wait;
+
+        if inner
+            .paused_until
+            .map_or(true, |paused_until| until > paused_until)
+        {
+            inner.paused_until = Some(until);
+        }
+    }
+
+    pub fn set_total_ids(&self, total_ids: usize) {
+        self.inner.lock().unwrap().total_ids = Some(total_ids as u64);
+    }
+
+    /// Takes a sample and returns the throughput since the oldest sample in `WINDOW`
+    pub fn snapshot(&self) -> Snapshot {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+
+        let current = inner.current;
+        inner.samples.push_back((now, current));
+
+        while inner.samples.len() > 2 && now.duration_since(inner.samples[0].0) > WINDOW {
+            inner.samples.pop_front();
+        }
+
+        let (since, old) = inner.samples[0];
+
+        let snapshot = Snapshot::new(
+            now.duration_since(since),
+            current.galleries - old.galleries,
+            current.bytes - old.bytes,
+            current.ids - old.ids,
+            current.ids,
+            inner.total_ids,
+        );
+
+        Snapshot {
+            paused_for: inner
+                .paused_until
+                .and_then(|until| until.checked_duration_since(now)),
+            ..snapshot
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub galleries_per_minute: f64,
+    pub megabytes_per_second: f64,
+    pub done_ids: u64,
+    pub total_ids: Option<u64>,
+    pub eta: Option<Duration>,
+    /// Left of `Retry-After`
+    pub paused_for: Option<Duration>,
+}
+
+impl Snapshot {
+    fn new(
+        elapsed: Duration,
+        galleries: u64,
+        bytes: u64,
+        ids: u64,
+        done_ids: u64,
+        total_ids: Option<u64>,
+    ) -> Self {
+        let secs = elapsed.as_secs_f64();
+
+        let per_second = |x: u64| if secs > 0.0 { x as f64 / secs } else { 0.0 };
+
+        let ids_per_second = per_second(ids);
+
+        let eta = total_ids.and_then(|total_ids| {
+            if ids_per_second > 0.0 {
+                let remaining = total_ids.saturating_sub(done_ids) as f64;
+                Some(Duration::from_secs((remaining / ids_per_second) as u64))
+            } else {
+                None
+            }
+        });
+
+        Self {
+            galleries_per_minute: per_second(galleries) * 60.0,
+            megabytes_per_second: per_second(bytes) / 1024.0 / 1024.0,
+            done_ids,
+            total_ids,
+            eta,
+            paused_for: None,
+        }
+    }
+}
+
+/// Progress line of a terminal, drawn over the last one
+pub fn draw_line(w: &mut impl Write, snapshot: &Snapshot) -> io::Result<()> {
+    write!(w, "{}{}", CLEAR_LINE, snapshot)?;
+    w.flush()
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+
+    let (h, m, s) = (secs / 3600, secs % 3600 / 60, secs % 60);
+
+    if h > 0 {
+        format!("{}h {}m", h, m)
+    } else if m > 0 {
+        format!("{}m {}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+impl Display for Snapshot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} galleries/min, {:.2} MB/s",
+            self.galleries_per_minute, self.megabytes_per_second
+        )?;
+
+        if let Some(total_ids) = self.total_ids {
+            write!(f, ", {} / {} ids", self.done_ids, total_ids)?;
+        }
+
+        match self.eta {
+            Some(eta) => write!(f, ", ETA {}", format_duration(eta))?,
+            None => write!(f, ", ETA unknown")?,
+        }
+
+        match self.paused_for {
+            Some(paused_for) => write!(f, ", rate limited for {}", format_duration(paused_for)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{draw_line, format_duration, Progress, Snapshot};
+
+    #[test]
+    fn snapshot_throughput_and_eta() -> anyhow::Result<()> {
+        let snapshot = Snapshot::new(
+            Duration::from_secs(60),
+            30,
+            60 * 1024 * 1024,
+            600,
+            1000,
+            Some(7000),
+        );
+
+        assert_eq!(30.0, snapshot.galleries_per_minute);
+        assert_eq!(1.0, snapshot.megabytes_per_second);
+        assert_eq!(Some(Duration::from_secs(600)), snapshot.eta);
+        assert_eq!(
+            "30.0 galleries/min, 1.00 MB/s, 1000 / 7000 ids, ETA 10m 0s",
+            snapshot.to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_without_total() -> anyhow::Result<()> {
+        let snapshot = Snapshot::new(Duration::from_secs(0), 0, 0, 0, 0, None);
+
+        assert_eq!(None, snapshot.eta);
+        assert_eq!(
+            "0.0 galleries/min, 0.00 MB/s, ETA unknown",
+            snapshot.to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_rate_limited() -> anyhow::Result<()> {
+        let snapshot = Snapshot {
+            paused_for: Some(Duration::from_secs(90)),
+            ..Snapshot::new(Duration::from_secs(0), 0, 0, 0, 0, None)
+        };
+
+        assert_eq!(
+            "0.0 galleries/min, 0.00 MB/s, ETA unknown, rate limited for 1m 30s",
+            snapshot.to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn resume_from_checkpoint() -> anyhow::Result<()> {
+        let progress = Progress::new();
+        progress.set_total_ids(7000);
+
+        // resumed from page 5 of 250 ids
+        progress.skip_ids(1000);
+        progress.add_ids(250);
+
+        let snapshot = progress.snapshot();
+
+        assert_eq!(1250, snapshot.done_ids);
+        assert!(snapshot.to_string().contains(", 1250 / 7000 ids"));
+
+        let mut line = vec![];
+        draw_line(&mut line, &snapshot)?;
+
+        assert_eq!(format!("\r\x1b[K{}", snapshot), String::from_utf8(line)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn format_durations() -> anyhow::Result<()> {
+        assert_eq!("59s", format_duration(Duration::from_secs(59)));
+        assert_eq!("1m 1s", format_duration(Duration::from_secs(61)));
+        assert_eq!(
+            "3h 21m",
+            format_duration(Duration::from_secs(3 * 3600 + 21 * 60))
+        );
+
+        Ok(())
+    }
+}
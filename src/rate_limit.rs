@@ -0,0 +1,211 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+/// 429 of a host, with its `Retry-After` if any
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryAfter {
+    pub wait: Option<Duration>,
+    /// `ltn.hitomi.la`, `None` if the response isn't at hand
+    pub host: Option<String>,
+}
+
+impl Display for RetryAfter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "429 Too Many Requests")?;
+
+        match self.wait {
+            Some(wait) => write!(f, ", retry after {}s", wait.as_secs()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Error for RetryAfter {}
+
+/// Wait of a `RetryAfter` in the chain of `err`
+pub fn retry_after(err: &anyhow::Error) -> Option<Duration> {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<RetryAfter>())
+        .and_then(|retry_after| retry_after.wait)
+}
+
+/// Host of a `RetryAfter` in the chain of `err`
+pub fn rate_limited_host(err: &anyhow::Error) -> Option<String> {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<RetryAfter>())
+        .and_then(|retry_after| retry_after.host.clone())
+}
+
+/// `Retry-After` of seconds or of HTTP-date, `Wed, 21 Oct 2015 07:28:00 GMT`
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = PrimitiveDateTime::parse(value.trim_end_matches(" GMT"), "%a, %d %b %Y %H:%M:%S")
+        .ok()?
+        .assume_utc();
+    let secs = (date - OffsetDateTime::now_utc()).whole_seconds();
+
+    Some(Duration::from_secs(secs.max(0) as u64))
+}
+
+/// Spaces out requests to a host shared by every thread
+///
+/// Galleries are synchronized in parallel, so while one gallery downloads its images
+/// another fetches its metadata; each host still gets no more than `per_second` requests.
+pub struct RateLimiter {
+    interval: Option<Duration>,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// No limit when `per_second` is 0
+    pub fn new(per_second: f64) -> Self {
+        let interval = if per_second > 0.0 {
+            Some(Duration::from_secs_f64(1.0 / per_second))
+        } else {
+            None
+        };
+
+        Self {
+            interval,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(0.0)
+    }
+
+    /// Holds every request back for `wait`, even when unlimited
+    pub fn pause(&self, wait: Duration) {
+        let mut next = self.next.lock().unwrap();
+        let until = Instant::now() + wait;
+
+        if until > *next {
+            *next = until;
+        }
+    }
+
+    /// `None` if unlimited
+    pub fn per_second(&self) -> Option<f64> {
+        self.interval.map(|interval| 1.0 / interval.as_secs_f64())
+    }
+
+    /// How long a request made now would wait, by the rate or a pause
+    pub fn waiting(&self) -> Option<Duration> {
+        self.next
+            .lock()
+            .unwrap()
+            .checked_duration_since(Instant::now())
+            .filter(|wait| *wait > Duration::from_secs(0))
+    }
+
+    /// Blocks until the next request is allowed
+    pub fn acquire(&self) {
+        let wait = {
+            let mut next = self.next.lock().unwrap();
+            let now = Instant::now();
+
+            let at = if *next > now { *next } else { now };
+
+            if let Some(interval) = self.interval {
+                *next = at + interval;
+            }
+
+            at - now
+        };
+
+        if wait > Duration::from_secs(0) {
+            thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::{parse_retry_after, RateLimiter};
+
+    #[test]
+    fn space_out_requests() -> anyhow::Result<()> {
+        let rate_limiter = Arc::new(RateLimiter::new(20.0));
+        let started_at = Instant::now();
+
+        let handles = (0..5)
+            .map(|_| {
+                let rate_limiter = Arc::clone(&rate_limiter);
+                thread::spawn(move || rate_limiter.acquire())
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // first one goes right away, the other 4 wait 50ms each
+        assert!(started_at.elapsed() >= Duration::from_millis(200));
+
+        Ok(())
+    }
+
+    #[test]
+    fn pause_unlimited() -> anyhow::Result<()> {
+        let rate_limiter = RateLimiter::unlimited();
+        let started_at = Instant::now();
+
+        assert_eq!(None, rate_limiter.per_second());
+        assert_eq!(None, rate_limiter.waiting());
+
+        rate_limiter.pause(Duration::from_millis(100));
+
+        assert!(rate_limiter.waiting().is_some());
+
+        rate_limiter.acquire();
+
+        assert!(started_at.elapsed() >= Duration::from_millis(100));
+        assert_eq!(None, rate_limiter.waiting());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_retry_after_header() -> anyhow::Result<()> {
+        assert_eq!(Some(Duration::from_secs(120)), parse_retry_after("120"));
+        assert_eq!(
+            Some(Duration::from_secs(0)),
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+        assert!(
+            parse_retry_after("Fri, 31 Dec 2999 23:59:59 GMT").unwrap() > Duration::from_secs(0)
+        );
+        assert_eq!(None, parse_retry_after("soon"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn unlimited() -> anyhow::Result<()> {
+        let rate_limiter = RateLimiter::unlimited();
+        let started_at = Instant::now();
+
+        for _ in 0..100 {
+            rate_limiter.acquire();
+        }
+
+        assert!(started_at.elapsed() < Duration::from_millis(100));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use anyhow;
+use serde_json;
+
+use crate::models::{Metadata, MetadataBook};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Field {
+    Title,
+    Artists,
+    Series,
+    Groups,
+    Characters,
+    Tags,
+}
+
+impl Field {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Artists => "artists",
+            Self::Series => "series",
+            Self::Groups => "groups",
+            Self::Characters => "characters",
+            Self::Tags => "tags",
+        }
+    }
+
+    fn value(&self, metadata_book: &MetadataBook) -> serde_json::Value {
+        let metadata = match self {
+            Self::Title => &metadata_book.title,
+            Self::Artists => &metadata_book.artists,
+            Self::Series => &metadata_book.series,
+            Self::Groups => &metadata_book.groups,
+            Self::Characters => &metadata_book.characters,
+            Self::Tags => &metadata_book.tags,
+        };
+
+        match metadata {
+            Metadata::Title(x) => serde_json::json!(x),
+            Metadata::Artists(x)
+            | Metadata::Series(x)
+            | Metadata::Groups(x)
+            | Metadata::Characters(x)
+            | Metadata::Tags(x) => serde_json::json!(x),
+            _ => serde_json::Value::Null,
+        }
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Field {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let r = match s.trim() {
+            "title" => Self::Title,
+            "artists" => Self::Artists,
+            "series" => Self::Series,
+            "groups" => Self::Groups,
+            "characters" => Self::Characters,
+            "tags" => Self::Tags,
+            _ => return Err(anyhow::Error::msg(format!("Can't resync field `{}`", s))),
+        };
+
+        Ok(r)
+    }
+}
+
+/// `tags,characters`
+pub fn parse_fields(fields: &str) -> anyhow::Result<Vec<Field>> {
+    let mut fields = fields
+        .split(',')
+        .map(|field| field.parse::<Field>())
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    fields.sort();
+    fields.dedup();
+
+    Ok(fields)
+}
+
+/// `{ "tags": [...], "characters": null }`, only with given fields
+pub fn book_patch(metadata_book: &MetadataBook, fields: &[Field]) -> serde_json::Value {
+    let patch = fields
+        .iter()
+        .map(|field| (field.as_str().to_string(), field.value(metadata_book)))
+        .collect::<serde_json::Map<_, _>>();
+
+    serde_json::Value::Object(patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{book_patch, parse_fields, Field};
+    use crate::models::{self, Metadata, MetadataBook};
+
+    #[test]
+    fn parse_resync_fields() -> anyhow::Result<()> {
+        assert_eq!(
+            vec![Field::Characters, Field::Tags],
+            parse_fields("tags, characters,tags")?
+        );
+        assert!(parse_fields("tags,pages").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn patch_only_given_fields() -> anyhow::Result<()> {
+        let metadata_book = MetadataBook {
+            artists: Metadata::Artists(Some(vec!["airandou".to_string()])),
+            tags: Metadata::Tags(Some(vec!["incest".to_string()])),
+            ..models::book(Some(1724122), Some("Tsundere Imouto"))
+        };
+
+        let patch = book_patch(&metadata_book, &[Field::Characters, Field::Tags]);
+
+        assert_eq!(json!({ "characters": null, "tags": ["incest"] }), patch);
+
+        Ok(())
+    }
+}
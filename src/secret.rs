@@ -0,0 +1,47 @@
+use std::sync::RwLock;
+
+/// Shorter ones would redact ordinary words
+const MIN_SECRET_LEN: usize = 8;
+
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Tokens loaded or refreshed, redacted from logs and failure reports from now on
+pub fn register(secret: &str) {
+    let secret = secret.trim();
+
+    if secret.len() < MIN_SECRET_LEN {
+        return;
+    }
+
+    let mut secrets = SECRETS.write().unwrap();
+
+    if !secrets.iter().any(|x| x == secret) {
+        secrets.push(secret.to_string());
+    }
+}
+
+pub fn redact(text: &str) -> String {
+    let secrets = SECRETS.read().unwrap();
+
+    secrets.iter().fold(text.to_string(), |text, secret| {
+        text.replace(secret.as_str(), "[REDACTED]")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{redact, register};
+
+    #[test]
+    fn redact_registered_secrets() -> anyhow::Result<()> {
+        register("eyJhbGciOiJIUzI1NiJ9.secret");
+        register("short");
+
+        assert_eq!(
+            "401 Unauthorized with [REDACTED], short",
+            redact("401 Unauthorized with eyJhbGciOiJIUzI1NiJ9.secret, short")
+        );
+
+        Ok(())
+    }
+}
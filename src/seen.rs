@@ -0,0 +1,90 @@
+use std::path::Path;
+
+use anyhow;
+use sled;
+
+/// # SeenSet
+/// Ids discovery has handed to the workers, kept in a sled database
+///
+/// Full-index and multi-language runs see millions of ids,
+/// only the pages sled caches stay in memory
+pub struct SeenSet {
+    db: sled::Db,
+}
+
+impl SeenSet {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let db = sled::open(path)?;
+
+        Ok(Self { db })
+    }
+
+    /// Big endian keys, sled iterates them in order of id
+    fn key(id: u32) -> [u8; 4] {
+        id.to_be_bytes()
+    }
+
+    pub fn contains(&self, id: u32) -> anyhow::Result<bool> {
+        Ok(self.db.contains_key(Self::key(id))?)
+    }
+
+    /// True if it wasn't seen before
+    pub fn insert(&self, id: u32) -> anyhow::Result<bool> {
+        Ok(self.db.insert(Self::key(id), &[])?.is_none())
+    }
+
+    pub fn remove(&self, id: u32) -> anyhow::Result<bool> {
+        Ok(self.db.remove(Self::key(id))?.is_some())
+    }
+
+    pub fn len(&self) -> usize {
+        self.db.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.db.is_empty()
+    }
+
+    /// sled flushes in the background too, this is for the end of a page
+    pub fn flush(&self) -> anyhow::Result<()> {
+        self.db.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::SeenSet;
+
+    #[test]
+    fn seen_ids_are_kept_on_disk() -> anyhow::Result<()> {
+        let path = env::temp_dir().join("madome_synchronizer_seen");
+        let _ = fs::remove_dir_all(&path);
+
+        {
+            let seen = SeenSet::open(&path)?;
+
+            assert!(seen.is_empty());
+            assert!(seen.insert(1724122)?);
+            assert!(!seen.insert(1724122)?);
+            assert!(seen.insert(1)?);
+            assert!(seen.remove(1)?);
+
+            seen.flush()?;
+        }
+
+        let seen = SeenSet::open(&path)?;
+
+        assert!(seen.contains(1724122)?);
+        assert!(!seen.contains(1)?);
+        assert_eq!(1, seen.len());
+
+        fs::remove_dir_all(&path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, Instant};
+
+use anyhow;
+
+use crate::models::Metadata;
+use crate::parser::{GalleryInfo, Nozomi, Parser, ParserRegistry};
+use crate::rate_limit::RateLimiter;
+use crate::urls;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Pass(String),
+    Fail(String),
+    /// An earlier check failed, nothing to check with
+    Skip,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    pub elapsed: Duration,
+    pub outcome: Outcome,
+}
+
+/// # SelfTest
+/// One request of each kind a synchronize makes, with the client and rate limits it uses
+///
+/// A nozomi page, the metadata of its first gallery and a `HEAD` of its first image
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfTest {
+    pub checks: Vec<Check>,
+}
+
+impl SelfTest {
+    pub fn run(language: &str, registry: &ParserRegistry, image_limit: &RateLimiter) -> Self {
+        let mut checks = vec![];
+
+        let id = check(&mut checks, "nozomi", || {
+            let nozomi = Nozomi::new(1, 25, language).request()?;
+            let ids = nozomi.parse()?;
+
+            let id = *ids
+                .first()
+                .ok_or_else(|| anyhow::Error::msg("empty page"))?;
+            let total = nozomi
+                .total_ids()
+                .map_or("?".to_string(), |x| x.to_string());
+
+            Ok((id, format!("{} ids of {}, {}", ids.len(), total, language)))
+        });
+
+        let images = match id {
+            Some(id) => check(&mut checks, "metadata", || {
+                let files = GalleryInfo::new(id).request()?.parse()?.files;
+                let book = registry.fetch(id)?;
+                let title = match book.title {
+                    Metadata::Title(Some(title)) => title,
+                    _ => return Err(anyhow::Error::msg(format!("{} has no title", id))),
+                };
+
+                let detail = format!("{} `{}`, {} pages", id, title, files.len());
+
+                Ok(((id, files), detail))
+            }),
+            None => skip(&mut checks, "metadata"),
+        };
+
+        match images {
+            Some((id, files)) => {
+                check(&mut checks, "image", || {
+                    let file = files
+                        .first()
+                        .ok_or_else(|| anyhow::Error::msg(format!("{} has no page", id)))?;
+                    let (image_url, _) = file.url(id)?;
+
+                    image_limit.acquire();
+                    let response = crate::client::shared()?
+                        .head(&image_url)
+                        .header("Referer", urls::reader(id))
+                        .send()?;
+
+                    if !response.status().is_success() {
+                        return Err(anyhow::Error::msg(response.status().to_string()));
+                    }
+
+                    Ok(((), format!("{} {}", response.status(), image_url)))
+                });
+            }
+            None => {
+                skip::<()>(&mut checks, "image");
+            }
+        }
+
+        Self { checks }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| match check.outcome {
+            Outcome::Pass(_) => true,
+            _ => false,
+        })
+    }
+}
+
+/// Runs `f` as the check `name`, `None` if it failed
+fn check<T>(
+    checks: &mut Vec<Check>,
+    name: &'static str,
+    f: impl FnOnce() -> anyhow::Result<(T, String)>,
+) -> Option<T> {
+    let started_at = Instant::now();
+    let r = f();
+    let elapsed = started_at.elapsed();
+
+    let (r, outcome) = match r {
+        Ok((x, detail)) => (Some(x), Outcome::Pass(detail)),
+        Err(err) => (None, Outcome::Fail(err.to_string())),
+    };
+
+    checks.push(Check {
+        name,
+        elapsed,
+        outcome,
+    });
+
+    r
+}
+
+fn skip<T>(checks: &mut Vec<Check>, name: &'static str) -> Option<T> {
+    checks.push(Check {
+        name,
+        elapsed: Duration::from_secs(0),
+        outcome: Outcome::Skip,
+    });
+
+    None
+}
+
+/// `nozomi    PASS  0.31s  25 ids of 2147940, korean`
+impl Display for SelfTest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let (state, detail) = match &check.outcome {
+                Outcome::Pass(detail) => ("PASS", detail.as_str()),
+                Outcome::Fail(err) => ("FAIL", err.as_str()),
+                Outcome::Skip => ("SKIP", ""),
+            };
+
+            writeln!(
+                f,
+                "{:<9} {}  {:.2}s  {}",
+                check.name,
+                state,
+                check.elapsed.as_secs_f64(),
+                detail
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Check, Outcome, SelfTest};
+
+    #[test]
+    fn print_matrix() -> anyhow::Result<()> {
+        let check = |name, outcome| Check {
+            name,
+            elapsed: Duration::from_millis(310),
+            outcome,
+        };
+
+        let selftest = SelfTest {
+            checks: vec![
+                check(
+                    "nozomi",
+                    Outcome::Pass("25 ids of 2147940, korean".to_string()),
+                ),
+                check("metadata", Outcome::Fail("404 Not Found".to_string())),
+                check("image", Outcome::Skip),
+            ],
+        };
+
+        assert!(!selftest.passed());
+        assert_eq!(
+            "nozomi    PASS  0.31s  25 ids of 2147940, korean\n\
+             metadata  FAIL  0.31s  404 Not Found\n\
+             image     SKIP  0.31s  \n",
+            selftest.to_string()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::str::FromStr;
+
+use anyhow;
+
+/// # Shard
+/// `2/8`, the 2nd of 8 synchronizers sharing the nozomi index by id modulo
+///
+/// Every synchronizer has to be given the same count
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shard {
+    /// Starts from 1
+    pub index: u32,
+    pub count: u32,
+}
+
+impl Shard {
+    pub fn contains(&self, id: u32) -> bool {
+        id % self.count == self.index - 1
+    }
+
+    pub fn checkpoint_path(&self) -> String {
+        format!("./checkpoint.{}-{}.txt", self.index, self.count)
+    }
+
+    /// `./checkpoint.{name}.{index}-{count}.txt`
+    pub fn named_checkpoint_path(&self, name: &str) -> String {
+        format!("./checkpoint.{}.{}-{}.txt", name, self.index, self.count)
+    }
+}
+
+impl Display for Shard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+impl FromStr for Shard {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || anyhow::Error::msg(format!("Can't parse shard from `{}`, e.g. 2/8", s));
+
+        let mut it = s.trim().splitn(2, '/');
+
+        let index = it
+            .next()
+            .and_then(|x| x.parse::<u32>().ok())
+            .ok_or_else(err)?;
+        let count = it
+            .next()
+            .and_then(|x| x.parse::<u32>().ok())
+            .ok_or_else(err)?;
+
+        if index == 0 || index > count {
+            return Err(err());
+        }
+
+        Ok(Self { index, count })
+    }
+}
+
+/// Page to resume from, saved after each page of nozomi
+pub struct Checkpoint {
+    path: String,
+}
+
+impl Checkpoint {
+    pub fn new(shard: Option<Shard>) -> Self {
+        let path = shard
+            .map(|shard| shard.checkpoint_path())
+            .unwrap_or("./checkpoint.txt".to_string());
+
+        Self { path }
+    }
+
+    /// Crawls of an artist or series resume apart from the language, `./checkpoint.{name}.txt`
+    pub fn named(shard: Option<Shard>, name: &str) -> Self {
+        let path = shard
+            .map(|shard| shard.named_checkpoint_path(name))
+            .unwrap_or(format!("./checkpoint.{}.txt", name));
+
+        Self { path }
+    }
+
+    pub fn load(&self) -> Option<usize> {
+        fs::read_to_string(&self.path).ok()?.trim().parse().ok()
+    }
+
+    pub fn save(&self, page: usize) -> std::io::Result<()> {
+        fs::write(&self.path, page.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Shard;
+
+    #[test]
+    fn parse_shard() -> anyhow::Result<()> {
+        assert_eq!(Shard { index: 2, count: 8 }, "2/8".parse::<Shard>()?);
+        assert!("0/8".parse::<Shard>().is_err());
+        assert!("9/8".parse::<Shard>().is_err());
+        assert!("2".parse::<Shard>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn shards_partition_ids() -> anyhow::Result<()> {
+        let shards = (1..=3)
+            .map(|index| Shard { index, count: 3 })
+            .collect::<Vec<_>>();
+
+        for id in 1700000..1700100 {
+            let owners = shards.iter().filter(|shard| shard.contains(id)).count();
+
+            assert_eq!(1, owners);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+use std::fs::File;
+use std::path::Path;
+
+use anyhow;
+use reqwest;
+use serde::Deserialize;
+use serde_json;
+
+use crate::catalog::TombstoneAction;
+use crate::client;
+use crate::manifest::Checksum;
+use crate::token::TokenStore;
+
+/// What the file repository has of the files of a gallery, hashed on its side
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Acknowledgement {
+    pub files: Vec<Checksum>,
+}
+
+/// # Sink
+/// Requests to the Madome API that `madome_client` doesn't have yet
+///
+/// Their methods, paths and bodies are pinned by the tests against `mock::MockServer`
+pub struct Sink {
+    url: String,
+    client: &'static reqwest::blocking::Client,
+}
+
+impl Sink {
+    pub fn new(url: impl Into<String>) -> anyhow::Result<Self> {
+        Ok(Self {
+            url: url.into(),
+            client: client::shared()?,
+        })
+    }
+
+    /// Overwrites only given fields of a book
+    pub fn patch_book(
+        &self,
+        token: &TokenStore,
+        id: u32,
+        patch: &serde_json::Value,
+    ) -> anyhow::Result<()> {
+        token.with_token(|token| {
+            let response = self
+                .client
+                .patch(&format!("{}/v1/book/{}", self.url, id))
+                .header("Authorization", token.as_str())
+                .json(patch)
+                .send()?;
+
+            client::check_rate_limit(response)?.error_for_status()?;
+
+            Ok(())
+        })
+    }
+
+    /// Uploads a file as `url_path` of the file repository, read from disk while it is sent
+    ///
+    /// `FileClient::upload` of `madome_client` takes the whole file in a buffer
+    pub fn upload_file(
+        &self,
+        token: &TokenStore,
+        url_path: &str,
+        path: impl AsRef<Path>,
+    ) -> anyhow::Result<()> {
+        token.with_token(|token| {
+            // opened again on a retry, the body of a failed request is consumed
+            let file = File::open(path.as_ref())?;
+            let len = file.metadata()?.len();
+
+            let response = self
+                .client
+                .post(&format!("{}/{}", self.url, url_path))
+                .header("Authorization", token.as_str())
+                .body(reqwest::blocking::Body::sized(file, len))
+                .send()?;
+
+            client::check_rate_limit(response)?.error_for_status()?;
+
+            Ok(())
+        })
+    }
+
+    /// Sends checksums of the uploaded files of a gallery to the file repository,
+    /// `Sink` of `FILE_REPOSITORY_URL`
+    pub fn confirm_upload(
+        &self,
+        token: &TokenStore,
+        id: u32,
+        checksums: &[Checksum],
+    ) -> anyhow::Result<Acknowledgement> {
+        token.with_token(|token| {
+            let response = self
+                .client
+                .post(&format!("{}/image/library/{}/checksums", self.url, id))
+                .header("Authorization", token.as_str())
+                .json(&serde_json::json!({ "files": checksums }))
+                .send()?;
+
+            Ok(client::check_rate_limit(response)?
+                .error_for_status()?
+                .json()?)
+        })
+    }
+
+    /// Of a gallery removed from hitomi
+    pub fn tombstone(
+        &self,
+        token: &TokenStore,
+        id: u32,
+        action: TombstoneAction,
+    ) -> anyhow::Result<()> {
+        match action {
+            TombstoneAction::Delete => token.with_token(|token| {
+                let response = self
+                    .client
+                    .delete(&format!("{}/v1/book/{}", self.url, id))
+                    .header("Authorization", token.as_str())
+                    .send()?;
+
+                client::check_rate_limit(response)?.error_for_status()?;
+
+                Ok(())
+            }),
+            TombstoneAction::Hide => {
+                self.patch_book(token, id, &serde_json::json!({ "hidden": true }))
+            }
+            TombstoneAction::Flag => {
+                self.patch_book(token, id, &serde_json::json!({ "removed_upstream": true }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use madome_client::auth::Token;
+    use madome_client::AuthClient;
+
+    use super::Sink;
+    use crate::catalog::TombstoneAction;
+    use crate::manifest::{Checksum, ManifestFile};
+    use crate::mock::MockServer;
+    use crate::token::{TokenSource, TokenStore};
+
+    fn token_store(url: &str) -> TokenStore {
+        TokenStore::new(
+            AuthClient::new(url),
+            TokenSource::File(env::temp_dir().join("madome_synchronizer_sink_token")),
+            Token {
+                token: "token".to_string(),
+            },
+        )
+    }
+
+    #[test]
+    fn tombstone_requests() -> anyhow::Result<()> {
+        let server = MockServer::start(vec![
+            (200, String::new()),
+            (200, String::new()),
+            (200, String::new()),
+            (500, String::new()),
+        ])?;
+        let sink = Sink::new(server.url())?;
+        let token = token_store(server.url());
+
+        sink.tombstone(&token, 1724122, TombstoneAction::Delete)?;
+        sink.tombstone(&token, 1724122, TombstoneAction::Hide)?;
+        sink.tombstone(&token, 1724122, TombstoneAction::Flag)?;
+        assert!(sink
+            .tombstone(&token, 1724122, TombstoneAction::Delete)
+            .is_err());
+
+        let requests = server.requests()?;
+
+        assert_eq!(
+            vec![
+                ("DELETE", "/v1/book/1724122"),
+                ("PATCH", "/v1/book/1724122"),
+                ("PATCH", "/v1/book/1724122"),
+                ("DELETE", "/v1/book/1724122"),
+            ],
+            requests
+                .iter()
+                .map(|x| (x.method.as_str(), x.path.as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert!(requests
+            .iter()
+            .all(|x| x.header("authorization") == Some("token")));
+        assert_eq!(serde_json::json!({ "hidden": true }), requests[1].json()?);
+        assert_eq!(
+            serde_json::json!({ "removed_upstream": true }),
+            requests[2].json()?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn upload_and_confirm_requests() -> anyhow::Result<()> {
+        let file = ManifestFile::new(
+            "1.jpg".to_string(),
+            "image/library/1724122/1.jpg".to_string(),
+            "https://aa.hitomi.la/images/1.jpg".to_string(),
+            b"one",
+        );
+        let acknowledged = serde_json::json!({ "files": [Checksum::from(&file)] }).to_string();
+
+        let server = MockServer::start(vec![(201, String::new()), (200, acknowledged)])?;
+        let sink = Sink::new(server.url())?;
+        let token = token_store(server.url());
+
+        let path = env::temp_dir().join("madome_synchronizer_sink_upload");
+        fs::write(&path, b"one")?;
+
+        sink.upload_file(&token, &file.path, &path)?;
+        let acknowledgement = sink.confirm_upload(&token, 1724122, &[Checksum::from(&file)])?;
+
+        fs::remove_file(&path)?;
+
+        let requests = server.requests()?;
+
+        assert_eq!("POST", requests[0].method);
+        assert_eq!("/image/library/1724122/1.jpg", requests[0].path);
+        assert_eq!(Some("3"), requests[0].header("content-length"));
+        assert_eq!(b"one".to_vec(), requests[0].body);
+
+        assert_eq!("POST", requests[1].method);
+        assert_eq!("/image/library/1724122/checksums", requests[1].path);
+        assert_eq!(
+            serde_json::json!({ "files": [Checksum::from(&file)] }),
+            requests[1].json()?
+        );
+        assert_eq!(vec![Checksum::from(&file)], acknowledgement.files);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use crate::parser::File;
+
+/// Why a gallery was left out of the synchronize on purpose
+///
+/// It is returned as an error so that it stops the stages of the gallery,
+/// but it is not a failure, so it never goes to `fail_store`
+#[derive(Debug, Clone, PartialEq)]
+pub enum SkipReason {
+    /// Anime galleries have a video instead of images
+    Video { url: Option<String> },
+    /// Exceeds `SizeLimit`, deferred to be synchronized manually
+    TooLarge { pages: usize, estimated_bytes: u64 },
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Video { url: Some(url) } => write!(f, "Video gallery ({})", url),
+            Self::Video { url: None } => write!(f, "Video gallery"),
+            Self::TooLarge {
+                pages,
+                estimated_bytes,
+            } => write!(
+                f,
+                "Too large gallery ({} pages, about {} MB)",
+                pages,
+                estimated_bytes / 1024 / 1024
+            ),
+        }
+    }
+}
+
+impl Error for SkipReason {}
+
+pub fn is_skipped(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<SkipReason>().is_some()
+}
+
+pub fn is_deferred(err: &anyhow::Error) -> bool {
+    match err.downcast_ref::<SkipReason>() {
+        Some(SkipReason::TooLarge { .. }) => true,
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SizeLimit {
+    pub max_pages: Option<usize>,
+    pub max_bytes: Option<u64>,
+}
+
+impl SizeLimit {
+    pub fn check(&self, files: &[File]) -> Result<(), SkipReason> {
+        let pages = files.len();
+        let estimated_bytes = files.iter().map(|file| file.estimated_bytes()).sum();
+
+        let too_many_pages = self.max_pages.map_or(false, |max| pages > max);
+        let too_many_bytes = self.max_bytes.map_or(false, |max| estimated_bytes > max);
+
+        if too_many_pages || too_many_bytes {
+            return Err(SkipReason::TooLarge {
+                pages,
+                estimated_bytes,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SizeLimit, SkipReason};
+    use crate::parser::File;
+
+    fn file(width: u32, height: u32) -> File {
+        File {
+            width,
+            height,
+            hash: String::new(),
+            haswebp: None,
+            hasavifsmalltn: None,
+            hasavif: None,
+            name: "1.jpg".to_string(),
+        }
+    }
+
+    #[test]
+    fn size_limit_unlimited() -> anyhow::Result<()> {
+        let files = (0..3000).map(|_| file(1280, 1810)).collect::<Vec<_>>();
+
+        assert_eq!(Ok(()), SizeLimit::default().check(&files));
+
+        Ok(())
+    }
+
+    #[test]
+    fn size_limit_max_pages() -> anyhow::Result<()> {
+        let limit = SizeLimit {
+            max_pages: Some(2),
+            max_bytes: None,
+        };
+
+        assert_eq!(Ok(()), limit.check(&[file(2, 2), file(2, 2)]));
+        assert_eq!(
+            Err(SkipReason::TooLarge {
+                pages: 3,
+                estimated_bytes: 6,
+            }),
+            limit.check(&[file(2, 2), file(2, 2), file(2, 2)])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn size_limit_max_bytes() -> anyhow::Result<()> {
+        let limit = SizeLimit {
+            max_pages: None,
+            max_bytes: Some(1000),
+        };
+
+        assert_eq!(Ok(()), limit.check(&[file(40, 50)]));
+        assert_eq!(
+            Err(SkipReason::TooLarge {
+                pages: 1,
+                estimated_bytes: 1250,
+            }),
+            limit.check(&[file(50, 50)])
+        );
+
+        Ok(())
+    }
+}
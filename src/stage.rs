@@ -1,15 +1,21 @@
 use std::collections::HashMap;
 use std::fmt::{self, Display, Formatter};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use log::{error, info};
 
+#[cfg(feature = "metrics")]
+use crate::dashboard::Dashboard;
+use crate::skip::SkipReason;
+
 pub struct StageUpdater<ID>
 where
     ID: Display,
 {
     id: ID,
     inner: Mutex<HashMap<u8, usize>>,
+    #[cfg(feature = "metrics")]
+    dashboard: Option<Arc<Dashboard>>,
 }
 
 impl<ID> StageUpdater<ID>
@@ -20,9 +26,18 @@ where
         Self {
             id,
             inner: Mutex::new(HashMap::new()),
+            #[cfg(feature = "metrics")]
+            dashboard: None,
         }
     }
 
+    /// Stages in progress are counted on it
+    #[cfg(feature = "metrics")]
+    pub fn with_dashboard(mut self, dashboard: Arc<Dashboard>) -> Self {
+        self.dashboard = Some(dashboard);
+        self
+    }
+
     pub fn update<T, F>(&self, stage: Stage, f: F) -> anyhow::Result<T>
     where
         F: Fn() -> StageR<T>,
@@ -36,6 +51,12 @@ where
             }
         }
 
+        #[cfg(feature = "metrics")]
+        let StageR(state, max_call_count, r) = match &self.dashboard {
+            Some(dashboard) => dashboard.track(stage.as_u8(), &f),
+            None => f(),
+        };
+        #[cfg(not(feature = "metrics"))]
         let StageR(state, max_call_count, r) = f();
 
         let current_call_count: usize = {
@@ -73,7 +94,11 @@ where
                 Ok(r)
             }
             Err(err) => {
-                error!("{}: {}: Error: {:#?}", self.id, stage, err);
+                if let Some(reason) = err.downcast_ref::<SkipReason>() {
+                    info!("{}: {}: Skipped: {}", self.id, stage, reason);
+                } else {
+                    error!("{}: {}: Error: {:#?}", self.id, stage, err);
+                }
                 Err(err)
             }
         }
@@ -117,6 +142,7 @@ pub enum Stage {
     AddImages,
     AddImageList,
     AddBook,
+    AddManifest,
 }
 
 impl PartialEq for Stage {
@@ -135,6 +161,7 @@ impl Display for Stage {
             Self::AddImages => "Add Images",
             Self::AddImageList => "Add Image List",
             Self::AddBook => "Add Book",
+            Self::AddManifest => "Add Manifest",
         };
 
         write!(f, "{}", r)
@@ -150,6 +177,7 @@ impl From<u8> for Stage {
             3 => Self::AddImages,
             4 => Self::AddImageList,
             5 => Self::AddBook,
+            6 => Self::AddManifest,
             _ => panic!("Can't Stage from {}", x),
         }
     }
@@ -164,6 +192,7 @@ impl Stage {
             Self::AddImages => 3,
             Self::AddImageList => 4,
             Self::AddBook => 5,
+            Self::AddManifest => 6,
         }
     }
 }
@@ -0,0 +1,128 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::Serialize;
+
+use crate::catalog::{Catalog, Status};
+
+/// # Stats
+/// What is synchronized of a language, for dashboards and sanity checks
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LanguageStats {
+    pub language: String,
+    /// Ids in the remote index, `None` if it didn't answer `Content-Range`
+    pub remote_total: Option<usize>,
+    pub synced: usize,
+    pub partial: usize,
+    pub imported: usize,
+    /// Remote ids not synced yet
+    pub pending: Option<usize>,
+    pub failures: usize,
+    /// Unix timestamp of the latest synced gallery
+    pub last_synced_at: Option<i64>,
+}
+
+impl LanguageStats {
+    /// Entries without a language count as `unlabeled`,
+    /// catalogs written before the language was recorded
+    pub fn of(
+        language: &str,
+        unlabeled: bool,
+        remote_total: Option<usize>,
+        failures: usize,
+        catalog: &Catalog,
+    ) -> Self {
+        let mut stats = Self {
+            language: language.to_string(),
+            remote_total,
+            synced: 0,
+            partial: 0,
+            imported: 0,
+            pending: None,
+            failures,
+            last_synced_at: None,
+        };
+
+        for (_, entry) in catalog.iter() {
+            let of_language = match &entry.language {
+                Some(x) => x.eq_ignore_ascii_case(language),
+                None => unlabeled,
+            };
+
+            if !of_language {
+                continue;
+            }
+
+            match entry.status {
+                Status::Synced => stats.synced += 1,
+                Status::Partial => stats.partial += 1,
+                Status::Imported => stats.imported += 1,
+            }
+
+            if entry.status != Status::Imported {
+                stats.last_synced_at = stats.last_synced_at.max(Some(entry.updated_at));
+            }
+        }
+
+        stats.pending = remote_total.map(|total| total.saturating_sub(stats.synced));
+
+        stats
+    }
+}
+
+impl Display for LanguageStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let or_unknown = |x: Option<String>| x.unwrap_or_else(|| "?".to_string());
+
+        write!(
+            f,
+            "{}: remote {}, synced {}, partial {}, imported {}, pending {}, failures {}, last synced at {}",
+            self.language,
+            or_unknown(self.remote_total.map(|x| x.to_string())),
+            self.synced,
+            self.partial,
+            self.imported,
+            or_unknown(self.pending.map(|x| x.to_string())),
+            self.failures,
+            or_unknown(self.last_synced_at.map(|x| x.to_string())),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LanguageStats;
+    use crate::catalog::{Catalog, Status};
+
+    #[test]
+    fn count_by_language() -> anyhow::Result<()> {
+        let mut catalog = Catalog::new();
+        catalog.set_status(1, Status::Synced);
+        catalog.set_status(2, Status::Synced);
+        catalog.set_language(2, "english");
+        catalog.set_status(3, Status::Partial);
+        catalog.set_language(3, "korean");
+        catalog.set_status(4, Status::Imported);
+
+        let korean = LanguageStats::of("korean", true, Some(10), 2, &catalog);
+
+        assert_eq!(1, korean.synced);
+        assert_eq!(1, korean.partial);
+        assert_eq!(1, korean.imported);
+        assert_eq!(Some(9), korean.pending);
+        assert_eq!(2, korean.failures);
+        assert!(korean.last_synced_at.is_some());
+
+        let english = LanguageStats::of("english", false, None, 0, &catalog);
+
+        assert_eq!(1, english.synced);
+        assert_eq!(None, english.pending);
+
+        let japanese = LanguageStats::of("japanese", false, Some(3), 0, &catalog);
+
+        assert_eq!(0, japanese.synced);
+        assert_eq!(Some(3), japanese.pending);
+        assert_eq!(None, japanese.last_synced_at);
+
+        Ok(())
+    }
+}
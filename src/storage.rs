@@ -0,0 +1,98 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use anyhow;
+
+use crate::manifest::{HashWriter, Manifest};
+
+/// # Storage
+/// Local copy of galleries, `{root}/{id}/{filename}`
+pub struct Storage {
+    root: PathBuf,
+}
+
+impl Storage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Ids of stored galleries in ascending order
+    pub fn ids(&self) -> io::Result<Vec<u32>> {
+        let mut ids = fs::read_dir(&self.root)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+            .collect::<Vec<_>>();
+
+        ids.sort();
+
+        Ok(ids)
+    }
+
+    pub fn gallery_dir(&self, id: u32) -> PathBuf {
+        self.root.join(id.to_string())
+    }
+
+    pub fn write(&self, id: u32, filename: &str, buf: &[u8]) -> io::Result<()> {
+        let dir = self.gallery_dir(id);
+
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(filename), buf)
+    }
+
+    /// Writes what `write` streams into `{filename}.part`, and renames it on success
+    ///
+    /// Returns (size, sha256) of the file
+    pub fn write_with<F>(&self, id: u32, filename: &str, write: F) -> anyhow::Result<(u64, String)>
+    where
+        F: FnOnce(&mut HashWriter<BufWriter<File>>) -> anyhow::Result<()>,
+    {
+        let dir = self.gallery_dir(id);
+        let part = dir.join(format!("{}.part", filename));
+
+        fs::create_dir_all(&dir)?;
+
+        let mut writer = HashWriter::new(BufWriter::new(File::create(&part)?));
+
+        let r = write(&mut writer).and_then(|_| Ok(writer.flush()?));
+
+        if let Err(err) = r {
+            let _ = fs::remove_file(&part);
+            return Err(err);
+        }
+
+        let digest = writer.finish();
+
+        fs::rename(&part, dir.join(filename))?;
+
+        Ok(digest)
+    }
+
+    pub fn path(&self, id: u32, filename: &str) -> PathBuf {
+        self.gallery_dir(id).join(filename)
+    }
+
+    pub fn read(&self, id: u32, filename: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(id, filename))
+    }
+
+    /// (size, sha256) of a stored file, read in chunks
+    pub fn digest(&self, id: u32, filename: &str) -> io::Result<(u64, String)> {
+        let mut writer = HashWriter::new(io::sink());
+
+        io::copy(&mut File::open(self.path(id, filename))?, &mut writer)?;
+
+        Ok(writer.finish())
+    }
+
+    pub fn write_manifest(&self, manifest: &Manifest) -> anyhow::Result<()> {
+        self.write(manifest.id, "manifest.json", manifest.to_json()?.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn manifest(&self, id: u32) -> anyhow::Result<Manifest> {
+        Manifest::from_file(self.gallery_dir(id).join("manifest.json"))
+    }
+}
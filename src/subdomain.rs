@@ -0,0 +1,232 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::str::FromStr;
+use std::sync::{Condvar, Mutex};
+
+use anyhow;
+
+/// # SubdomainLimits
+/// Downloads in flight by image CDN subdomain, `aa`, `ba`, ...
+///
+/// Each subdomain has its own cap, so a slow one holds only its own downloads
+#[derive(Debug)]
+pub struct SubdomainLimits {
+    /// `None` is unlimited
+    cap: Option<usize>,
+    caps: HashMap<String, Option<usize>>,
+    in_flight: Mutex<HashMap<String, usize>>,
+    released: Condvar,
+}
+
+impl SubdomainLimits {
+    pub fn new(cap: Option<usize>) -> Self {
+        Self {
+            cap,
+            caps: HashMap::new(),
+            in_flight: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(None)
+    }
+
+    /// `0` is unlimited for `subdomain`
+    pub fn with_cap(mut self, subdomain: &str, cap: usize) -> Self {
+        self.caps
+            .insert(subdomain.to_string(), Some(cap).filter(|cap| *cap > 0));
+        self
+    }
+
+    pub fn cap(&self, subdomain: &str) -> Option<usize> {
+        match self.caps.get(subdomain) {
+            Some(cap) => *cap,
+            None => self.cap,
+        }
+    }
+
+    /// Blocks while `subdomain` has as many downloads as its cap
+    pub fn acquire(&self, subdomain: &str) -> SubdomainPermit<'_> {
+        let cap = self.cap(subdomain);
+        let mut in_flight = self.in_flight.lock().unwrap();
+
+        while cap.map_or(false, |cap| {
+            in_flight.get(subdomain).copied().unwrap_or(0) >= cap
+        }) {
+            in_flight = self.released.wait(in_flight).unwrap();
+        }
+
+        *in_flight.entry(subdomain.to_string()).or_insert(0) += 1;
+
+        SubdomainPermit {
+            limits: self,
+            subdomain: subdomain.to_string(),
+        }
+    }
+
+    /// (subdomain, downloads in flight)
+    pub fn in_flight(&self) -> Vec<(String, usize)> {
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, n)| **n > 0)
+            .map(|(subdomain, n)| (subdomain.clone(), *n))
+            .collect::<Vec<_>>();
+
+        in_flight.sort();
+        in_flight
+    }
+}
+
+/// `4` for every subdomain, `4,aa=2` overrides `aa`, `0` is unlimited, `4,aa=0` too for `aa`
+impl FromStr for SubdomainLimits {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || {
+            anyhow::Error::msg(format!(
+                "Can't parse subdomain limits from `{}`, e.g. 4,aa=2",
+                s
+            ))
+        };
+        let parse_cap = |x: &str| x.trim().parse::<usize>().map_err(|_| err());
+
+        let mut limits = Self::unlimited();
+
+        for part in s.split(',').filter(|part| !part.trim().is_empty()) {
+            match part.splitn(2, '=').collect::<Vec<_>>().as_slice() {
+                [cap] => limits.cap = Some(parse_cap(cap)?).filter(|cap| *cap > 0),
+                [subdomain, cap] if !subdomain.trim().is_empty() => {
+                    limits = limits.with_cap(subdomain.trim(), parse_cap(cap)?)
+                }
+                _ => return Err(err()),
+            }
+        }
+
+        Ok(limits)
+    }
+}
+
+pub struct SubdomainPermit<'a> {
+    limits: &'a SubdomainLimits,
+    subdomain: String,
+}
+
+impl<'a> Drop for SubdomainPermit<'a> {
+    fn drop(&mut self) {
+        let mut in_flight = self.limits.in_flight.lock().unwrap();
+
+        if let Some(n) = in_flight.get_mut(&self.subdomain) {
+            *n -= 1;
+        }
+
+        self.limits.released.notify_all();
+    }
+}
+
+/// Takes one item of each key in turn, keys in order of their first item
+///
+/// Pages of a gallery are spread over subdomains this way,
+/// so workers don't line up behind a run of pages of one subdomain
+pub fn round_robin<T, K, F>(items: Vec<T>, key: F) -> Vec<T>
+where
+    K: Eq + Hash,
+    F: Fn(&T) -> K,
+{
+    let len = items.len();
+    let mut order = HashMap::new();
+    let mut groups: Vec<VecDeque<T>> = vec![];
+
+    for item in items {
+        let i = *order.entry(key(&item)).or_insert_with(|| {
+            groups.push(VecDeque::new());
+            groups.len() - 1
+        });
+
+        groups[i].push_back(item);
+    }
+
+    let mut r = Vec::with_capacity(len);
+
+    while r.len() < len {
+        for group in groups.iter_mut() {
+            if let Some(item) = group.pop_front() {
+                r.push(item);
+            }
+        }
+    }
+
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{round_robin, SubdomainLimits};
+
+    #[test]
+    fn take_subdomains_in_turn() -> anyhow::Result<()> {
+        let pages = vec![
+            (1, "aa"),
+            (2, "aa"),
+            (3, "aa"),
+            (4, "ba"),
+            (5, "ca"),
+            (6, "ba"),
+        ];
+
+        assert_eq!(
+            vec![1, 4, 5, 2, 6, 3],
+            round_robin(pages, |(_, subdomain)| *subdomain)
+                .into_iter()
+                .map(|(page, _)| page)
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn cap_each_subdomain() -> anyhow::Result<()> {
+        let limits = Arc::new("1,ba=2".parse::<SubdomainLimits>()?);
+
+        assert_eq!(Some(1), limits.cap("aa"));
+        assert_eq!(Some(2), limits.cap("ba"));
+        assert!("1,=2".parse::<SubdomainLimits>().is_err());
+        assert_eq!(None, "0".parse::<SubdomainLimits>()?.cap("aa"));
+        assert_eq!(None, "4,aa=0".parse::<SubdomainLimits>()?.cap("aa"));
+        assert_eq!(Some(4), "4,aa=0".parse::<SubdomainLimits>()?.cap("ba"));
+
+        let aa = limits.acquire("aa");
+        let _ba = limits.acquire("ba");
+        let _ba = limits.acquire("ba");
+
+        let handle = {
+            let limits = Arc::clone(&limits);
+            thread::spawn(move || {
+                let _aa = limits.acquire("aa");
+            })
+        };
+
+        thread::sleep(Duration::from_millis(50));
+
+        // waits for `aa` only
+        assert_eq!(
+            vec![("aa".to_string(), 1), ("ba".to_string(), 2)],
+            limits.in_flight()
+        );
+
+        drop(aa);
+        handle.join().unwrap();
+
+        assert_eq!(vec![("ba".to_string(), 2)], limits.in_flight());
+
+        Ok(())
+    }
+}
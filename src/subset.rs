@@ -0,0 +1,188 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use anyhow;
+use serde::{Deserialize, Serialize};
+
+/// Which pages of a gallery to download
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PageSubset {
+    All,
+    /// Only the first page
+    Cover,
+    /// First N pages
+    First(usize),
+    /// 1, K + 1, 2K + 1, ...
+    Every(usize),
+}
+
+impl Default for PageSubset {
+    fn default() -> Self {
+        Self::All
+    }
+}
+
+impl PageSubset {
+    pub fn contains(&self, page: usize) -> bool {
+        match *self {
+            Self::All => true,
+            Self::Cover => page == 1,
+            Self::First(n) => page <= n,
+            Self::Every(k) => (page - 1) % k == 0,
+        }
+    }
+
+    /// Pages of 1..=total in it
+    pub fn pages(&self, total: usize) -> Vec<usize> {
+        (1..=total).filter(|page| self.contains(*page)).collect()
+    }
+
+    /// Pages of 1..=total left out of it
+    pub fn missing_pages(&self, total: usize) -> Vec<usize> {
+        (1..=total).filter(|page| !self.contains(*page)).collect()
+    }
+
+    pub fn is_all(&self) -> bool {
+        *self == Self::All
+    }
+}
+
+/// Pages downloaded in time of (page, `None` if it wasn't) in order, and their files
+///
+/// Downloads run in parallel, pages after a missing one may be done already
+pub fn completed<T>(pages: Vec<(usize, Option<T>)>) -> (Vec<usize>, Vec<T>) {
+    pages
+        .into_iter()
+        .filter_map(|(page, file)| file.map(|file| (page, file)))
+        .unzip()
+}
+
+/// `1-3,5,7-8`, pages in ascending order
+pub fn format_pages(pages: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = vec![];
+
+    for page in pages.iter().copied() {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == page => *end = page,
+            _ => ranges.push((page, page)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl Display for PageSubset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::All => write!(f, "all"),
+            Self::Cover => write!(f, "cover"),
+            Self::First(n) => write!(f, "first:{}", n),
+            Self::Every(k) => write!(f, "every:{}", k),
+        }
+    }
+}
+
+/// `all`, `cover`, `first:N` or `every:K`
+impl FromStr for PageSubset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(2, ':');
+
+        let kind = parts.next().unwrap_or("");
+        let n = parts
+            .next()
+            .map(|n| n.parse::<usize>())
+            .transpose()?
+            .filter(|n| *n > 0);
+
+        let r = match (kind, n) {
+            ("all", None) => Self::All,
+            ("cover", None) => Self::Cover,
+            ("first", Some(n)) => Self::First(n),
+            ("every", Some(k)) => Self::Every(k),
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "Can't parse page subset `{}`",
+                    s
+                )))
+            }
+        };
+
+        Ok(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{completed, format_pages, PageSubset};
+
+    #[test]
+    fn select_pages() -> anyhow::Result<()> {
+        assert_eq!(vec![1, 2, 3], PageSubset::All.pages(3));
+        assert_eq!(vec![1], "cover".parse::<PageSubset>()?.pages(3));
+        assert_eq!(vec![1, 2], "first:2".parse::<PageSubset>()?.pages(5));
+        assert_eq!(vec![1, 4, 7], "every:3".parse::<PageSubset>()?.pages(8));
+        assert_eq!(
+            vec![2, 3, 5, 6, 8],
+            "every:3".parse::<PageSubset>()?.missing_pages(8)
+        );
+
+        assert!("first:0".parse::<PageSubset>().is_err());
+        assert!("first".parse::<PageSubset>().is_err());
+        assert!("cover:1".parse::<PageSubset>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn commit_completed_pages() -> anyhow::Result<()> {
+        assert_eq!(
+            (vec![1, 2, 3], vec!["1", "2", "3"]),
+            completed(vec![(1, Some("1")), (2, Some("2")), (3, Some("3"))])
+        );
+
+        // 4 was done before 3 timed out
+        assert_eq!(
+            (vec![1, 2, 4], vec!["1", "2", "4"]),
+            completed(vec![
+                (1, Some("1")),
+                (2, Some("2")),
+                (3, None),
+                (4, Some("4"))
+            ])
+        );
+        assert_eq!(
+            (vec![4, 7], vec!["4", "7"]),
+            completed(vec![(1, None), (4, Some("4")), (7, Some("7"))])
+        );
+
+        assert_eq!("1-2,4", format_pages(&[1, 2, 4]));
+        assert_eq!("1,4-6,9", format_pages(&[1, 4, 5, 6, 9]));
+        assert_eq!("", format_pages(&[]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn serialize_page_subset() -> anyhow::Result<()> {
+        assert_eq!("\"all\"", serde_json::to_string(&PageSubset::All)?);
+        assert_eq!(
+            "{\"first\":5}",
+            serde_json::to_string(&PageSubset::First(5))?
+        );
+
+        Ok(())
+    }
+}
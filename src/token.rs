@@ -0,0 +1,425 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+
+use anyhow;
+use fp_core::lens::Lens;
+use log::info;
+use madome_client::auth::Token;
+use madome_client::AuthClient;
+use reqwest;
+
+use crate::rate_limit::{retry_after, RateLimiter, RetryAfter};
+use crate::secret;
+
+/// Service name of tokens in the OS keyring
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &'static str = "madome_synchronizer";
+
+/// Where the token of Madome is kept, never in env or args of the process
+#[derive(Debug, Clone)]
+pub enum TokenSource {
+    /// `./.token` by default
+    File(PathBuf),
+    /// User of the OS keyring
+    #[cfg(feature = "keyring")]
+    Keyring(String),
+}
+
+impl TokenSource {
+    /// TOKEN_KEYRING=user, or TOKEN_FILE=path
+    pub fn from_env() -> anyhow::Result<Self> {
+        if let Ok(user) = env::var("TOKEN_KEYRING") {
+            #[cfg(feature = "keyring")]
+            return Ok(Self::Keyring(user));
+
+            #[cfg(not(feature = "keyring"))]
+            return Err(anyhow::Error::msg(format!(
+                "TOKEN_KEYRING={} needs the keyring feature",
+                user
+            )));
+        }
+
+        let path = env::var("TOKEN_FILE").unwrap_or("./.token".to_string());
+
+        Ok(Self::File(PathBuf::from(path)))
+    }
+
+    pub fn load(&self) -> anyhow::Result<Token> {
+        let token = match self {
+            Self::File(path) => String::from_utf8(fs::read(path)?)?,
+            #[cfg(feature = "keyring")]
+            Self::Keyring(user) => keyring::Keyring::new(KEYRING_SERVICE, user)
+                .get_password()
+                .map_err(|err| anyhow::Error::msg(format!("Can't read keyring: {}", err)))?,
+        };
+        let token = token.trim().to_string();
+
+        secret::register(&token);
+
+        Ok(Token { token })
+    }
+
+    pub fn save(&self, token: &str) -> anyhow::Result<()> {
+        secret::register(token);
+
+        match self {
+            Self::File(path) => fs::write(path, token)?,
+            #[cfg(feature = "keyring")]
+            Self::Keyring(user) => keyring::Keyring::new(KEYRING_SERVICE, user)
+                .set_password(token)
+                .map_err(|err| anyhow::Error::msg(format!("Can't write keyring: {}", err)))?,
+        }
+
+        Ok(())
+    }
+}
+
+pub struct TokenLens;
+
+impl Lens<Token, String> for TokenLens {
+    fn get(s: &Token) -> Option<&String> {
+        Some(&s.token)
+    }
+
+    fn set(a: String, _: &Token) -> Token {
+        Token { token: a }
+    }
+}
+
+pub struct TokenManager;
+
+impl TokenManager {
+    pub fn refresh(
+        auth_client: &AuthClient,
+        source: &TokenSource,
+        token: Token,
+    ) -> anyhow::Result<Token> {
+        let old_token = TokenLens::get(&token).unwrap();
+        let new_token = auth_client.refresh_token(old_token)?;
+
+        source.save(&new_token)?;
+
+        let new_token = TokenLens::set(new_token, &token);
+
+        Ok(new_token)
+    }
+}
+
+/// Token shared by every sync thread
+///
+/// Madome's tokens are short-lived, so a long run refreshes it
+/// whenever a request comes back with `401 Unauthorized`.
+///
+/// Only one thread refreshes at a time,
+/// threads that were waiting on that refresh just reuse its result.
+///
+/// Every request to Madome goes through it, so it holds the rate limit of Madome too.
+pub struct TokenStore {
+    refresh: Box<dyn Fn(Token) -> anyhow::Result<Token> + Send + Sync>,
+    /// MADOME_RATE, paused by a 429 of Madome only
+    limit: Arc<RateLimiter>,
+    /// (generation, token)
+    inner: RwLock<(usize, Token)>,
+    refresh_lock: Mutex<()>,
+}
+
+impl TokenStore {
+    pub fn new(auth_client: AuthClient, source: TokenSource, token: Token) -> Self {
+        Self {
+            refresh: Box::new(move |token| TokenManager::refresh(&auth_client, &source, token)),
+            limit: Arc::new(RateLimiter::unlimited()),
+            inner: RwLock::new((0, token)),
+            refresh_lock: Mutex::new(()),
+        }
+    }
+
+    /// Refreshes with `refresh` instead of the auth server
+    pub fn with_refresh<F>(mut self, refresh: F) -> Self
+    where
+        F: Fn(Token) -> anyhow::Result<Token> + Send + Sync + 'static,
+    {
+        self.refresh = Box::new(refresh);
+        self
+    }
+
+    pub fn with_limit(mut self, limit: Arc<RateLimiter>) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn get(&self) -> (usize, String) {
+        let inner = self.inner.read().unwrap();
+
+        (inner.0, TokenLens::get(&inner.1).unwrap().clone())
+    }
+
+    /// Refreshes the token unless another thread already did since `generation`
+    pub fn refresh(&self, generation: usize) -> anyhow::Result<()> {
+        let _guard = self.refresh_lock.lock().unwrap();
+
+        let (current_generation, token) = self.get();
+
+        if current_generation != generation {
+            return Ok(());
+        }
+
+        let token = (self.refresh)(Token { token })?;
+
+        info!("Refreshed token");
+
+        *self.inner.write().unwrap() = (generation + 1, token);
+
+        Ok(())
+    }
+
+    /// Calls `f` with the current token, and once more with a refreshed token if it was rejected
+    pub fn with_token<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        F: Fn(&String) -> anyhow::Result<T>,
+    {
+        let (generation, token) = self.get();
+
+        match self.request(&f, &token) {
+            Err(err) if is_status(&err, reqwest::StatusCode::UNAUTHORIZED) => {
+                self.refresh(generation)?;
+
+                let (_, token) = self.get();
+
+                self.request(&f, &token)
+            }
+            r => r,
+        }
+    }
+
+    /// `f` once the limit allows it, a 429 holds back every request to Madome
+    fn request<T, F>(&self, f: &F, token: &String) -> anyhow::Result<T>
+    where
+        F: Fn(&String) -> anyhow::Result<T>,
+    {
+        self.limit.acquire();
+
+        f(token).map_err(|err| {
+            // madome_client doesn't give its response, so no Retry-After of it
+            let err = if is_status(&err, reqwest::StatusCode::TOO_MANY_REQUESTS) {
+                RetryAfter {
+                    wait: None,
+                    host: None,
+                }
+                .into()
+            } else {
+                err
+            };
+
+            if let Some(wait) = retry_after(&err) {
+                self.limit.pause(wait);
+            }
+
+            err
+        })
+    }
+}
+
+/// `status` of a response checked by `error_for_status`,
+/// of our requests or of madome_client, which is still on reqwest 0.10
+fn is_status(err: &anyhow::Error, status: reqwest::StatusCode) -> bool {
+    err.chain().any(|cause| {
+        let actual = match cause.downcast_ref::<reqwest::Error>() {
+            Some(err) => err.status().map(|x| x.as_u16()),
+            None => cause
+                .downcast_ref::<reqwest_madome::Error>()
+                .and_then(|err| err.status())
+                .map(|x| x.as_u16()),
+        };
+
+        actual == Some(status.as_u16())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use madome_client::auth::Token;
+    use madome_client::{AuthClient, BookClient, FileClient};
+
+    use super::{TokenSource, TokenStore};
+    use crate::client;
+    use crate::mock::MockServer;
+    use crate::rate_limit::RetryAfter;
+
+    const CALLERS: usize = 8;
+
+    #[test]
+    fn refresh_once_for_concurrent_401() -> anyhow::Result<()> {
+        let server = MockServer::start(vec![(401, String::new()); CALLERS])?;
+        let url = server.url().to_string();
+
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let store = {
+            let refreshes = Arc::clone(&refreshes);
+
+            TokenStore::new(
+                AuthClient::new("http://127.0.0.1:1"),
+                TokenSource::File(env::temp_dir().join("madome_synchronizer_token")),
+                Token {
+                    token: "expired".to_string(),
+                },
+            )
+            .with_refresh(move |_| {
+                refreshes.fetch_add(1, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(50));
+
+                Ok(Token {
+                    token: "fresh".to_string(),
+                })
+            })
+        };
+        let store = Arc::new(store);
+        // every caller is rejected before any of them refreshes
+        let barrier = Arc::new(Barrier::new(CALLERS));
+
+        let handles = (0..CALLERS)
+            .map(|_| {
+                let store = Arc::clone(&store);
+                let barrier = Arc::clone(&barrier);
+                let url = url.clone();
+
+                thread::spawn(move || {
+                    store.with_token(|token| {
+                        if token == "fresh" {
+                            return Ok(token.clone());
+                        }
+
+                        barrier.wait();
+
+                        client::shared()?
+                            .get(&url)
+                            .header("Authorization", token.as_str())
+                            .send()?
+                            .error_for_status()?;
+
+                        Ok(token.clone())
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            assert_eq!("fresh", handle.join().unwrap()?);
+        }
+
+        assert_eq!(1, refreshes.load(Ordering::SeqCst));
+        assert_eq!(CALLERS, server.requests()?.len());
+
+        Ok(())
+    }
+
+    fn counted_store(refreshes: &Arc<AtomicUsize>) -> TokenStore {
+        let refreshes = Arc::clone(refreshes);
+
+        TokenStore::new(
+            AuthClient::new("http://127.0.0.1:1"),
+            TokenSource::File(env::temp_dir().join("madome_synchronizer_token")),
+            Token {
+                token: "expired".to_string(),
+            },
+        )
+        .with_refresh(move |_| {
+            refreshes.fetch_add(1, Ordering::SeqCst);
+
+            Ok(Token {
+                token: "fresh".to_string(),
+            })
+        })
+    }
+
+    #[test]
+    fn refresh_on_401_of_madome_client() -> anyhow::Result<()> {
+        let server = MockServer::start(vec![(401, String::new()); 4])?;
+        let refreshes = Arc::new(AtomicUsize::new(0));
+
+        let book_client = BookClient::new(server.url());
+        let store = counted_store(&refreshes);
+
+        assert!(store
+            .with_token(|token| book_client.get_book_by_id(token, 1724122))
+            .is_err());
+        assert_eq!(1, refreshes.load(Ordering::SeqCst));
+
+        let file_client = FileClient::new(server.url());
+        let store = counted_store(&refreshes);
+
+        assert!(store
+            .with_token(|token| file_client.upload(
+                token,
+                "image/library/1724122/1.jpg",
+                Bytes::from_static(b"one")
+            ))
+            .is_err());
+        assert_eq!(2, refreshes.load(Ordering::SeqCst));
+
+        // each call, and once more after its refresh
+        assert_eq!(4, server.requests()?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rate_limited_by_429_of_madome_client() -> anyhow::Result<()> {
+        let server = MockServer::start(vec![(429, String::new())])?;
+        let refreshes = Arc::new(AtomicUsize::new(0));
+
+        let book_client = BookClient::new(server.url());
+        let store = counted_store(&refreshes);
+
+        let err = store
+            .with_token(|token| book_client.get_book_by_id(token, 1724122))
+            .err()
+            .unwrap();
+
+        assert!(err.downcast_ref::<RetryAfter>().is_some());
+        assert_eq!(0, refreshes.load(Ordering::SeqCst));
+        assert_eq!(1, server.requests()?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn other_errors_are_not_refreshed() -> anyhow::Result<()> {
+        let server = MockServer::start(vec![(403, String::new())])?;
+
+        let store = TokenStore::new(
+            AuthClient::new("http://127.0.0.1:1"),
+            TokenSource::File(env::temp_dir().join("madome_synchronizer_token")),
+            Token {
+                token: "token".to_string(),
+            },
+        )
+        .with_refresh(|_| panic!("refreshed on 403"));
+
+        let r = store.with_token(|token| {
+            client::shared()?
+                .get(server.url())
+                .header("Authorization", token.as_str())
+                .send()?
+                .error_for_status()?;
+
+            Ok(())
+        });
+
+        assert!(r.is_err());
+        // the text has 401 in it, but it isn't a response of 401
+        assert!(store
+            .with_token(|_| -> anyhow::Result<()> { Err(anyhow::Error::msg("401 Unauthorized")) })
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+use std::io::{self, Stdout};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use anyhow;
+use crossterm::event::{self, Event, KeyCode};
+use crossterm::execute;
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::widgets::{Block, Borders, Gauge, List, ListItem, Paragraph, Row, Table};
+use ratatui::{Frame, Terminal};
+
+use crate::dashboard::Dashboard;
+use crate::progress::Progress;
+use crate::rate_limit::RateLimiter;
+
+const TICK: Duration = Duration::from_millis(500);
+
+/// What the dashboard draws, shared with the workers
+pub struct Sources {
+    pub dashboard: Arc<Dashboard>,
+    pub progress: Arc<Progress>,
+    /// (name, limiter)
+    pub limiters: Vec<(&'static str, Arc<RateLimiter>)>,
+}
+
+/// # Tui
+/// Draws `Sources` until it is dropped or `q` is pressed, synchronize goes on either way
+pub struct Tui {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Tui {
+    pub fn spawn(sources: Sources) -> anyhow::Result<Self> {
+        let mut terminal = enter()?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_ = Arc::clone(&stop);
+
+        let handle = thread::spawn(move || {
+            while !stop_.load(Ordering::Relaxed) {
+                if terminal.draw(|f| draw(f, &sources)).is_err() {
+                    break;
+                }
+
+                match event::poll(TICK) {
+                    Ok(true) => {
+                        if let Ok(Event::Key(key)) = event::read() {
+                            if key.code == KeyCode::Char('q') {
+                                break;
+                            }
+                        }
+                    }
+                    Ok(false) => {}
+                    Err(_) => break,
+                }
+            }
+
+            leave(&mut terminal);
+        });
+
+        Ok(Self {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn enter() -> anyhow::Result<Terminal<CrosstermBackend<Stdout>>> {
+    terminal::enable_raw_mode()?;
+
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+
+    Ok(Terminal::new(CrosstermBackend::new(stdout))?)
+}
+
+fn leave(terminal: &mut Terminal<CrosstermBackend<Stdout>>) {
+    let _ = terminal::disable_raw_mode();
+    let _ = execute!(terminal.backend_mut(), LeaveAlternateScreen);
+    let _ = terminal.show_cursor();
+}
+
+fn human_bytes(bytes: f64) -> String {
+    if bytes >= 1024.0 * 1024.0 {
+        format!("{:.1} MB", bytes / 1024.0 / 1024.0)
+    } else {
+        format!("{:.0} KB", bytes / 1024.0)
+    }
+}
+
+fn draw(f: &mut Frame<CrosstermBackend<Stdout>>, sources: &Sources) {
+    let progress = sources.progress.snapshot();
+    let dashboard = sources.dashboard.snapshot();
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(11),
+            Constraint::Min(5),
+            Constraint::Length(8),
+        ])
+        .split(f.size());
+
+    let ratio = match progress.total_ids {
+        Some(total) if total > 0 => (progress.done_ids as f64 / total as f64).min(1.0),
+        _ => 0.0,
+    };
+    let gauge = Gauge::default()
+        .block(Block::default().borders(Borders::ALL).title("Progress"))
+        .ratio(ratio)
+        .label(progress.to_string());
+    f.render_widget(gauge, rows[0]);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[1]);
+
+    let stages = Table::new(
+        std::iter::once(Row::new(vec![
+            "Queued".to_string(),
+            dashboard.queued.to_string(),
+        ]))
+        .chain(
+            dashboard
+                .stages
+                .iter()
+                .map(|(stage, n)| Row::new(vec![stage.clone(), n.to_string()])),
+        ),
+    )
+    .block(Block::default().borders(Borders::ALL).title("Stages"))
+    .widths(&[Constraint::Percentage(70), Constraint::Percentage(30)]);
+    f.render_widget(stages, columns[0]);
+
+    let limiters = sources
+        .limiters
+        .iter()
+        .map(|(name, limiter)| {
+            let rate = limiter
+                .per_second()
+                .map(|x| format!("{:.1}/s", x))
+                .unwrap_or_else(|| "unlimited".to_string());
+            let waiting = limiter
+                .waiting()
+                .map(|x| format!(", waiting {:.1}s", x.as_secs_f64()))
+                .unwrap_or_default();
+
+            format!("{}: {}{}", name, rate, waiting)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let limiters = Paragraph::new(limiters).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Rate limiters"),
+    );
+    f.render_widget(limiters, columns[1]);
+
+    let downloads = Table::new(dashboard.downloads.iter().map(|download| {
+        Row::new(vec![
+            download.id.to_string(),
+            download.filename.clone(),
+            human_bytes(download.bytes as f64),
+            format!("{}/s", human_bytes(download.bytes_per_second)),
+        ])
+    }))
+    .header(Row::new(vec!["Id", "File", "Downloaded", "Speed"]))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!("Downloads ({})", dashboard.downloads.len())),
+    )
+    .widths(&[
+        Constraint::Length(10),
+        Constraint::Min(16),
+        Constraint::Length(12),
+        Constraint::Length(12),
+    ]);
+    f.render_widget(downloads, rows[2]);
+
+    let errors = dashboard
+        .errors
+        .iter()
+        .map(|(id, error)| ListItem::new(format!("{}: {}", id, error)))
+        .collect::<Vec<_>>();
+    let errors = List::new(errors).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Recent errors"),
+    );
+    f.render_widget(errors, rows[3]);
+}
@@ -0,0 +1,263 @@
+use std::env;
+
+use once_cell::sync::Lazy;
+
+/// URLs of hitomi, every endpoint the parsers request is built here
+pub const HITOMI: &'static str = "https://hitomi.la";
+/// Gallery blocks, gallery info and nozomi indexes
+pub const LTN: &'static str = "https://ltn.hitomi.la";
+/// Thumbnails
+pub const TN: &'static str = "https://tn.hitomi.la";
+pub const STREAMING: &'static str = "https://streaming.hitomi.la";
+
+static HITOMI_URL: Lazy<String> =
+    Lazy::new(|| base_url_or(env::var("HITOMI_BASE_URL").ok(), HITOMI));
+static LTN_URL: Lazy<String> = Lazy::new(|| base_url_or(env::var("LTN_BASE_URL").ok(), LTN));
+static TN_URL: Lazy<String> = Lazy::new(|| base_url_or(env::var("TN_BASE_URL").ok(), TN));
+static STREAMING_URL: Lazy<String> =
+    Lazy::new(|| base_url_or(env::var("STREAMING_BASE_URL").ok(), STREAMING));
+
+/// `value` without a trailing slash, `default` if it is unset or empty
+fn base_url_or(value: Option<String>, default: &str) -> String {
+    match value.map(|x| x.trim().trim_end_matches('/').to_string()) {
+        Some(x) if !x.is_empty() => x,
+        _ => default.to_string(),
+    }
+}
+
+/// HITOMI_BASE_URL, a mirror or a local server of fixtures instead of `HITOMI`
+pub fn hitomi() -> &'static str {
+    &HITOMI_URL
+}
+
+/// LTN_BASE_URL, instead of `LTN`
+pub fn ltn() -> &'static str {
+    &LTN_URL
+}
+
+/// TN_BASE_URL, instead of `TN`
+pub fn tn() -> &'static str {
+    &TN_URL
+}
+
+/// STREAMING_BASE_URL, instead of `STREAMING`
+pub fn streaming() -> &'static str {
+    &STREAMING_URL
+}
+
+/// Redirects to the content url
+pub fn gallery(id: u32) -> String {
+    format!("{}/galleries/{}.html", hitomi(), id)
+}
+
+/// `https://hitomi.la/doujinshi/{slug}-{id}.html`
+pub fn content(content_type: &str, slug: &str, id: u32) -> String {
+    format!("{}/{}/{}-{}.html", hitomi(), content_type, slug, id)
+}
+
+/// `Referer` images need
+pub fn reader(id: u32) -> String {
+    format!("{}/reader/{}.html", hitomi(), id)
+}
+
+pub fn gallery_block(id: u32) -> String {
+    format!("{}/galleryblock/{}.html", ltn(), id)
+}
+
+/// `galleryinfo` of the gallery and its files
+pub fn gallery_info(id: u32) -> String {
+    format!("{}/galleries/{}.js", ltn(), id)
+}
+
+/// `index`, `popular/week`, ... of `NozomiIndex::path`
+pub fn nozomi(path: &str, language: &str) -> String {
+    format!("{}/{}-{}.nozomi", ltn(), path, language.to_lowercase())
+}
+
+/// Galleries of a tag area such as `artist`, `n/artist/airandou-korean.nozomi`
+pub fn tag_nozomi(area: &str, name: &str, language: &str) -> String {
+    format!(
+        "{}/n/{}/{}-{}.nozomi",
+        ltn(),
+        area,
+        encode_uri(&name.to_lowercase()),
+        language.to_lowercase()
+    )
+}
+
+/// `encodeURI` of javascript, hitomi builds the urls of tags with it
+pub fn encode_uri(s: &str) -> String {
+    s.bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b";,/?:@&=+$-_.!~*'()#".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect()
+}
+
+/// Directories of a file hash, `…abc` is `c/ab`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashPath<'a> {
+    pub hash: &'a str,
+}
+
+impl<'a> HashPath<'a> {
+    /// `None` if the hash is shorter than 3
+    pub fn new(hash: &'a str) -> Option<Self> {
+        if hash.len() < 3 || !hash.is_char_boundary(hash.len() - 3) {
+            return None;
+        }
+
+        Some(Self { hash })
+    }
+
+    /// Two characters before the last, hexadecimal selects the subdomain
+    pub fn middle(&self) -> &'a str {
+        &self.hash[self.hash.len() - 3..self.hash.len() - 1]
+    }
+
+    pub fn last(&self) -> &'a str {
+        &self.hash[self.hash.len() - 1..]
+    }
+}
+
+/// `https://{subdomain}b.hitomi.la/images/c/ab/{hash}.{ext}`
+///
+/// The host isn't overridden, the subdomain is picked from the hash and `SubdomainLimits` caps
+/// downloads by it, a mirror has no such subdomains
+pub fn image(subdomain: &str, path: HashPath, ext: &str) -> String {
+    format!(
+        "https://{}b.hitomi.la/images/{}/{}/{}.{}",
+        subdomain,
+        path.last(),
+        path.middle(),
+        path.hash,
+        ext
+    )
+}
+
+pub fn thumbnail(path: HashPath) -> String {
+    format!(
+        "{}/bigtn/{}/{}/{}.jpg",
+        tn(),
+        path.last(),
+        path.middle(),
+        path.hash
+    )
+}
+
+/// `/bigtn/c/ab/{hash}.jpg` of a thumbnail url, as Madome keeps it
+pub fn thumbnail_path(url: &str) -> &str {
+    let url = url.strip_prefix(tn()).unwrap_or(url);
+    let url = url.strip_prefix("https:").unwrap_or(url);
+    let url = url.strip_prefix("//tn.hitomi.la").unwrap_or(url);
+
+    url
+}
+
+/// `ab.hitomi.la` of `https://ab.hitomi.la/...`
+pub fn host(url: &str) -> Option<&str> {
+    url.split("//").nth(1)?.split('/').next()
+}
+
+/// `ab` of `https://ab.hitomi.la/...`
+pub fn subdomain(url: &str) -> Option<&str> {
+    host(url)?.strip_suffix(".hitomi.la")
+}
+
+pub fn video(filename: &str) -> String {
+    format!("{}/videos/{}", streaming(), filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_gallery_urls() -> anyhow::Result<()> {
+        assert_eq!("https://hitomi.la/galleries/1724122.html", gallery(1724122));
+        assert_eq!(
+            "https://hitomi.la/doujinshi/a-b-한국어-1724122.html",
+            content("doujinshi", "a-b-한국어", 1724122)
+        );
+        assert_eq!("https://hitomi.la/reader/1724122.html", reader(1724122));
+        assert_eq!(
+            "https://ltn.hitomi.la/galleryblock/1724122.html",
+            gallery_block(1724122)
+        );
+        assert_eq!(
+            "https://ltn.hitomi.la/galleries/1724122.js",
+            gallery_info(1724122)
+        );
+        assert_eq!(
+            "https://ltn.hitomi.la/popular/week-korean.nozomi",
+            nozomi("popular/week", "Korean")
+        );
+        assert_eq!(
+            "https://ltn.hitomi.la/n/artist/kisaragi%20gunma-korean.nozomi",
+            tag_nozomi("artist", "Kisaragi Gunma", "korean")
+        );
+        assert_eq!(
+            "https://ltn.hitomi.la/n/series/%ED%8F%AC%EC%BC%93%EB%AA%AC-all.nozomi",
+            tag_nozomi("series", "포켓몬", "all")
+        );
+        assert_eq!(
+            "https://streaming.hitomi.la/videos/anime.mp4",
+            video("anime.mp4")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn override_base_urls() -> anyhow::Result<()> {
+        assert_eq!(
+            "http://127.0.0.1:8080",
+            base_url_or(Some("http://127.0.0.1:8080/".to_string()), LTN)
+        );
+        assert_eq!(
+            "https://mirror.example/hitomi",
+            base_url_or(Some(" https://mirror.example/hitomi ".to_string()), HITOMI)
+        );
+        assert_eq!(LTN, base_url_or(Some("".to_string()), LTN));
+        assert_eq!(HITOMI, base_url_or(None, HITOMI));
+
+        Ok(())
+    }
+
+    #[test]
+    fn build_image_urls() -> anyhow::Result<()> {
+        let path =
+            HashPath::new("0a1b2c3d4e5f6f7e8d9c0b1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0abc")
+                .unwrap();
+
+        assert_eq!("ab", path.middle());
+        assert_eq!("c", path.last());
+        assert_eq!(
+            format!("https://ab.hitomi.la/images/c/ab/{}.jpg", path.hash),
+            image("a", path, "jpg")
+        );
+        assert_eq!(
+            format!("https://tn.hitomi.la/bigtn/c/ab/{}.jpg", path.hash),
+            thumbnail(path)
+        );
+        assert_eq!(
+            format!("/bigtn/c/ab/{}.jpg", path.hash),
+            thumbnail_path(&thumbnail(path))
+        );
+        assert_eq!(
+            "/smallbigtn/c/ab/x.jpg",
+            thumbnail_path("//tn.hitomi.la/smallbigtn/c/ab/x.jpg")
+        );
+        assert_eq!(None, HashPath::new("ab"));
+        assert_eq!(Some("ab"), subdomain(&image("a", path, "jpg")));
+        assert_eq!(Some("tn"), subdomain(&thumbnail(path)));
+        assert_eq!(None, subdomain("https://example.com/1.jpg"));
+        assert_eq!(Some("ltn.hitomi.la"), host(LTN));
+
+        Ok(())
+    }
+}
@@ -1,21 +1,20 @@
-use std::collections::HashSet;
+use std::collections::BTreeSet;
 use std::fmt::Display;
 use std::fs;
-use std::hash::Hash;
 use std::str::FromStr;
 
 pub struct TextStore<T>
 where
-    T: Eq + Hash + Display + FromStr,
+    T: Ord + Display + FromStr,
 {
-    inner: HashSet<T>,
+    inner: BTreeSet<T>,
 }
 
 impl<T> TextStore<T>
 where
-    T: Eq + Hash + Display + FromStr,
+    T: Ord + Display + FromStr,
 {
-    pub fn iter(&self) -> std::collections::hash_set::Iter<'_, T> {
+    pub fn iter(&self) -> std::collections::btree_set::Iter<'_, T> {
         self.inner.iter()
     }
 
@@ -47,7 +46,7 @@ where
 
         if text.trim().is_empty() {
             return Ok(Self {
-                inner: HashSet::new(),
+                inner: BTreeSet::new(),
             });
         }
 
@@ -55,7 +54,7 @@ where
             .trim()
             .lines()
             .filter_map(|s| s.parse::<T>().ok())
-            .collect::<HashSet<_>>();
+            .collect::<BTreeSet<_>>();
 
         Ok(Self { inner })
     }
@@ -0,0 +1,264 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use anyhow;
+use time::Date;
+
+use crate::models::{Metadata, MetadataBook};
+
+/// hitomi.la opened in 2007, older dates are broken ones
+const FIRST_YEAR: i32 = 2007;
+
+/// How much of the metadata is checked before the book goes to Madome
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strictness {
+    Off,
+    /// Id, title, language and date if there is one
+    Lenient,
+    /// `Lenient`, a date is required and tag names are checked
+    Strict,
+}
+
+impl Default for Strictness {
+    fn default() -> Self {
+        Self::Lenient
+    }
+}
+
+impl FromStr for Strictness {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let r = match s.trim() {
+            "off" => Self::Off,
+            "lenient" => Self::Lenient,
+            "strict" => Self::Strict,
+            _ => return Err(anyhow::Error::msg(format!("Unknown strictness `{}`", s))),
+        };
+
+        Ok(r)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    InvalidId(u32),
+    /// Id of the metadata isn't the gallery's
+    IdMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    MissingTitle,
+    MissingLanguage,
+    MissingDate,
+    /// Not `2020-09-02 ...`, or before 2007
+    InvalidDate(String),
+    /// Ahead of the clock, by `FutureDates::Reject`
+    FutureDate(String),
+    InvalidTag(String),
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidId(id) => write!(f, "invalid id {}", id),
+            Self::IdMismatch { expected, actual } => {
+                write!(f, "id {} of metadata isn't {}", actual, expected)
+            }
+            Self::MissingTitle => write!(f, "missing title"),
+            Self::MissingLanguage => write!(f, "missing language"),
+            Self::MissingDate => write!(f, "missing date"),
+            Self::InvalidDate(date) => write!(f, "invalid date `{}`", date),
+            Self::FutureDate(date) => write!(f, "date `{}` is in the future", date),
+            Self::InvalidTag(tag) => write!(f, "invalid tag `{}`", tag),
+        }
+    }
+}
+
+/// Returned instead of uploading the book, `invalid` class of `ERROR_POLICY`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invalid {
+    pub id: u32,
+    pub violations: Vec<Violation>,
+}
+
+impl Display for Invalid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let violations = self
+            .violations
+            .iter()
+            .map(|violation| violation.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        write!(f, "Invalid metadata of {}: {}", self.id, violations)
+    }
+}
+
+impl Error for Invalid {}
+
+pub fn is_invalid(err: &anyhow::Error) -> bool {
+    err.chain()
+        .any(|cause| cause.downcast_ref::<Invalid>().is_some())
+}
+
+/// Lowercase ascii, digits, some punctuation and the gender signs of hitomi, `loli ♀`
+fn is_tag_char(c: char) -> bool {
+    c.is_ascii_lowercase()
+        || c.is_ascii_digit()
+        || " -'.+&!:/()".contains(c)
+        || c == '♀'
+        || c == '♂'
+}
+
+fn is_valid_tag(tag: &str) -> bool {
+    !tag.trim().is_empty() && tag.trim() == tag && tag.chars().all(is_tag_char)
+}
+
+/// `YYYY-MM-DD` at the start, the rest differs by metadata source
+fn parse_date(date: &str) -> Option<Date> {
+    let mut ymd = date.get(..10)?.split('-');
+
+    let year = ymd.next()?.parse().ok()?;
+    let month = ymd.next()?.parse().ok()?;
+    let day = ymd.next()?.parse().ok()?;
+
+    Date::try_from_ymd(year, month, day).ok()
+}
+
+/// Dates in the future are left to `CreatedAtPolicy`
+fn is_sane_date(date: &str) -> bool {
+    match parse_date(date) {
+        Some(date) => date.year() >= FIRST_YEAR,
+        None => false,
+    }
+}
+
+pub fn validate(id: u32, book: &MetadataBook, strictness: Strictness) -> Result<(), Invalid> {
+    if strictness == Strictness::Off {
+        return Ok(());
+    }
+
+    let mut violations = vec![];
+
+    if id == 0 {
+        violations.push(Violation::InvalidId(id));
+    }
+
+    if let Metadata::ID(Some(actual)) = &book.id {
+        if *actual != id {
+            violations.push(Violation::IdMismatch {
+                expected: id,
+                actual: *actual,
+            });
+        }
+    }
+
+    match &book.title {
+        Metadata::Title(Some(title)) if !title.trim().is_empty() => {}
+        _ => violations.push(Violation::MissingTitle),
+    }
+
+    match &book.language {
+        Metadata::Language(Some(_)) => {}
+        _ => violations.push(Violation::MissingLanguage),
+    }
+
+    match &book.created_at {
+        Metadata::CreatedAt(Some(date)) => {
+            if !is_sane_date(date) {
+                violations.push(Violation::InvalidDate(date.clone()));
+            }
+        }
+        _ if strictness == Strictness::Strict => violations.push(Violation::MissingDate),
+        _ => {}
+    }
+
+    if strictness == Strictness::Strict {
+        if let Metadata::Tags(Some(tags)) = &book.tags {
+            violations.extend(
+                tags.iter()
+                    .filter(|tag| !is_valid_tag(tag))
+                    .map(|tag| Violation::InvalidTag(tag.clone())),
+            );
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(Invalid { id, violations })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{is_sane_date, is_valid_tag, validate, Strictness, Violation};
+    use crate::models::{self, Metadata, MetadataBook};
+
+    fn book() -> MetadataBook {
+        MetadataBook {
+            artists: Metadata::Artists(Some(vec!["airandou".to_string()])),
+            tags: Metadata::Tags(Some(vec!["loli ♀".to_string(), "incest".to_string()])),
+            created_at: Metadata::CreatedAt(Some("2020-09-02 10:01:00 -05:00".to_string())),
+            ..models::book(Some(1724122), Some("Tsundere Imouto | 츤데레 여동생"))
+        }
+    }
+
+    #[test]
+    fn violations_by_strictness() -> anyhow::Result<()> {
+        let mut book = book();
+
+        assert_eq!(Ok(()), validate(1724122, &book, Strictness::Off));
+        assert_eq!(
+            vec![Violation::MissingLanguage],
+            validate(1724122, &book, Strictness::Lenient)
+                .unwrap_err()
+                .violations
+        );
+
+        book.title = Metadata::Title(Some(" ".to_string()));
+        book.tags = Metadata::Tags(Some(vec!["loli%20♀".to_string()]));
+        book.created_at = Metadata::CreatedAt(None);
+
+        assert_eq!(
+            vec![
+                Violation::IdMismatch {
+                    expected: 1,
+                    actual: 1724122
+                },
+                Violation::MissingTitle,
+                Violation::MissingLanguage,
+                Violation::MissingDate,
+                Violation::InvalidTag("loli%20♀".to_string()),
+            ],
+            validate(1, &book, Strictness::Strict)
+                .unwrap_err()
+                .violations
+        );
+
+        assert_eq!(Strictness::Strict, "strict".parse()?);
+        assert!("loose".parse::<Strictness>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn dates_and_tags() -> anyhow::Result<()> {
+        assert!(is_sane_date("2020-09-02 10:01:00 -05:00"));
+        assert!(is_sane_date("2021-05-02 01:00:00+09"));
+        assert!(!is_sane_date("1970-01-01 00:00:00"));
+        assert!(!is_sane_date("2020-13-01"));
+        assert!(!is_sane_date("Sep 2, 2020"));
+
+        assert!(is_valid_tag("big breasts ♀"));
+        assert!(is_valid_tag("x-ray"));
+        assert!(!is_valid_tag(""));
+        assert!(!is_valid_tag(" incest"));
+        assert!(!is_valid_tag("<b>incest</b>"));
+
+        Ok(())
+    }
+}
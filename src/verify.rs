@@ -0,0 +1,307 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+
+use anyhow;
+#[cfg(feature = "net")]
+use log::info;
+
+use crate::manifest::{Checksum, ManifestFile};
+#[cfg(feature = "net")]
+use crate::parser::download_image;
+use crate::postprocess::{ImageHooks, ImageInfo};
+use crate::storage::Storage;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Problem {
+    Missing,
+    /// Size or hash is different from the manifest
+    Corrupt,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let r = match self {
+            Self::Missing => "Missing",
+            Self::Corrupt => "Corrupt",
+        };
+
+        write!(f, "{}", r)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BadFile {
+    pub file: ManifestFile,
+    pub problem: Problem,
+}
+
+/// Re-hashes stored files of a gallery against its manifest
+pub fn verify_files(storage: &Storage, id: u32, files: &[ManifestFile]) -> Vec<BadFile> {
+    files
+        .iter()
+        .filter_map(|file| {
+            let problem = match storage.read(id, &file.filename) {
+                Ok(buf) if file.is_same(&buf) => return None,
+                Ok(_) => Problem::Corrupt,
+                Err(_) => Problem::Missing,
+            };
+
+            Some(BadFile {
+                file: file.clone(),
+                problem,
+            })
+        })
+        .collect()
+}
+
+/// Files of `files` the file repository didn't acknowledge as they are
+pub fn verify_acknowledgement(files: &[ManifestFile], acknowledged: &[Checksum]) -> Vec<BadFile> {
+    files
+        .iter()
+        .filter_map(|file| {
+            let problem = match acknowledged.iter().find(|x| x.path == file.path) {
+                Some(x) if *x == Checksum::from(file) => return None,
+                Some(_) => Problem::Corrupt,
+                None => Problem::Missing,
+            };
+
+            Some(BadFile {
+                file: file.clone(),
+                problem,
+            })
+        })
+        .collect()
+}
+
+/// The file repository didn't confirm every file of the manifest, the gallery isn't synced
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnconfirmedUpload {
+    pub id: u32,
+    pub bad_files: Vec<BadFile>,
+}
+
+impl Display for UnconfirmedUpload {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let bad_files = self
+            .bad_files
+            .iter()
+            .map(|x| format!("{} {}", x.problem, x.file.filename))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        write!(f, "Upload of {} isn't confirmed: {}", self.id, bad_files)
+    }
+}
+
+impl Error for UnconfirmedUpload {}
+
+/// Thumbnail and images of a stored gallery
+pub fn verify_gallery(storage: &Storage, id: u32) -> anyhow::Result<Vec<BadFile>> {
+    let manifest = storage.manifest(id)?;
+
+    let mut files = vec![manifest.thumbnail];
+    files.extend(manifest.files);
+
+    Ok(verify_files(storage, id, &files))
+}
+
+/// Downloads only bad files again from their source url
+#[cfg(feature = "net")]
+pub fn repair_gallery(
+    storage: &Storage,
+    id: u32,
+    bad_files: &[BadFile],
+    hooks: &ImageHooks,
+) -> anyhow::Result<()> {
+    for BadFile { file, problem } in bad_files {
+        info!("{}: Repair {}: {}", id, problem, file.filename);
+
+        let buf = download_image(id, &file.source_url)?;
+
+        repair_file(storage, id, file, &buf, hooks)?;
+    }
+
+    Ok(())
+}
+
+/// Replaces a stored file with `buf` as it was downloaded,
+/// the manifest has it after IMAGE_HOOKS so they run before comparing
+pub fn repair_file(
+    storage: &Storage,
+    id: u32,
+    file: &ManifestFile,
+    buf: &[u8],
+    hooks: &ImageHooks,
+) -> anyhow::Result<()> {
+    let part = format!("{}.part", file.filename);
+    let part_path = storage.path(id, &part);
+
+    storage.write(id, &part, buf)?;
+
+    let image = ImageInfo {
+        id,
+        filename: &file.filename,
+        source_url: &file.source_url,
+        is_thumbnail: file.filename.starts_with("thumbnail."),
+    };
+
+    let r = hooks.run(&part_path, &image).and_then(|_| {
+        if file.is_same(&storage.read(id, &part)?) {
+            Ok(())
+        } else {
+            Err(anyhow::Error::msg(format!(
+                "{}: {} is different from the manifest even at {}",
+                id, file.filename, file.source_url
+            )))
+        }
+    });
+
+    if let Err(err) = r {
+        let _ = fs::remove_file(&part_path);
+        return Err(err);
+    }
+
+    fs::rename(&part_path, storage.path(id, &file.filename))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use std::path::Path;
+
+    use super::{
+        repair_file, verify_acknowledgement, verify_files, BadFile, Problem, UnconfirmedUpload,
+    };
+    use crate::manifest::{Checksum, ManifestFile};
+    use crate::postprocess::{ImageHook, ImageHooks, ImageInfo};
+    use crate::storage::Storage;
+
+    fn file(filename: &str, buf: &[u8]) -> ManifestFile {
+        ManifestFile::new(
+            filename.to_string(),
+            format!("image/library/1/{}", filename),
+            format!("https://aa.hitomi.la/images/{}", filename),
+            buf,
+        )
+    }
+
+    #[test]
+    fn verify_missing_and_corrupt_files() -> anyhow::Result<()> {
+        let root = env::temp_dir().join("madome_synchronizer_verify");
+        let _ = fs::remove_dir_all(&root);
+        let storage = Storage::new(&root);
+
+        storage.write(1, "1.jpg", b"one")?;
+        storage.write(1, "2.jpg", b"tow")?;
+
+        let files = vec![
+            file("1.jpg", b"one"),
+            file("2.jpg", b"two"),
+            file("3.jpg", b"three"),
+        ];
+
+        let bad_files = verify_files(&storage, 1, &files);
+
+        let expected = vec![
+            BadFile {
+                file: files[1].clone(),
+                problem: Problem::Corrupt,
+            },
+            BadFile {
+                file: files[2].clone(),
+                problem: Problem::Missing,
+            },
+        ];
+
+        assert_eq!(expected, bad_files);
+        assert_eq!(vec![1], storage.ids()?);
+
+        fs::remove_dir_all(&root)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_acknowledged_files() -> anyhow::Result<()> {
+        let files = vec![
+            file("1.jpg", b"one"),
+            file("2.jpg", b"two"),
+            file("3.jpg", b"three"),
+        ];
+
+        // the repository has a truncated 2.jpg and no 3.jpg
+        let acknowledged = vec![
+            Checksum::from(&files[0]),
+            Checksum::from(&file("2.jpg", b"tw")),
+        ];
+
+        let bad_files = verify_acknowledgement(&files, &acknowledged);
+
+        assert_eq!(
+            vec![(Problem::Corrupt, "2.jpg"), (Problem::Missing, "3.jpg")],
+            bad_files
+                .iter()
+                .map(|x| (x.problem, x.file.filename.as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "Upload of 1 isn't confirmed: Corrupt 2.jpg, Missing 3.jpg",
+            UnconfirmedUpload { id: 1, bad_files }.to_string()
+        );
+
+        let acknowledged = files.iter().map(Checksum::from).collect::<Vec<_>>();
+        assert!(verify_acknowledgement(&files, &acknowledged).is_empty());
+
+        Ok(())
+    }
+
+    /// Appends a byte, as a hook re-encoding images would change them
+    struct AppendByte;
+
+    impl ImageHook for AppendByte {
+        fn name(&self) -> &'static str {
+            "append"
+        }
+
+        fn process(&self, path: &Path, _image: &ImageInfo) -> anyhow::Result<()> {
+            let mut buf = fs::read(path)?;
+            buf.push(b'!');
+            fs::write(path, buf)?;
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn repair_after_hooks() -> anyhow::Result<()> {
+        let root = env::temp_dir().join("madome_synchronizer_repair");
+        let _ = fs::remove_dir_all(&root);
+        let storage = Storage::new(&root);
+
+        let mut hooks = ImageHooks::new();
+        hooks.register(Box::new(AppendByte));
+
+        // hashed after the hook when it was uploaded
+        let manifest_file = file("1.jpg", b"one!");
+        storage.write(1, "1.jpg", b"tow")?;
+
+        // as downloaded, different from the manifest without the hook
+        assert!(repair_file(&storage, 1, &manifest_file, b"one", &ImageHooks::new()).is_err());
+        assert_eq!(b"tow".to_vec(), storage.read(1, "1.jpg")?);
+        assert!(storage.read(1, "1.jpg.part").is_err());
+
+        repair_file(&storage, 1, &manifest_file, b"one", &hooks)?;
+
+        assert_eq!(b"one!".to_vec(), storage.read(1, "1.jpg")?);
+        assert!(verify_files(&storage, 1, &[manifest_file]).is_empty());
+
+        fs::remove_dir_all(&root)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+use std::io::Write;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow;
+use log::{error, info};
+use serde::Deserialize;
+use serde_json;
+
+use crate::http::{read_request, Request};
+use crate::pipeline::PriorityLane;
+
+/// Larger bodies are refused, a request is a single id
+const MAX_BODY_LEN: usize = 4096;
+
+const READ_TIMEOUT_SECS: u64 = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn new(status: u16, body: serde_json::Value) -> Self {
+        Self {
+            status,
+            body: body.to_string(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::new(status, serde_json::json!({ "error": message }))
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            202 => "Accepted",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            _ => "",
+        }
+    }
+
+    pub fn write_to(&self, w: &mut impl Write) -> anyhow::Result<()> {
+        write!(
+            w,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.reason(),
+            self.body.len(),
+            self.body
+        )?;
+        w.flush()?;
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct SyncRequest {
+    id: u32,
+}
+
+/// # Webhook
+/// `POST /sync {"id": 1744332}` pushes the gallery to the priority lane of the running daemon
+///
+/// With WEBHOOK_TOKEN, requests need `Authorization: Bearer <token>`
+pub struct Webhook {
+    lane: Arc<PriorityLane<u32>>,
+    token: Option<String>,
+}
+
+impl Webhook {
+    pub fn new(lane: Arc<PriorityLane<u32>>, token: Option<String>) -> Self {
+        Self { lane, token }
+    }
+
+    pub fn handle(&self, request: &Request) -> Response {
+        if request.path != "/sync" {
+            return Response::error(404, "only /sync");
+        }
+
+        if request.method != "POST" {
+            return Response::error(405, "only POST");
+        }
+
+        if let Some(token) = &self.token {
+            let bearer = request
+                .headers
+                .get("authorization")
+                .and_then(|x| x.strip_prefix("Bearer "));
+
+            if bearer != Some(token.as_str()) {
+                return Response::error(401, "invalid token");
+            }
+        }
+
+        let id = match serde_json::from_slice::<SyncRequest>(&request.body) {
+            Ok(SyncRequest { id }) if id > 0 => id,
+            Ok(_) => return Response::error(400, "invalid id"),
+            Err(err) => return Response::error(400, &err.to_string()),
+        };
+
+        let queued = self.lane.push(id);
+
+        info!(
+            "{}: Requested by webhook, {} in the priority lane",
+            id, queued
+        );
+
+        Response::new(202, serde_json::json!({ "id": id, "queued": queued }))
+    }
+
+    fn serve(&self, mut stream: TcpStream) -> anyhow::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(READ_TIMEOUT_SECS)))?;
+
+        let response = match read_request(&stream, Some(MAX_BODY_LEN)) {
+            Ok(request) => self.handle(&request),
+            Err(err) if err.to_string().contains("too large") => {
+                Response::error(413, &err.to_string())
+            }
+            Err(err) => Response::error(400, &err.to_string()),
+        };
+
+        response.write_to(&mut stream)
+    }
+
+    /// Listens on `addr` on its own thread, requests are handled one at a time
+    pub fn spawn(self, addr: &str) -> anyhow::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+
+        info!("Webhook listens on {}", addr);
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let r = stream
+                    .map_err(anyhow::Error::from)
+                    .and_then(|stream| self.serve(stream));
+
+                if let Err(err) = r {
+                    error!("Webhook: {}", err);
+                }
+            }
+        });
+
+        Ok(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::Arc;
+
+    use super::{Webhook, MAX_BODY_LEN};
+    use crate::http::{read_request, Request};
+    use crate::pipeline::PriorityLane;
+
+    fn request(raw: &str) -> anyhow::Result<Request> {
+        read_request(raw.as_bytes(), Some(MAX_BODY_LEN))
+    }
+
+    #[test]
+    fn push_requested_ids() -> anyhow::Result<()> {
+        let lane = Arc::new(PriorityLane::new());
+        let webhook = Webhook::new(Arc::clone(&lane), Some("s3cret-token".to_string()));
+
+        let sync = |authorization: &str, body: &str| -> anyhow::Result<u16> {
+            let raw = format!(
+                "POST /sync HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\n\r\n{}",
+                authorization,
+                body.len(),
+                body
+            );
+
+            Ok(webhook.handle(&request(&raw)?).status)
+        };
+
+        let bearer = "Authorization: Bearer s3cret-token\r\n";
+
+        assert_eq!(202, sync(bearer, r#"{"id": 1744332}"#)?);
+        assert_eq!(401, sync("", r#"{"id": 1744332}"#)?);
+        assert_eq!(401, sync("Authorization: Bearer x\r\n", r#"{"id": 1}"#)?);
+        assert_eq!(400, sync(bearer, r#"{"id": "1744332"}"#)?);
+        assert_eq!(400, sync(bearer, r#"{"id": 0}"#)?);
+
+        let get = request("GET /sync HTTP/1.1\r\n\r\n")?;
+        let other = request("POST /galleries HTTP/1.1\r\n\r\n")?;
+
+        assert_eq!(405, webhook.handle(&get).status);
+        assert_eq!(404, webhook.handle(&other).status);
+
+        assert_eq!(Some(1744332), lane.pop());
+        assert!(lane.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn listen_on_socket() -> anyhow::Result<()> {
+        let lane = Arc::new(PriorityLane::new());
+        let addr = Webhook::new(Arc::clone(&lane), None).spawn("127.0.0.1:0")?;
+
+        let body = r#"{"id": 1744332}"#;
+        let mut stream = TcpStream::connect(addr)?;
+        write!(
+            stream,
+            "POST /sync HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+
+        assert!(response.starts_with("HTTP/1.1 202 Accepted\r\n"));
+        assert!(response.ends_with(r#"{"id":1744332,"queued":1}"#));
+        assert_eq!(Some(1744332), lane.pop());
+
+        Ok(())
+    }
+}
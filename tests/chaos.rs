@@ -0,0 +1,103 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow;
+
+use madome_synchronizer::chaos::{Chaos, ChaosConfig};
+use madome_synchronizer::exit::{ExitCode, Failure};
+use madome_synchronizer::models::{Metadata, MetadataBook};
+use madome_synchronizer::parser::{GalleryInfo, MetadataSource, Parser, ParserRegistry};
+use madome_synchronizer::pipeline;
+use madome_synchronizer::policy::{classify, ErrorClass, ErrorPolicy, Policy};
+
+const GALLERIES: u32 = 500;
+
+fn fixture(id: u32) -> String {
+    format!(
+        r#"{{"id": {}, "title": "Gallery {}", "type": "manga", "language": "korean", "files": []}}"#,
+        id, id
+    )
+}
+
+/// Gallery info of `fixture` fetched through chaos
+struct ChaosSource(Arc<Chaos>);
+
+impl MetadataSource for ChaosSource {
+    fn name(&self) -> &'static str {
+        "chaos"
+    }
+
+    fn fetch(&self, id: u32) -> anyhow::Result<MetadataBook> {
+        let body = self.0.fetch(|| Ok(fixture(id)))?;
+
+        Ok(GalleryInfo::new(id)
+            .with_request_data(body)
+            .parse()?
+            .to_metadata_book())
+    }
+}
+
+#[test]
+fn synchronize_under_fault_injection() -> anyhow::Result<()> {
+    let chaos = Arc::new(Chaos::new(ChaosConfig::uniform(0.1, 1724122)));
+
+    let mut registry = ParserRegistry::new();
+    registry.register(Box::new(ChaosSource(Arc::clone(&chaos))));
+
+    let error_policy = ErrorPolicy::default();
+    let failures = Mutex::new(vec![]);
+    // what the policy routed each failure to, as `route_failure` of the binary does
+    let retried = Mutex::new(vec![]);
+    let quarantined = Mutex::new(vec![]);
+    let wrong = Mutex::new(vec![]);
+
+    let done = pipeline::bounded(1..=GALLERIES, 16, 8, |id| match registry.fetch(id) {
+        Ok(book) => {
+            if book.title != Metadata::Title(Some(format!("Gallery {}", id))) {
+                wrong.lock().unwrap().push(id);
+            }
+            true
+        }
+        Err(err) => {
+            let class = classify(&err);
+
+            match error_policy.policy(&err) {
+                Policy::Retry | Policy::Backoff => {
+                    retried.lock().unwrap().push((class, err.to_string()))
+                }
+                Policy::Quarantine => quarantined.lock().unwrap().push((class, err.to_string())),
+                // a fault isn't a deleted gallery
+                Policy::Skip => wrong.lock().unwrap().push(id),
+            }
+            failures.lock().unwrap().push(Failure::new(id, &err));
+            false
+        }
+    });
+
+    let failures = failures.into_inner().unwrap();
+    let retried = retried.into_inner().unwrap();
+    let quarantined = quarantined.into_inner().unwrap();
+
+    assert!(wrong.into_inner().unwrap().is_empty());
+    assert_eq!(GALLERIES as usize, done + failures.len());
+    assert_eq!(chaos.injected(), failures.len());
+    assert!(chaos.injected() > 0);
+    assert!(chaos.delayed() > 0);
+
+    // failed requests are retried, truncated and malformed bodies are quarantined
+    assert!(!retried.is_empty());
+    assert!(retried
+        .iter()
+        .all(|(class, err)| *class == ErrorClass::Other && err == "Injected failure"));
+    assert!(!quarantined.is_empty());
+    assert!(quarantined
+        .iter()
+        .all(|(class, _)| *class == ErrorClass::Parse));
+    assert_eq!(failures.len(), retried.len() + quarantined.len());
+
+    assert_eq!(
+        ExitCode::PartialFailure,
+        ExitCode::of(&failures, done as u64)
+    );
+
+    Ok(())
+}